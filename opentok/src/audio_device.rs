@@ -13,6 +13,8 @@
 /// a mix of all participants' audio streams. Check
 /// <https://github.com/opentok/opentok-linux-sdk-samples/issues/25>
 use crate::enums::{IntoResult, OtcBool, OtcResult};
+#[cfg(feature = "mock")]
+use crate::mock::ffi;
 
 use lazy_static::lazy_static;
 use log::warn;
@@ -22,7 +24,11 @@ use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
 lazy_static! {
-    static ref SINGLETON: Arc<Mutex<AudioDevice>> = Arc::new(Mutex::new(AudioDevice::new()));
+    /// The device the SDK calls back into. Its state is shared with the one
+    /// behind `SINGLETON`, but the callbacks do not need to take the lock
+    /// the application may be holding.
+    static ref DEVICE: AudioDevice = AudioDevice::new();
+    static ref SINGLETON: Arc<Mutex<AudioDevice>> = Arc::new(Mutex::new(DEVICE.clone()));
 }
 
 #[derive(Error, Debug)]
@@ -46,15 +52,10 @@ unsafe extern "C" fn get_capture_settings(
     if settings.is_null() {
         return false.into();
     }
-    if let Ok(singleton) = SINGLETON.try_lock() {
-        let capture_settings = singleton.capture_settings();
-        (*settings).sampling_rate = capture_settings.sampling_rate;
-        (*settings).number_of_channels = capture_settings.number_of_channels;
-        true
-    } else {
-        false
-    }
-    .into()
+    let capture_settings = DEVICE.capture_settings();
+    (*settings).sampling_rate = capture_settings.sampling_rate;
+    (*settings).number_of_channels = capture_settings.number_of_channels;
+    true.into()
 }
 
 unsafe extern "C" fn get_render_settings(
@@ -65,15 +66,10 @@ unsafe extern "C" fn get_render_settings(
     if settings.is_null() {
         return false.into();
     }
-    if let Ok(singleton) = SINGLETON.try_lock() {
-        let render_settings = singleton.render_settings();
-        (*settings).sampling_rate = render_settings.sampling_rate;
-        (*settings).number_of_channels = render_settings.number_of_channels;
-        true
-    } else {
-        false
-    }
-    .into()
+    let render_settings = DEVICE.render_settings();
+    (*settings).sampling_rate = render_settings.sampling_rate;
+    (*settings).number_of_channels = render_settings.number_of_channels;
+    true.into()
 }
 
 /// Raw data holder for audio samples.
@@ -234,14 +230,12 @@ impl AudioDevice {
             if data.is_empty() {
                 continue;
             }
-            if let Ok(callbacks) = on_audio_sample_callbacks.try_lock() {
-                for ref callback in callbacks.iter() {
-                    callback(AudioSample {
-                        data: AudioSampleData(data.clone()),
-                        sampling_rate,
-                        number_of_channels,
-                    });
-                }
+            for callback in on_audio_sample_callbacks.lock().unwrap().iter() {
+                callback(AudioSample {
+                    data: AudioSampleData(data.clone()),
+                    sampling_rate,
+                    number_of_channels,
+                });
             }
             std::thread::sleep(std::time::Duration::from_micros(10000));
         });
@@ -1,19 +1,122 @@
+#[cfg(feature = "mock")]
+use crate::mock::ffi;
+use std::collections::HashMap;
+use std::ffi::CStr;
 use std::sync::atomic::{AtomicPtr, Ordering};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ConnectionDataError {
+    #[error("Connection data is not a valid JSON object: {0}")]
+    InvalidJson(String),
+}
+
+/// Parses connection data into a map of keys and values.
+///
+/// Two formats are supported:
+/// * `key=value` pairs separated by `&`, as in URL query strings. Keys and values
+///   are percent-decoded and a pair without `=` maps to an empty value.
+/// * A JSON object, if the `serde_json` feature is enabled. String values are
+///   stored as is and any other value is stored as its JSON representation.
+///
+/// Empty data results in an empty map.
+pub fn parse_connection_data(data: &str) -> Result<HashMap<String, String>, ConnectionDataError> {
+    let data = data.trim();
+    if data.starts_with('{') {
+        return parse_json(data);
+    }
+
+    Ok(data
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut split = pair.splitn(2, '=');
+            let key = split.next().unwrap_or_default();
+            let value = split.next().unwrap_or_default();
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect())
+}
+
+#[cfg(feature = "serde_json")]
+fn parse_json(data: &str) -> Result<HashMap<String, String>, ConnectionDataError> {
+    let object: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(data).map_err(|e| ConnectionDataError::InvalidJson(e.to_string()))?;
+    Ok(object
+        .into_iter()
+        .map(|(key, value)| match value {
+            serde_json::Value::String(value) => (key, value),
+            value => (key, value.to_string()),
+        })
+        .collect())
+}
+
+#[cfg(not(feature = "serde_json"))]
+fn parse_json(_: &str) -> Result<HashMap<String, String>, ConnectionDataError> {
+    Err(ConnectionDataError::InvalidJson(
+        "JSON support requires the `serde_json` feature".into(),
+    ))
+}
+
+fn percent_decode(input: &str) -> String {
+    let hex = |byte: u8| (byte as char).to_digit(16).map(|digit| digit as u8);
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                (Some(high), Some(low)) => {
+                    decoded.push(high << 4 | low);
+                    i += 2;
+                }
+                _ => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
 
 pub struct Connection {
     ptr: AtomicPtr<*const ffi::otc_connection>,
 }
 
 impl Connection {
-    /*string_getter!(
+    pub fn inner(&self) -> *const ffi::otc_connection {
+        self.ptr.load(Ordering::Relaxed) as *const _
+    }
+
+    string_getter!(
         /// Returns the unique identifier for this connection.
-        => (get_id, otc_connection_get_id)
+        => (id, otc_connection_get_id)
     );
 
     string_getter!(
         /// Returns the session ID associated with this connection.
-        => (get_session_id, otc_connection_get_session_id)
-    );*/
+        => (session_id, otc_connection_get_session_id)
+    );
+
+    /// Returns the data associated with this connection. This data is set
+    /// when generating the token used to connect to the session.
+    /// It is empty if the token carries no data.
+    pub fn data(&self) -> String {
+        let data = unsafe { ffi::otc_connection_get_data(self.inner()) };
+        if data.is_null() {
+            return String::new();
+        }
+        unsafe { CStr::from_ptr(data) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Returns the connection data parsed into a map of keys and values.
+    /// See `parse_connection_data` for the supported formats.
+    pub fn parsed_data(&self) -> Result<HashMap<String, String>, ConnectionDataError> {
+        parse_connection_data(&self.data())
+    }
 
     /// Returns the timestamp corresponding with the creation of the OpenTok
     /// session.
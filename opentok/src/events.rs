@@ -0,0 +1,128 @@
+//! Event stream support.
+//!
+//! With the `futures` feature enabled, sessions, publishers and subscribers
+//! can hand out streams of events in addition to the callbacks passed on
+//! construction. See `Session::events`, `Publisher::events` and
+//! `Subscriber::events`.
+
+#[cfg(feature = "futures")]
+use crate::enums::OtcError;
+#[cfg(feature = "futures")]
+use crate::publisher::PublisherError;
+#[cfg(feature = "futures")]
+use crate::session::SessionError;
+#[cfg(feature = "futures")]
+use crate::subscriber::SubscriberError;
+
+#[cfg(feature = "futures")]
+use futures::channel::mpsc::{self, Receiver, Sender, UnboundedReceiver, UnboundedSender};
+#[cfg(feature = "futures")]
+use futures::stream::{self, Select};
+#[cfg(not(feature = "futures"))]
+use std::marker::PhantomData;
+#[cfg(feature = "futures")]
+use std::sync::Mutex;
+#[cfg(feature = "futures")]
+use thiserror::Error;
+
+/// Errors returned by the asynchronous `Session` methods.
+#[cfg(feature = "futures")]
+#[derive(Clone, Debug, Error)]
+pub enum AsyncError {
+    /// The operation could not be started.
+    #[error(transparent)]
+    Otc(#[from] OtcError),
+    /// The session reported an error before the operation completed.
+    #[error(transparent)]
+    Session(#[from] SessionError),
+    /// The publisher reported an error before the operation completed.
+    #[error("Publisher error: {0:?}")]
+    Publisher(PublisherError),
+    /// The subscriber reported an error before the operation completed.
+    #[error("Subscriber error: {0:?}")]
+    Subscriber(SubscriberError),
+    /// The session, publisher or subscriber was disconnected before the
+    /// operation completed.
+    #[error("Disconnected before the operation completed")]
+    Disconnected,
+}
+
+/// Number of lossy events, such as rendered frames, a receiver can have
+/// pending. Further ones are dropped for that receiver until it catches up.
+#[cfg(feature = "futures")]
+pub const LOSSY_EVENT_QUEUE_LENGTH: usize = 4;
+
+/// Set of channels events are broadcast to.
+///
+/// Without the `futures` feature this is empty and emitting is a no-op.
+pub(crate) struct EventSenders<T> {
+    #[cfg(feature = "futures")]
+    senders: Mutex<Vec<UnboundedSender<T>>>,
+    #[cfg(feature = "futures")]
+    lossy_senders: Mutex<Vec<Sender<T>>>,
+    #[cfg(not(feature = "futures"))]
+    _event: PhantomData<T>,
+}
+
+impl<T> Default for EventSenders<T> {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "futures")]
+            senders: Default::default(),
+            #[cfg(feature = "futures")]
+            lossy_senders: Default::default(),
+            #[cfg(not(feature = "futures"))]
+            _event: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<T: Clone> EventSenders<T> {
+    /// Registers a new receiver for all events emitted from now on. Lossy
+    /// events are not ordered with respect to the others.
+    pub fn subscribe(&self) -> Select<UnboundedReceiver<T>, Receiver<T>> {
+        let (sender, receiver) = mpsc::unbounded();
+        // The capacity of a bounded channel grows by one per sender.
+        let (lossy_sender, lossy_receiver) = mpsc::channel(LOSSY_EVENT_QUEUE_LENGTH - 1);
+        self.senders.lock().unwrap().push(sender);
+        self.lossy_senders.lock().unwrap().push(lossy_sender);
+        stream::select(receiver, lossy_receiver)
+    }
+
+    /// Sends an event to every receiver, dropping the ones that are gone.
+    /// The event is only built if there is at least one receiver.
+    pub fn emit<F: FnOnce() -> T>(&self, event: F) {
+        let mut senders = self.senders.lock().unwrap();
+        if senders.is_empty() {
+            return;
+        }
+        let event = event();
+        senders.retain(|sender| sender.unbounded_send(event.clone()).is_ok());
+    }
+
+    /// Sends an event to every receiver that has room for it, dropping the
+    /// receivers that are gone. It is meant for frequent events a slow
+    /// receiver can miss, so that its queue does not grow without bound.
+    pub fn emit_lossy<F: FnOnce() -> T>(&self, event: F) {
+        let mut senders = self.lossy_senders.lock().unwrap();
+        if senders.is_empty() {
+            return;
+        }
+        let event = event();
+        *senders = std::mem::take(&mut *senders)
+            .into_iter()
+            .filter_map(|mut sender| match sender.try_send(event.clone()) {
+                Err(error) if error.is_disconnected() => None,
+                _ => Some(sender),
+            })
+            .collect();
+    }
+}
+
+#[cfg(not(feature = "futures"))]
+impl<T> EventSenders<T> {
+    pub fn emit<F: FnOnce() -> T>(&self, _: F) {}
+
+    pub fn emit_lossy<F: FnOnce() -> T>(&self, _: F) {}
+}
@@ -9,17 +9,43 @@ mod macros;
 pub mod connection;
 pub mod audio_device;
 mod enums;
+pub mod events;
 pub mod log;
+#[cfg(feature = "mock")]
+pub mod mock;
 pub mod publisher;
+pub mod reconnect;
+pub mod roster;
+pub mod rtc_stats;
 pub mod session;
 pub mod stream;
 pub mod subscriber;
+mod user_data;
 pub mod video_capturer;
 pub mod video_frame;
+pub mod video_source;
 
 pub use crate::enums::{IntoResult, OtcError, OtcResult};
 
+#[cfg(feature = "mock")]
+use crate::mock::ffi;
+
 use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static LATE_EVENTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of events that the SDK delivered for a session,
+/// publisher, subscriber or video capturer that no longer exists. These
+/// events are discarded. A growing count usually means objects are dropped
+/// while the SDK is still using them.
+pub fn late_event_count() -> usize {
+    LATE_EVENTS.load(Ordering::Relaxed)
+}
+
+pub(crate) fn count_late_event() {
+    LATE_EVENTS.fetch_add(1, Ordering::Relaxed);
+}
 
 /// Initializes the library. You must call this function before
 /// the execution of any other code using the library.
@@ -31,13 +57,13 @@ pub fn init() -> OtcResult {
 /// executing code that uses the library.
 pub fn deinit() -> OtcResult {
     audio_device::AudioDevice::stop();
-    for (_, publisher) in publisher::INSTANCES.lock().unwrap().drain() {
+    for publisher in publisher::LIVE.clones() {
         let _ = publisher.unpublish();
     }
-    for (_, subscriber) in subscriber::INSTANCES.lock().unwrap().drain() {
+    for subscriber in subscriber::LIVE.clones() {
         let _ = subscriber.unsubscribe();
     }
-    for (_, session) in session::INSTANCES.lock().unwrap().drain() {
+    for session in session::LIVE.clones() {
         let _ = session.disconnect();
     }
     unsafe { ffi::otc_destroy() }.into_result()
@@ -1,4 +1,6 @@
 use lazy_static::lazy_static;
+use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::ffi::CStr;
 use std::sync::{Arc, Mutex};
 
@@ -74,15 +76,40 @@ lazy_static! {
     pub static ref LOGGER_CALLBACKS: Arc<Mutex<Vec<LoggerCallback>>> = Default::default();
 }
 
+thread_local! {
+    /// Messages logged on this thread while it runs the logger callbacks,
+    /// or `None` if it is not running them.
+    static PENDING: RefCell<Option<VecDeque<String>>> = const { RefCell::new(None) };
+}
+
 unsafe extern "C" fn ffi_logger_callback(message: *const ::std::os::raw::c_char) {
     let message: &CStr = CStr::from_ptr(message);
-    if let Ok(message) = message.to_str() {
-        if let Ok(callbacks) = LOGGER_CALLBACKS.try_lock() {
-            for ref callback in callbacks.iter() {
-                callback(message);
-            }
+    let message = match message.to_str() {
+        Ok(message) => message.to_owned(),
+        Err(_) => return,
+    };
+    // A callback may make the SDK log again. Those messages are queued and
+    // delivered once it returns, instead of locking the callbacks again.
+    let nested = PENDING.with(|pending| match *pending.borrow_mut() {
+        Some(ref mut pending) => {
+            pending.push_back(message.clone());
+            true
+        }
+        None => false,
+    });
+    if nested {
+        return;
+    }
+    PENDING.with(|pending| *pending.borrow_mut() = Some(vec![message].into()));
+    let callbacks = LOGGER_CALLBACKS.lock().unwrap();
+    while let Some(message) =
+        PENDING.with(|pending| pending.borrow_mut().as_mut().and_then(VecDeque::pop_front))
+    {
+        for callback in callbacks.iter() {
+            callback(&message);
         }
     }
+    PENDING.with(|pending| *pending.borrow_mut() = None);
 }
 
 pub fn logger_callback(callback: LoggerCallback) {
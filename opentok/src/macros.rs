@@ -1,82 +1,74 @@
 macro_rules! ffi_callback {
     ($fn_name:ident, $target_type:ty, $target_rust_type:ty) => {
-        extern "C" fn $fn_name(target: $target_type, _: *mut c_void) {
-            if let Ok(ptr) = INSTANCES.try_lock() {
-                if let Some(target) = ptr.get(&(target as usize)) {
-                    target.$fn_name();
-                }
+        extern "C" fn $fn_name(_: $target_type, user_data: *mut c_void) {
+            match unsafe { crate::user_data::target::<$target_rust_type>(user_data) } {
+                Some(target) => target.$fn_name(),
+                None => crate::count_late_event(),
             }
         }
     };
     ($fn_name:ident, $target_type:ty, $target_rust_type:ty, $arg1_ty1:ty) => {
-        unsafe extern "C" fn $fn_name(target: $target_type, _: *mut c_void, arg1: $arg1_ty1) {
-            if let Ok(ptr) = INSTANCES.try_lock() {
-                if let Some(target) = ptr.get(&(target as usize)) {
-                    target.$fn_name(arg1.into());
-                }
+        unsafe extern "C" fn $fn_name(_: $target_type, user_data: *mut c_void, arg1: $arg1_ty1) {
+            match crate::user_data::target::<$target_rust_type>(user_data) {
+                Some(target) => target.$fn_name(arg1.into()),
+                None => crate::count_late_event(),
             }
         }
     };
     ($fn_name:ident, $target_type:ty, $target_rust_type:ty, $arg1_ty:ty, $arg2_ty:ty) => {
         unsafe extern "C" fn $fn_name(
-            target: $target_type,
-            _: *mut c_void,
+            _: $target_type,
+            user_data: *mut c_void,
             arg1: $arg1_ty,
             arg2: $arg2_ty,
         ) {
-            if let Ok(ptr) = INSTANCES.try_lock() {
-                if let Some(target) = ptr.get(&(target as usize)) {
-                    target.$fn_name(arg1.into(), arg2.into());
-                }
+            match crate::user_data::target::<$target_rust_type>(user_data) {
+                Some(target) => target.$fn_name(arg1.into(), arg2.into()),
+                None => crate::count_late_event(),
             }
         }
     };
     ($fn_name:ident, $target_type:ty, $target_rust_type:ty, $arg1_ty:ty, $arg2_ty:ty, $arg3_ty:ty) => {
         unsafe extern "C" fn $fn_name(
-            target: $target_type,
-            _: *mut c_void,
+            _: $target_type,
+            user_data: *mut c_void,
             arg1: $arg1_ty,
             arg2: $arg2_ty,
             arg3: $arg3_ty,
         ) {
-            if let Ok(ptr) = INSTANCES.try_lock() {
-                if let Some(target) = ptr.get(&(target as usize)) {
-                    target.$fn_name(arg1.into(), arg2.into(), arg3.into());
-                }
+            match crate::user_data::target::<$target_rust_type>(user_data) {
+                Some(target) => target.$fn_name(arg1.into(), arg2.into(), arg3.into()),
+                None => crate::count_late_event(),
             }
         }
     };
 }
 
 macro_rules! ffi_callback_with_return_user_data {
-    ($fn_name:ident, $target_type:ty, $return_type:ty) => {
+    ($fn_name:ident, $target_type:ty, $target_rust_type:ty, $return_type:ty) => {
         unsafe extern "C" fn $fn_name(_: $target_type, user_data: *mut c_void) -> $return_type {
-            let result: OtcBool = if let Ok(ptr) = INSTANCES.try_lock() {
-                if let Some(target) = ptr.get(&(user_data as usize)) {
-                    target.$fn_name().into()
-                } else {
+            let result: OtcBool = match crate::user_data::target::<$target_rust_type>(user_data) {
+                Some(target) => target.$fn_name().into(),
+                None => {
+                    crate::count_late_event();
                     false.into()
                 }
-            } else {
-                false.into()
             };
             result.0
         }
     };
-    ($fn_name:ident, $target_type:ty, $arg1_type:ty, $return_type:ty) => {
+    ($fn_name:ident, $target_type:ty, $target_rust_type:ty, $arg1_type:ty, $return_type:ty) => {
         unsafe extern "C" fn $fn_name(
             _: $target_type,
             user_data: *mut c_void,
             arg1: $arg1_type,
         ) -> $return_type {
-            let result: OtcBool = if let Ok(ptr) = INSTANCES.try_lock() {
-                if let Some(target) = ptr.get(&(user_data as usize)) {
-                    target.$fn_name(arg1).into()
-                } else {
+            let result: OtcBool = match crate::user_data::target::<$target_rust_type>(user_data) {
+                Some(target) => target.$fn_name(arg1).into(),
+                None => {
+                    crate::count_late_event();
                     false.into()
                 }
-            } else {
-                false.into()
             };
             result.0
         }
@@ -86,11 +78,7 @@ macro_rules! ffi_callback_with_return_user_data {
 macro_rules! ffi_callback_with_return_singleton {
     ($fn_name:ident, $target_type:ty, $return_type:ty) => {
         unsafe extern "C" fn $fn_name(_: $target_type, _: *mut c_void) -> $return_type {
-            let result: OtcBool = if let Ok(singleton) = SINGLETON.try_lock() {
-                singleton.$fn_name().into()
-            } else {
-                false.into()
-            };
+            let result: OtcBool = DEVICE.$fn_name().into();
             result.0
         }
     };
@@ -100,11 +88,7 @@ macro_rules! ffi_callback_with_return_singleton {
             user_data: *mut c_void,
             arg1: $arg1_type,
         ) -> $return_type {
-            let result: OtcBool = if let Ok(singleton) = SINGLETON.try_lock() {
-                singleton.$fn_name(arg1).into()
-            } else {
-                false.into()
-            };
+            let result: OtcBool = DEVICE.$fn_name(arg1).into();
             result.0
         }
     };
@@ -284,32 +268,30 @@ macro_rules! callback_setter_with_return {
 }
 
 macro_rules! callback_call {
-    ($fn_name:ident) => {
+    ($fn_name:ident => $event:path) => {
         fn $fn_name(&self) {
-            if let Ok(callbacks) = self.callbacks.try_lock() {
-                callbacks.$fn_name(self);
-            }
+            self.events.emit(|| $event);
+            self.callbacks.$fn_name(self);
         }
     };
-    ($fn_name:ident, $ty1:ty) => {
+    ($fn_name:ident, $ty1:ty => $event:path) => {
         fn $fn_name(&self, arg1: $ty1) {
-            if let Ok(callbacks) = self.callbacks.try_lock() {
-                callbacks.$fn_name(self, arg1.into());
-            }
+            self.events.emit(|| $event(arg1.into()));
+            self.callbacks.$fn_name(self, arg1.into());
         }
     };
-    ($fn_name:ident, $ty1:ty, $ty2:ty) => {
+    ($fn_name:ident, $ty1:ty, $ty2:ty => $event:path) => {
         fn $fn_name(&self, arg1: $ty1, arg2: $ty2) {
-            if let Ok(callbacks) = self.callbacks.try_lock() {
-                callbacks.$fn_name(self, arg1.into(), arg2.into());
-            }
+            self.events.emit(|| $event(arg1.into(), arg2.into()));
+            self.callbacks.$fn_name(self, arg1.into(), arg2.into());
         }
     };
-    ($fn_name:ident, $ty1:ty, $ty2:ty, $ty3:ty) => {
+    ($fn_name:ident, $ty1:ty, $ty2:ty, $ty3:ty => $event:path) => {
         fn $fn_name(&self, arg1: $ty1, arg2: $ty2, arg3: $ty3) {
-            if let Ok(callbacks) = self.callbacks.try_lock() {
-                callbacks.$fn_name(self, arg1.into(), arg2.into(), arg3.into());
-            }
+            self.events
+                .emit(|| $event(arg1.into(), arg2.into(), arg3.into()));
+            self.callbacks
+                .$fn_name(self, arg1.into(), arg2.into(), arg3.into());
         }
     };
 }
@@ -317,27 +299,22 @@ macro_rules! callback_call {
 macro_rules! callback_call_with_return {
     ($fn_name:ident, $ret:ty) => {
         fn $fn_name(&self) -> $ret {
-            self.callbacks.lock().unwrap().$fn_name(self)
+            self.callbacks.$fn_name(self)
         }
     };
     ($fn_name:ident, $ty1:ty, $ret:ty) => {
         fn $fn_name(&self, arg1: $ty1) -> $ret {
-            self.callbacks.lock().unwrap().$fn_name(self, arg1.into())
+            self.callbacks.$fn_name(self, arg1.into())
         }
     };
     ($fn_name:ident, $ty1:ty, $ty2:ty, $ret:ty) => {
         fn $fn_name(&self, arg1: $ty1, arg2: $ty2) -> $ret {
-            self.callbacks
-                .lock()
-                .unwrap()
-                .$fn_name(self, arg1.into(), arg2.into())
+            self.callbacks.$fn_name(self, arg1.into(), arg2.into())
         }
     };
     ($fn_name:ident, $ty1:ty, $ty2:ty, $ty3:ty, $ret:ty) => {
         fn $fn_name(&self, arg1: $ty1, arg2: $ty2, arg3: $ty3) -> $ret {
             self.callbacks
-                .lock()
-                .unwrap()
                 .$fn_name(self, arg1.into(), arg2.into(), arg3.into())
         }
     };
@@ -0,0 +1,1419 @@
+//! In-process fake of the OpenTok SDK, for tests without network access.
+//!
+//! With the `mock` feature, sessions, publishers and subscribers run against
+//! this module instead of the OpenTok servers. Sessions created with the same
+//! session ID share a room: they see each other's connections and streams,
+//! get each other's signals, and subscribers receive the video frames of the
+//! publishers they subscribe to. Audio pushed to the `AudioDevice` is played
+//! back on its render side while a subscriber listens to a stream with audio.
+//! API keys and tokens are ignored.
+//!
+//! As with the SDK, callbacks are delivered asynchronously, from a single
+//! thread and in the order the fake server produced them. `flush` waits until
+//! every pending callback has been delivered, so tests can check the outcome
+//! of a call deterministically.
+//!
+//! The functions of this module play the part of the OpenTok servers, for
+//! instance to start an archive or to reject connections, and a `Script`
+//! injects network faults at given times. Video frames and logging are still
+//! handled by the SDK library, so it is needed to build, but no OpenTok
+//! project nor network access are.
+
+/// Calls `$callback` of the ffi callbacks struct `$callbacks`, if set.
+macro_rules! invoke {
+    ($callbacks:expr, $callback:ident, $target:expr $(, $arg:expr)*) => {
+        if let Some(callback) = $callbacks.$callback {
+            #[allow(unused_unsafe)]
+            let _ = unsafe { callback($target, $callbacks.user_data $(, $arg)*) };
+        }
+    };
+}
+
+pub(crate) mod ffi;
+
+use crate::publisher::PublisherError;
+use crate::session::{Session, SessionError};
+use crate::subscriber::SubscriberError;
+
+use lazy_static::lazy_static;
+use log::warn;
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CString;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Work for the fake server, run on the dispatcher thread. It returns the
+/// callbacks to deliver, which are called once the state is unlocked.
+type Job = Box<dyn FnOnce(&mut World) -> Vec<Call> + Send>;
+type Call = Box<dyn FnOnce()>;
+
+lazy_static! {
+    static ref WORLD: Mutex<World> = Default::default();
+    static ref QUEUE: Mutex<Sender<Job>> = Mutex::new(spawn_dispatcher());
+    /// Held while callbacks are delivered, so that deleting an object waits
+    /// for the callbacks it may be receiving.
+    static ref DELIVERING: Mutex<()> = Mutex::new(());
+}
+
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    static IS_DISPATCHER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Largest number of audio samples waiting to be rendered. Older samples
+/// are dropped.
+const MAX_BUFFERED_SAMPLES: usize = 480_000;
+
+fn spawn_dispatcher() -> Sender<Job> {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    thread::Builder::new()
+        .name("opentok-mock".into())
+        .spawn(move || {
+            IS_DISPATCHER.with(|is_dispatcher| is_dispatcher.set(true));
+            for job in receiver {
+                let _delivering = DELIVERING.lock().unwrap_or_else(|e| e.into_inner());
+                let calls = job(&mut world());
+                for call in calls {
+                    if panic::catch_unwind(AssertUnwindSafe(call)).is_err() {
+                        warn!("Mock callback panicked");
+                    }
+                }
+                PENDING.fetch_sub(1, Ordering::SeqCst);
+            }
+        })
+        .expect("Could not spawn the mock dispatcher thread");
+    sender
+}
+
+fn world() -> MutexGuard<'static, World> {
+    WORLD.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn enqueue<F: FnOnce(&mut World) -> Vec<Call> + Send + 'static>(job: F) {
+    PENDING.fetch_add(1, Ordering::SeqCst);
+    let _ = QUEUE.lock().unwrap().send(Box::new(job));
+}
+
+fn is_dispatcher() -> bool {
+    IS_DISPATCHER.with(|is_dispatcher| is_dispatcher.get())
+}
+
+/// Waits for callbacks being delivered, unless called from one of them.
+fn delivering() -> Option<MutexGuard<'static, ()>> {
+    if is_dispatcher() {
+        return None;
+    }
+    Some(DELIVERING.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+fn call<F: FnOnce() + 'static>(f: F) -> Call {
+    Box::new(f)
+}
+
+fn next_id() -> usize {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A new pointer value for an SDK object. It is never dereferenced.
+fn new_handle() -> usize {
+    next_id() << 4
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn cstring(value: &str) -> CString {
+    CString::new(value.replace('\0', "")).unwrap()
+}
+
+/// Waits until every callback triggered so far has been delivered,
+/// including the callbacks triggered by these. This must not be called from
+/// a callback.
+pub fn flush() {
+    loop {
+        let (sender, receiver) = mpsc::channel();
+        enqueue(move |_| {
+            // Not counting this job.
+            let _ = sender.send(PENDING.load(Ordering::SeqCst) - 1);
+            vec![]
+        });
+        if receiver.recv().unwrap_or_default() == 0 {
+            break;
+        }
+    }
+}
+
+/// Starts archiving the session `session_id` and returns the archive ID. The
+/// clients connected to the session, and those connecting while the archive
+/// runs, get `on_archive_started`.
+pub fn start_archive(session_id: &str, name: &str) -> String {
+    let archive_id = format!("archive-{}", next_id());
+    let (session_id, id, name) = (session_id.to_owned(), cstring(&archive_id), cstring(name));
+    enqueue(move |w| {
+        w.room(&session_id)
+            .archives
+            .push((id.clone(), name.clone()));
+        w.connected(&session_id, 0)
+            .into_iter()
+            .map(|(handle, callbacks)| {
+                let (id, name) = (id.clone(), name.clone());
+                call(move || {
+                    invoke!(
+                        callbacks,
+                        on_archive_started,
+                        handle as *mut _,
+                        id.as_ptr(),
+                        name.as_ptr()
+                    )
+                })
+            })
+            .collect()
+    });
+    archive_id
+}
+
+/// Stops the archive `archive_id` of the session `session_id`. The clients
+/// connected to the session get `on_archive_stopped`.
+pub fn stop_archive(session_id: &str, archive_id: &str) {
+    let (session_id, id) = (session_id.to_owned(), cstring(archive_id));
+    enqueue(move |w| {
+        let archives = &mut w.room(&session_id).archives;
+        let running = archives.len();
+        archives.retain(|(archive, _)| *archive != id);
+        if archives.len() == running {
+            return vec![];
+        }
+        w.connected(&session_id, 0)
+            .into_iter()
+            .map(|(handle, callbacks)| {
+                let id = id.clone();
+                call(move || invoke!(callbacks, on_archive_stopped, handle as *mut _, id.as_ptr()))
+            })
+            .collect()
+    });
+}
+
+/// Makes the following connections to the session `session_id` fail with
+/// `error`, reported to `on_error`, or succeed again with `None`.
+pub fn set_connect_error(session_id: &str, error: Option<SessionError>) {
+    world().room(session_id).connect_error = error;
+}
+
+/// Makes the following attempts to publish to the session `session_id` fail
+/// with `error`, reported to the publisher's `on_error`, or succeed again
+/// with `None`.
+pub fn set_publish_error(session_id: &str, error: Option<PublisherError>) {
+    world().room(session_id).publish_error = error;
+}
+
+/// Makes the following attempts to subscribe to a stream of the session
+/// `session_id` fail with `error`, reported to the subscriber's `on_error`,
+/// or succeed again with `None`.
+pub fn set_subscribe_error(session_id: &str, error: Option<SubscriberError>) {
+    world().room(session_id).subscribe_error = error;
+}
+
+/// A fault inflicted by the fake server on a connected session, see
+/// `Script`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fault {
+    /// The connection of the session is interrupted and the session gets
+    /// `on_reconnection_started`. Until it is reconnected, the session misses
+    /// the events of its room.
+    ConnectionInterrupted,
+    /// The interrupted connection of the session is restored and the session
+    /// gets `on_reconnected`.
+    Reconnected,
+    /// The connection of the session is lost for good. Its streams are
+    /// dropped, its subscribers disconnected, and it gets `on_disconnected`
+    /// although it did not ask to disconnect.
+    Disconnected,
+    /// The session gets `on_error` with this error. Its connection is not
+    /// affected, so this is usually followed by `Disconnected`.
+    Error(SessionError),
+    /// The streams published to the session are dropped by the server. The
+    /// publishers get `on_stream_destroyed` and the other clients
+    /// `on_stream_dropped`.
+    StreamsDropped,
+    /// The publishers publishing to the session get `on_error` with this
+    /// error, and their streams are dropped.
+    PublisherError(PublisherError),
+}
+
+enum Step {
+    Fault(usize, Fault),
+    Stall(Duration),
+}
+
+/// A timeline of faults, to test how an application copes with an unreliable
+/// network.
+///
+/// Each step is played at its time, counted from the call to `play`. Steps at
+/// the same time are played in the order they were added. Faults targeting a
+/// session which is not connected, or which do not apply to its state, such as
+/// `Reconnected` for a session which is not reconnecting, are ignored.
+#[derive(Default)]
+pub struct Script {
+    steps: Vec<(Duration, Step)>,
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Inflicts `fault` on `session` at `time`.
+    pub fn at(mut self, time: Duration, session: &Session, fault: Fault) -> Self {
+        self.steps
+            .push((time, Step::Fault(session.inner() as usize, fault)));
+        self
+    }
+
+    /// Stops delivering callbacks at `time`, for `duration`. The callbacks
+    /// triggered meanwhile, by the script or by the application, are delivered
+    /// late. Deleting an SDK object waits for the stall to end.
+    pub fn stall(mut self, time: Duration, duration: Duration) -> Self {
+        self.steps.push((time, Step::Stall(duration)));
+        self
+    }
+
+    /// Plays the script and waits until every callback it triggered has been
+    /// delivered. This must not be called from a callback.
+    pub fn play(mut self) {
+        self.steps.sort_by_key(|(time, _)| *time);
+        let start = Instant::now();
+        for (time, step) in self.steps {
+            if let Some(wait) = time.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+            match step {
+                Step::Fault(session, fault) => enqueue(move |w| w.inflict(session, fault)),
+                Step::Stall(duration) => {
+                    enqueue(move |_| vec![call(move || thread::sleep(duration))])
+                }
+            }
+        }
+        flush();
+    }
+}
+
+fn session_error_code(error: SessionError) -> ffi::otc_session_error_code {
+    match error {
+        SessionError::AuthorizationFailure => {
+            ffi::otc_session_error_code_OTC_SESSION_AUTHORIZATION_FAILURE
+        }
+        SessionError::BlockedCountry => ffi::otc_session_error_code_OTC_SESSION_BLOCKED_COUNTRY,
+        SessionError::ConnectionDropped => {
+            ffi::otc_session_error_code_OTC_SESSION_CONNECTION_DROPPED
+        }
+        SessionError::ConnectionFailed => ffi::otc_session_error_code_OTC_SESSION_CONNECTION_FAILED,
+        SessionError::ConnectionLimitExceeded => {
+            ffi::otc_session_error_code_OTC_SESSION_CONNECTION_LIMIT_EXCEEDED
+        }
+        SessionError::ConnectionRefused => {
+            ffi::otc_session_error_code_OTC_SESSION_CONNECTION_REFUSED
+        }
+        SessionError::ConnectionTimedOut => {
+            ffi::otc_session_error_code_OTC_SESSION_CONNECTION_TIMED_OUT
+        }
+        SessionError::ForceUnpublishOrInvalidStream => {
+            ffi::otc_session_error_code_OTC_SESSION_FORCE_UNPUBLISH_OR_INVALID_STREAM
+        }
+        SessionError::IllegalState => ffi::otc_session_error_code_OTC_SESSION_ILLEGAL_STATE,
+        SessionError::InternalError | SessionError::__Unknown => {
+            ffi::otc_session_error_code_OTC_SESSION_INTERNAL_ERROR
+        }
+        SessionError::InvalidSession => ffi::otc_session_error_code_OTC_SESSION_INVALID_SESSION,
+        SessionError::InvalidSignalType => {
+            ffi::otc_session_error_code_OTC_SESSION_INVALID_SIGNAL_TYPE
+        }
+        SessionError::NotConnected => ffi::otc_session_error_code_OTC_SESSION_NOT_CONNECTED,
+        SessionError::NoMessagingServer => {
+            ffi::otc_session_error_code_OTC_SESSION_NO_MESSAGING_SERVER
+        }
+        SessionError::NullOrInvalidParameter => {
+            ffi::otc_session_error_code_OTC_SESSION_NULL_OR_INVALID_PARAMETER
+        }
+        SessionError::PublisherNotFound => {
+            ffi::otc_session_error_code_OTC_SESSION_PUBLISHER_NOT_FOUND
+        }
+        SessionError::SignalDataTooLong => {
+            ffi::otc_session_error_code_OTC_SESSION_SIGNAL_DATA_TOO_LONG
+        }
+        SessionError::SignalTypeTooLong => {
+            ffi::otc_session_error_code_OTC_SESSION_SIGNAL_TYPE_TOO_LONG
+        }
+        SessionError::StateFailed => ffi::otc_session_error_code_OTC_SESSION_STATE_FAILED,
+        SessionError::SubscriberNotFound => {
+            ffi::otc_session_error_code_OTC_SESSION_SUBSCRIBER_NOT_FOUND
+        }
+        SessionError::UnexpectedGetSessionInfoResponse => {
+            ffi::otc_session_error_code_OTC_SESSION_UNEXPECTED_GET_SESSION_INFO_REPONSE
+        }
+    }
+}
+
+fn publisher_error_code(error: PublisherError) -> ffi::otc_publisher_error_code {
+    match error {
+        PublisherError::Internal | PublisherError::__Unknown => {
+            ffi::otc_publisher_error_code_OTC_PUBLISHER_INTERNAL_ERROR
+        }
+        PublisherError::SessionDisconnected => {
+            ffi::otc_publisher_error_code_OTC_PUBLISHER_SESSION_DISCONNECTED
+        }
+        PublisherError::TimedOut => ffi::otc_publisher_error_code_OTC_PUBLISHER_TIMED_OUT,
+        PublisherError::UnableToPublish => {
+            ffi::otc_publisher_error_code_OTC_PUBLISHER_UNABLE_TO_PUBLISH
+        }
+        PublisherError::WebRtcError => ffi::otc_publisher_error_code_OTC_PUBLISHER_WEBRTC_ERROR,
+    }
+}
+
+fn subscriber_error_code(error: SubscriberError) -> ffi::otc_subscriber_error_code {
+    match error {
+        SubscriberError::Internal | SubscriberError::__Unknown => {
+            ffi::otc_subscriber_error_code_OTC_SUBSCRIBER_INTERNAL_ERROR
+        }
+        SubscriberError::SessionDisconnected => {
+            ffi::otc_subscriber_error_code_OTC_SUBSCRIBER_SESSION_DISCONNECTED
+        }
+        SubscriberError::ServerCannotFindStream => {
+            ffi::otc_subscriber_error_code_OTC_SUBSCRIBER_SERVER_CANNOT_FIND_STREAM
+        }
+        SubscriberError::StreamLimitExceeded => {
+            ffi::otc_subscriber_error_code_OTC_SUBSCRIBER_STREAM_LIMIT_EXCEEDED
+        }
+        SubscriberError::TimedOut => ffi::otc_subscriber_error_code_OTC_SUBSCRIBER_TIMED_OUT,
+        SubscriberError::WebRtcError => ffi::otc_subscriber_error_code_OTC_SUBSCRIBER_WEBRTC_ERROR,
+    }
+}
+
+fn stream_video_type(video_type: ffi::otc_publisher_video_type) -> ffi::otc_stream_video_type {
+    match video_type {
+        ffi::otc_publisher_video_type_OTC_PUBLISHER_VIDEO_TYPE_SCREEN => {
+            ffi::otc_stream_video_type_OTC_STREAM_VIDEO_TYPE_SCREEN
+        }
+        _ => ffi::otc_stream_video_type_OTC_STREAM_VIDEO_TYPE_CAMERA,
+    }
+}
+
+/// An SDK object handed out as a pointer to a boxed reference to its data,
+/// so that copies are distinct pointers sharing the same data.
+struct Handle<T>(*mut Arc<T>);
+
+unsafe impl<T: Send + Sync> Send for Handle<T> {}
+unsafe impl<T: Send + Sync> Sync for Handle<T> {}
+
+impl<T> Handle<T> {
+    fn new(data: &Arc<T>) -> Self {
+        Handle(Box::into_raw(Box::new(data.clone())))
+    }
+
+    fn as_ptr<U>(&self) -> *mut U {
+        self.0 as *mut U
+    }
+
+    fn into_ptr<U>(self) -> *mut U {
+        let ptr = self.0;
+        std::mem::forget(self);
+        ptr as *mut U
+    }
+
+    fn data(&self) -> &Arc<T> {
+        unsafe { &*self.0 }
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must be null or the pointer of a live handle.
+    unsafe fn get<'a, U>(ptr: *const U) -> Option<&'a Arc<T>> {
+        (ptr as *const Arc<T>).as_ref()
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must be null or a pointer returned by `into_ptr`.
+    unsafe fn delete<U>(ptr: *mut U) {
+        if !ptr.is_null() {
+            drop(Handle::<T>(ptr as *mut Arc<T>));
+        }
+    }
+}
+
+impl<T> Drop for Handle<T> {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.0) });
+    }
+}
+
+/// A copy of a frame provided by a video capturer.
+struct Frame(*mut ffi::otc_video_frame);
+
+unsafe impl Send for Frame {}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        unsafe { ffi::otc_video_frame_delete(self.0) };
+    }
+}
+
+struct ConnectionData {
+    id: CString,
+    session_id: CString,
+    data: CString,
+    creation_time: i64,
+}
+
+struct StreamData {
+    id: CString,
+    name: CString,
+    connection: Handle<ConnectionData>,
+    creation_time: i64,
+    has_audio_track: bool,
+    has_video_track: bool,
+    state: Mutex<StreamState>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct StreamState {
+    has_audio: bool,
+    has_video: bool,
+    width: i32,
+    height: i32,
+    video_type: ffi::otc_stream_video_type,
+}
+
+impl StreamData {
+    fn state(&self) -> StreamState {
+        *self.state.lock().unwrap()
+    }
+}
+
+#[derive(Default)]
+struct Room {
+    connect_error: Option<SessionError>,
+    publish_error: Option<PublisherError>,
+    subscribe_error: Option<SubscriberError>,
+    /// The ID and name of the running archives.
+    archives: Vec<(CString, CString)>,
+}
+
+#[derive(Default)]
+struct SessionSettings {
+    connection_events_suppressed: bool,
+}
+
+struct SessionState {
+    id: CString,
+    callbacks: ffi::otc_session_callbacks,
+    connection_events_suppressed: bool,
+    connection: Option<Handle<ConnectionData>>,
+    /// Whether the connection is interrupted. The session misses what
+    /// happens in its room meanwhile.
+    reconnecting: bool,
+}
+
+struct PublisherSettings {
+    name: CString,
+    capturer: Option<ffi::otc_video_capturer_callbacks>,
+    audio_track: bool,
+    video_track: bool,
+}
+
+impl Default for PublisherSettings {
+    fn default() -> Self {
+        PublisherSettings {
+            name: CString::default(),
+            capturer: None,
+            audio_track: true,
+            video_track: true,
+        }
+    }
+}
+
+struct PublisherState {
+    name: CString,
+    callbacks: ffi::otc_publisher_callbacks,
+    rtc_stats_report_cb: Option<ffi::otc_publisher_rtc_stats_report_cb>,
+    capturer: Option<(usize, ffi::otc_video_capturer_callbacks)>,
+    capturing: bool,
+    audio_track: bool,
+    video_track: bool,
+    publish_audio: bool,
+    publish_video: bool,
+    audio_fallback: bool,
+    max_audio_bitrate: u32,
+    video_type: ffi::otc_publisher_video_type,
+    width: i32,
+    height: i32,
+    session: Option<usize>,
+    stream: Option<Handle<StreamData>>,
+    frames: u64,
+}
+
+impl PublisherState {
+    fn new(callbacks: ffi::otc_publisher_callbacks, settings: PublisherSettings) -> Self {
+        PublisherState {
+            name: settings.name,
+            callbacks,
+            rtc_stats_report_cb: None,
+            capturer: settings.capturer.map(|capturer| (new_handle(), capturer)),
+            capturing: false,
+            audio_track: settings.audio_track,
+            video_track: settings.video_track,
+            publish_audio: true,
+            publish_video: true,
+            audio_fallback: false,
+            max_audio_bitrate: 0,
+            video_type: ffi::otc_publisher_video_type_OTC_PUBLISHER_VIDEO_TYPE_CAMERA,
+            width: 640,
+            height: 480,
+            session: None,
+            stream: None,
+            frames: 0,
+        }
+    }
+
+    /// The state of the published stream, as seen by other clients.
+    fn stream_state(&self) -> StreamState {
+        StreamState {
+            has_audio: self.audio_track && self.publish_audio,
+            has_video: self.video_track && self.publish_video,
+            width: self.width,
+            height: self.height,
+            video_type: stream_video_type(self.video_type),
+        }
+    }
+}
+
+struct SubscriberState {
+    stream: Arc<StreamData>,
+    callbacks: ffi::otc_subscriber_callbacks,
+    rtc_stats_report_cb: Option<ffi::otc_subscriber_rtc_stats_report_cb>,
+    session: Option<usize>,
+    connected: bool,
+    subscribe_to_audio: bool,
+    subscribe_to_video: bool,
+    preferred_resolution: (u32, u32),
+    preferred_framerate: f32,
+    frames: u64,
+}
+
+impl SubscriberState {
+    fn new(stream: Arc<StreamData>, callbacks: ffi::otc_subscriber_callbacks) -> Self {
+        SubscriberState {
+            stream,
+            callbacks,
+            rtc_stats_report_cb: None,
+            session: None,
+            connected: false,
+            subscribe_to_audio: true,
+            subscribe_to_video: true,
+            preferred_resolution: (0, 0),
+            preferred_framerate: 0.,
+            frames: 0,
+        }
+    }
+
+    fn receives(&self, stream: &Arc<StreamData>) -> bool {
+        self.connected && Arc::ptr_eq(&self.stream, stream)
+    }
+}
+
+#[derive(Default)]
+struct AudioState {
+    device: usize,
+    callbacks: Option<ffi::otc_audio_device_callbacks>,
+    capturing: bool,
+    rendering: bool,
+    samples: VecDeque<i16>,
+}
+
+/// The state of the fake server and of the SDK objects, which are keyed by
+/// their pointer.
+#[derive(Default)]
+struct World {
+    rooms: HashMap<String, Room>,
+    sessions: HashMap<usize, SessionState>,
+    publishers: HashMap<usize, PublisherState>,
+    subscribers: HashMap<usize, SubscriberState>,
+    audio: AudioState,
+}
+
+// The ffi callbacks structs hold raw pointers, which are only handed back to
+// the callbacks.
+unsafe impl Send for World {}
+unsafe impl Send for SessionState {}
+unsafe impl Send for PublisherState {}
+
+impl World {
+    fn room(&mut self, session_id: &str) -> &mut Room {
+        self.rooms.entry(session_id.to_owned()).or_default()
+    }
+
+    fn session_id(&self, session: usize) -> Option<String> {
+        self.sessions
+            .get(&session)
+            .map(|session| session.id.to_string_lossy().into_owned())
+    }
+
+    fn connection(&self, session: usize) -> Option<Arc<ConnectionData>> {
+        self.sessions
+            .get(&session)
+            .and_then(|session| session.connection.as_ref())
+            .map(|connection| connection.data().clone())
+    }
+
+    /// The sessions connected to the room `session_id`, but `except`.
+    fn connected(
+        &self,
+        session_id: &str,
+        except: usize,
+    ) -> Vec<(usize, ffi::otc_session_callbacks)> {
+        self.sessions
+            .iter()
+            .filter(|(handle, session)| {
+                **handle != except
+                    && session.connection.is_some()
+                    && !session.reconnecting
+                    && session.id.to_bytes() == session_id.as_bytes()
+            })
+            .map(|(handle, session)| (*handle, session.callbacks))
+            .collect()
+    }
+
+    /// The streams published to the room `session_id` by other sessions than
+    /// `except`.
+    fn streams(&self, session_id: &str, except: usize) -> Vec<Arc<StreamData>> {
+        self.publishers
+            .values()
+            .filter(|publisher| match publisher.session {
+                Some(session) => {
+                    session != except && self.session_id(session).as_deref() == Some(session_id)
+                }
+                None => false,
+            })
+            .filter_map(|publisher| publisher.stream.as_ref())
+            .map(|stream| stream.data().clone())
+            .collect()
+    }
+
+    /// The publishers publishing to `session`.
+    fn publishers_of(&self, session: usize) -> Vec<usize> {
+        self.publishers
+            .iter()
+            .filter(|(_, publisher)| publisher.session == Some(session))
+            .map(|(publisher, _)| *publisher)
+            .collect()
+    }
+
+    fn is_published(&self, stream: &Arc<StreamData>) -> bool {
+        self.publishers.values().any(|publisher| {
+            matches!(publisher.stream, Some(ref published) if Arc::ptr_eq(published.data(), stream))
+        })
+    }
+
+    fn connect(&mut self, session: usize) -> Vec<Call> {
+        let session_id = match self.session_id(session) {
+            Some(session_id) => session_id,
+            None => return vec![],
+        };
+        let state = &self.sessions[&session];
+        let (callbacks, suppressed) = (state.callbacks, state.connection_events_suppressed);
+        if state.connection.is_some() {
+            return vec![];
+        }
+        if let Some(error) = self.room(&session_id).connect_error {
+            let (message, code) = (cstring(&error.to_string()), session_error_code(error));
+            return vec![call(move || {
+                invoke!(
+                    callbacks,
+                    on_error,
+                    session as *mut _,
+                    message.as_ptr(),
+                    code
+                )
+            })];
+        }
+
+        let connection = Arc::new(ConnectionData {
+            id: cstring(&format!("connection-{}", next_id())),
+            session_id: cstring(&session_id),
+            data: CString::default(),
+            creation_time: now(),
+        });
+        let mut calls = vec![call(move || {
+            invoke!(callbacks, on_connected, session as *mut _)
+        })];
+        for (peer, peer_callbacks) in self.connected(&session_id, session) {
+            if !suppressed {
+                let existing = Handle::new(&self.connection(peer).unwrap());
+                calls.push(call(move || {
+                    invoke!(
+                        callbacks,
+                        on_connection_created,
+                        session as *mut _,
+                        existing.as_ptr()
+                    )
+                }));
+            }
+            if !self.sessions[&peer].connection_events_suppressed {
+                let created = Handle::new(&connection);
+                calls.push(call(move || {
+                    invoke!(
+                        peer_callbacks,
+                        on_connection_created,
+                        peer as *mut _,
+                        created.as_ptr()
+                    )
+                }));
+            }
+        }
+        for stream in self.streams(&session_id, session) {
+            let stream = Handle::new(&stream);
+            calls.push(call(move || {
+                invoke!(
+                    callbacks,
+                    on_stream_received,
+                    session as *mut _,
+                    stream.as_ptr()
+                )
+            }));
+        }
+        for (id, name) in self.room(&session_id).archives.clone() {
+            calls.push(call(move || {
+                invoke!(
+                    callbacks,
+                    on_archive_started,
+                    session as *mut _,
+                    id.as_ptr(),
+                    name.as_ptr()
+                )
+            }));
+        }
+        self.sessions.get_mut(&session).unwrap().connection = Some(Handle::new(&connection));
+        calls
+    }
+
+    fn disconnect(&mut self, session: usize) -> Vec<Call> {
+        let mut state = match self.sessions.remove(&session) {
+            Some(state) => state,
+            None => return vec![],
+        };
+        let connected = state.connection.is_some();
+        state.reconnecting = false;
+        let mut calls = self.leave(session, &mut state);
+        let callbacks = state.callbacks;
+        self.sessions.insert(session, state);
+        if connected {
+            calls.push(call(move || {
+                invoke!(callbacks, on_disconnected, session as *mut _)
+            }));
+        }
+        calls
+    }
+
+    /// Removes `session`, which is not in `sessions`, from its room. Its
+    /// streams are unpublished and its subscribers disconnected.
+    fn leave(&mut self, session: usize, state: &mut SessionState) -> Vec<Call> {
+        let connection = match state.connection.take() {
+            Some(connection) => connection,
+            None => return vec![],
+        };
+        let session_id = state.id.to_string_lossy().into_owned();
+        let mut calls = vec![];
+        for publisher in self.publishers_of(session) {
+            calls.extend(self.unpublish_from(publisher, &session_id, session));
+        }
+        for subscriber in self.subscribers.values_mut() {
+            if subscriber.session == Some(session) {
+                subscriber.session = None;
+                subscriber.connected = false;
+            }
+        }
+        for (peer, callbacks) in self.connected(&session_id, session) {
+            if self.sessions[&peer].connection_events_suppressed {
+                continue;
+            }
+            let dropped = Handle::new(connection.data());
+            calls.push(call(move || {
+                invoke!(
+                    callbacks,
+                    on_connection_dropped,
+                    peer as *mut _,
+                    dropped.as_ptr()
+                )
+            }));
+        }
+        calls
+    }
+
+    fn inflict(&mut self, session: usize, fault: Fault) -> Vec<Call> {
+        let state = match self.sessions.get_mut(&session) {
+            Some(state) if state.connection.is_some() => state,
+            _ => return vec![],
+        };
+        let callbacks = state.callbacks;
+        match fault {
+            Fault::ConnectionInterrupted if !state.reconnecting => {
+                state.reconnecting = true;
+                vec![call(move || {
+                    invoke!(callbacks, on_reconnection_started, session as *mut _)
+                })]
+            }
+            Fault::Reconnected if state.reconnecting => {
+                state.reconnecting = false;
+                vec![call(move || {
+                    invoke!(callbacks, on_reconnected, session as *mut _)
+                })]
+            }
+            Fault::Disconnected => self.disconnect(session),
+            Fault::Error(error) => {
+                let (message, code) = (cstring(&error.to_string()), session_error_code(error));
+                vec![call(move || {
+                    invoke!(
+                        callbacks,
+                        on_error,
+                        session as *mut _,
+                        message.as_ptr(),
+                        code
+                    )
+                })]
+            }
+            Fault::StreamsDropped => {
+                let mut calls = vec![];
+                for publisher in self.publishers_of(session) {
+                    calls.extend(self.unpublish(publisher));
+                }
+                calls
+            }
+            Fault::PublisherError(error) => {
+                let mut calls = vec![];
+                for publisher in self.publishers_of(session) {
+                    let callbacks = self.publishers[&publisher].callbacks;
+                    let (message, code) = (
+                        cstring(&format!("{:?}", error)),
+                        publisher_error_code(error),
+                    );
+                    calls.push(call(move || {
+                        invoke!(
+                            callbacks,
+                            on_error,
+                            publisher as *mut _,
+                            message.as_ptr(),
+                            code
+                        )
+                    }));
+                    calls.extend(self.unpublish(publisher));
+                }
+                calls
+            }
+            _ => vec![],
+        }
+    }
+
+    fn publish(&mut self, session: usize, publisher: usize) -> Vec<Call> {
+        let session_id = match self.session_id(session) {
+            Some(session_id) => session_id,
+            None => return vec![],
+        };
+        let connection = self.connection(session);
+        let publish_error = self.room(&session_id).publish_error;
+        let state = match self.publishers.get_mut(&publisher) {
+            Some(state) if state.session == Some(session) && state.stream.is_none() => state,
+            _ => return vec![],
+        };
+        let callbacks = state.callbacks;
+        let error = match connection {
+            Some(_) => publish_error,
+            None => Some(PublisherError::SessionDisconnected),
+        };
+        if let Some(error) = error {
+            state.session = None;
+            let (message, code) = (
+                cstring(&format!("{:?}", error)),
+                publisher_error_code(error),
+            );
+            return vec![call(move || {
+                invoke!(
+                    callbacks,
+                    on_error,
+                    publisher as *mut _,
+                    message.as_ptr(),
+                    code
+                )
+            })];
+        }
+
+        let stream = Arc::new(StreamData {
+            id: cstring(&format!("stream-{}", next_id())),
+            name: state.name.clone(),
+            connection: Handle::new(&connection.unwrap()),
+            creation_time: now(),
+            has_audio_track: state.audio_track,
+            has_video_track: state.video_track,
+            state: Mutex::new(state.stream_state()),
+        });
+        state.stream = Some(Handle::new(&stream));
+        let audio_track = state.audio_track;
+        let created = Handle::new(&stream);
+        let mut calls = vec![call(move || {
+            invoke!(
+                callbacks,
+                on_stream_created,
+                publisher as *mut _,
+                created.as_ptr()
+            )
+        })];
+        for (peer, callbacks) in self.connected(&session_id, session) {
+            let received = Handle::new(&stream);
+            calls.push(call(move || {
+                invoke!(
+                    callbacks,
+                    on_stream_received,
+                    peer as *mut _,
+                    received.as_ptr()
+                )
+            }));
+        }
+        if audio_track {
+            calls.extend(self.start_audio(true));
+        }
+        calls
+    }
+
+    fn unpublish(&mut self, publisher: usize) -> Vec<Call> {
+        let session = match self
+            .publishers
+            .get(&publisher)
+            .and_then(|state| state.session)
+        {
+            Some(session) => session,
+            None => return vec![],
+        };
+        let session_id = self.session_id(session).unwrap_or_default();
+        self.unpublish_from(publisher, &session_id, session)
+    }
+
+    fn unpublish_from(&mut self, publisher: usize, session_id: &str, session: usize) -> Vec<Call> {
+        let state = match self.publishers.get_mut(&publisher) {
+            Some(state) => state,
+            None => return vec![],
+        };
+        state.session = None;
+        let stream = match state.stream.take() {
+            Some(stream) => stream,
+            None => return vec![],
+        };
+        let callbacks = state.callbacks;
+        let mut calls = self.drop_stream(session_id, session, stream.data());
+        calls.push(call(move || {
+            invoke!(
+                callbacks,
+                on_stream_destroyed,
+                publisher as *mut _,
+                stream.as_ptr()
+            )
+        }));
+        calls
+    }
+
+    /// Removes `stream`, published by `session`, from the room `session_id`.
+    fn drop_stream(
+        &mut self,
+        session_id: &str,
+        session: usize,
+        stream: &Arc<StreamData>,
+    ) -> Vec<Call> {
+        for subscriber in self.subscribers.values_mut() {
+            if Arc::ptr_eq(&subscriber.stream, stream) {
+                subscriber.connected = false;
+            }
+        }
+        self.connected(session_id, session)
+            .into_iter()
+            .map(|(peer, callbacks)| {
+                let dropped = Handle::new(stream);
+                call(move || {
+                    invoke!(
+                        callbacks,
+                        on_stream_dropped,
+                        peer as *mut _,
+                        dropped.as_ptr()
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Applies the settings of `publisher` to its stream, notifying the other
+    /// clients and the subscribers of what changed.
+    fn update_stream(&mut self, publisher: usize) -> Vec<Call> {
+        let state = match self.publishers.get(&publisher) {
+            Some(state) => state,
+            None => return vec![],
+        };
+        let (session, stream) = match (state.session, &state.stream) {
+            (Some(session), Some(stream)) => (session, stream.data().clone()),
+            _ => return vec![],
+        };
+        let new = state.stream_state();
+        let old = std::mem::replace(&mut *stream.state.lock().unwrap(), new);
+        if old == new {
+            return vec![];
+        }
+
+        let session_id = self.session_id(session).unwrap_or_default();
+        let mut calls = vec![];
+        for (peer, callbacks) in self.connected(&session_id, session) {
+            let changed = Handle::new(&stream);
+            calls.push(call(move || {
+                let (peer, changed) = (peer as *mut _, changed.as_ptr());
+                if new.has_audio != old.has_audio {
+                    invoke!(
+                        callbacks,
+                        on_stream_has_audio_changed,
+                        peer,
+                        changed,
+                        new.has_audio as ffi::otc_bool
+                    );
+                }
+                if new.has_video != old.has_video {
+                    invoke!(
+                        callbacks,
+                        on_stream_has_video_changed,
+                        peer,
+                        changed,
+                        new.has_video as ffi::otc_bool
+                    );
+                }
+                if (new.width, new.height) != (old.width, old.height) {
+                    invoke!(
+                        callbacks,
+                        on_stream_video_dimensions_changed,
+                        peer,
+                        changed,
+                        new.width,
+                        new.height
+                    );
+                }
+                if new.video_type != old.video_type {
+                    invoke!(
+                        callbacks,
+                        on_stream_video_type_changed,
+                        peer,
+                        changed,
+                        new.video_type
+                    );
+                }
+            }));
+        }
+        for (subscriber, state) in &self.subscribers {
+            if !state.receives(&stream) {
+                continue;
+            }
+            let (subscriber, callbacks) = (*subscriber, state.callbacks);
+            calls.push(call(move || {
+                let subscriber = subscriber as *mut _;
+                let reason = ffi::otc_video_reason_OTC_VIDEO_REASON_PUBLISH_VIDEO;
+                match (old.has_audio, new.has_audio) {
+                    (true, false) => invoke!(callbacks, on_audio_disabled, subscriber),
+                    (false, true) => invoke!(callbacks, on_audio_enabled, subscriber),
+                    _ => (),
+                }
+                match (old.has_video, new.has_video) {
+                    (true, false) => invoke!(callbacks, on_video_disabled, subscriber, reason),
+                    (false, true) => invoke!(callbacks, on_video_enabled, subscriber, reason),
+                    _ => (),
+                }
+            }));
+        }
+        calls
+    }
+
+    fn subscribe(&mut self, session: usize, subscriber: usize) -> Vec<Call> {
+        let session_id = match self.session_id(session) {
+            Some(session_id) => session_id,
+            None => return vec![],
+        };
+        let connected = self.connection(session).is_some();
+        let subscribe_error = self.room(&session_id).subscribe_error;
+        let stream = match self.subscribers.get(&subscriber) {
+            Some(state) if state.session == Some(session) && !state.connected => {
+                state.stream.clone()
+            }
+            _ => return vec![],
+        };
+        let error = if !connected {
+            Some(SubscriberError::SessionDisconnected)
+        } else if subscribe_error.is_some() {
+            subscribe_error
+        } else if !self.is_published(&stream) {
+            Some(SubscriberError::ServerCannotFindStream)
+        } else {
+            None
+        };
+
+        let state = self.subscribers.get_mut(&subscriber).unwrap();
+        let callbacks = state.callbacks;
+        if let Some(error) = error {
+            state.session = None;
+            let (message, code) = (
+                cstring(&format!("{:?}", error)),
+                subscriber_error_code(error),
+            );
+            return vec![call(move || {
+                invoke!(
+                    callbacks,
+                    on_error,
+                    subscriber as *mut _,
+                    message.as_ptr(),
+                    code
+                )
+            })];
+        }
+        state.connected = true;
+        let connected = Handle::new(&stream);
+        let mut calls = vec![call(move || {
+            invoke!(
+                callbacks,
+                on_connected,
+                subscriber as *mut _,
+                connected.as_ptr()
+            )
+        })];
+        calls.extend(self.start_audio(false));
+        calls
+    }
+
+    fn signal(
+        &mut self,
+        session: usize,
+        type_: CString,
+        signal: CString,
+        to: Option<CString>,
+    ) -> Vec<Call> {
+        let (session_id, sender) = match (self.session_id(session), self.connection(session)) {
+            (Some(session_id), Some(sender)) => (session_id, sender),
+            _ => return vec![],
+        };
+        let (type_, signal) = (Rc::new(type_), Rc::new(signal));
+        self.connected(&session_id, 0)
+            .into_iter()
+            .filter(|(peer, _)| match to {
+                Some(ref to) => matches!(self.connection(*peer), Some(c) if c.id == *to),
+                None => true,
+            })
+            .map(|(peer, callbacks)| {
+                let (type_, signal, sender) = (type_.clone(), signal.clone(), Handle::new(&sender));
+                call(move || {
+                    invoke!(
+                        callbacks,
+                        on_signal_received,
+                        peer as *mut _,
+                        type_.as_ptr(),
+                        signal.as_ptr(),
+                        sender.as_ptr()
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Initializes and starts the video capturer of `publisher`, if any.
+    fn start_capturer(&mut self, publisher: usize) -> Vec<Call> {
+        let (capturer, callbacks) = match self.publishers.get(&publisher).and_then(|p| p.capturer) {
+            Some(capturer) => capturer,
+            None => return vec![],
+        };
+        vec![call(move || {
+            let capturer_ptr = capturer as *const _;
+            invoke!(callbacks, init, capturer_ptr);
+            let mut settings = ffi::otc_video_capturer_settings {
+                format: 0,
+                width: 0,
+                height: 0,
+                fps: 0,
+                expected_delay: 0,
+                mirror_on_local_render: 0,
+            };
+            invoke!(callbacks, get_capture_settings, capturer_ptr, &mut settings);
+            match world().publishers.get_mut(&publisher) {
+                Some(state) => {
+                    if settings.width > 0 && settings.height > 0 {
+                        state.width = settings.width;
+                        state.height = settings.height;
+                    }
+                    state.capturing = true;
+                }
+                None => return,
+            }
+            invoke!(callbacks, start, capturer_ptr);
+        })]
+    }
+
+    /// Renders `frame`, provided by `capturer`, in the publisher and in the
+    /// subscribers to its stream.
+    fn deliver_frame(&mut self, capturer: usize, frame: Frame) -> Vec<Call> {
+        let publisher = match self
+            .publishers
+            .iter()
+            .find(|(_, state)| state.capturer.map(|(c, _)| c) == Some(capturer))
+        {
+            Some((publisher, _)) => *publisher,
+            None => return vec![],
+        };
+        let state = self.publishers.get_mut(&publisher).unwrap();
+        state.width = unsafe { ffi::otc_video_frame_get_width(frame.0) };
+        state.height = unsafe { ffi::otc_video_frame_get_height(frame.0) };
+        state.frames += 1;
+        let mut calls = self.update_stream(publisher);
+
+        let frame = Rc::new(frame);
+        let state = &self.publishers[&publisher];
+        let (callbacks, preview) = (state.callbacks, frame.clone());
+        calls.push(call(move || {
+            invoke!(callbacks, on_render_frame, publisher as *mut _, preview.0)
+        }));
+        let stream = match state.stream {
+            Some(ref stream) if stream.data().state().has_video => stream.data().clone(),
+            _ => return calls,
+        };
+        for (subscriber, state) in self.subscribers.iter_mut() {
+            if !state.receives(&stream) || !state.subscribe_to_video {
+                continue;
+            }
+            state.frames += 1;
+            let (subscriber, callbacks, frame) = (*subscriber, state.callbacks, frame.clone());
+            calls.push(call(move || {
+                invoke!(callbacks, on_render_frame, subscriber as *mut _, frame.0)
+            }));
+        }
+        calls
+    }
+
+    fn publisher_rtc_stats(&mut self, publisher: usize) -> Vec<Call> {
+        let state = match self.publishers.get(&publisher) {
+            Some(state) => state,
+            None => return vec![],
+        };
+        let cb = match state.rtc_stats_report_cb {
+            Some(cb) => cb,
+            None => return vec![],
+        };
+        let report = cstring(&format!(
+            r#"[{{"id":"OT_outbound_video","type":"outbound-rtp","timestamp":{},"kind":"video","framesEncoded":{},"frameWidth":{},"frameHeight":{}}}]"#,
+            now(),
+            state.frames,
+            state.width,
+            state.height
+        ));
+        let mut connection_ids: Vec<CString> = match state.stream {
+            Some(ref stream) => self
+                .subscribers
+                .values()
+                .filter(|subscriber| subscriber.receives(stream.data()))
+                .filter_map(|subscriber| subscriber.session.and_then(|s| self.connection(s)))
+                .map(|connection| connection.id.clone())
+                .collect(),
+            None => vec![],
+        };
+        if connection_ids.is_empty() {
+            connection_ids.push(CString::default());
+        }
+        vec![call(move || {
+            let stats: Vec<ffi::otc_publisher_rtc_stats> = connection_ids
+                .iter()
+                .map(|connection_id| ffi::otc_publisher_rtc_stats {
+                    connection_id: connection_id.as_ptr(),
+                    json_array_of_reports: report.as_ptr(),
+                })
+                .collect();
+            invoke!(
+                cb,
+                on_rtc_stats_report,
+                publisher as *mut _,
+                stats.as_ptr(),
+                stats.len() as ffi::size_t
+            );
+        })]
+    }
+
+    fn subscriber_rtc_stats(&mut self, subscriber: usize) -> Vec<Call> {
+        let state = match self.subscribers.get(&subscriber) {
+            Some(state) => state,
+            None => return vec![],
+        };
+        let cb = match state.rtc_stats_report_cb {
+            Some(cb) => cb,
+            None => return vec![],
+        };
+        let stream = state.stream.state();
+        let report = cstring(&format!(
+            r#"[{{"id":"OT_inbound_video","type":"inbound-rtp","timestamp":{},"kind":"video","framesDecoded":{},"frameWidth":{},"frameHeight":{}}}]"#,
+            now(),
+            state.frames,
+            stream.width,
+            stream.height
+        ));
+        vec![call(move || {
+            invoke!(
+                cb,
+                on_rtc_stats_report,
+                subscriber as *mut _,
+                report.as_ptr()
+            )
+        })]
+    }
+
+    /// Starts the capture or the rendering of the audio device, if needed.
+    fn start_audio(&mut self, capture: bool) -> Vec<Call> {
+        let callbacks = match self.audio.callbacks {
+            Some(callbacks) => callbacks,
+            None => return vec![],
+        };
+        let device = self.audio.device;
+        let started = if capture {
+            &mut self.audio.capturing
+        } else {
+            &mut self.audio.rendering
+        };
+        if *started {
+            return vec![];
+        }
+        *started = true;
+        vec![call(move || {
+            let device = device as *const _;
+            let mut settings = ffi::otc_audio_device_settings {
+                sampling_rate: 0,
+                number_of_channels: 0,
+            };
+            if capture {
+                invoke!(callbacks, get_capture_settings, device, &mut settings);
+                invoke!(callbacks, start_capturer, device);
+            } else {
+                invoke!(callbacks, get_render_settings, device, &mut settings);
+                invoke!(callbacks, start_renderer, device);
+            }
+        })]
+    }
+
+    /// Whether a subscriber listens to a stream with audio.
+    fn is_listening(&self) -> bool {
+        self.subscribers
+            .values()
+            .any(|s| s.connected && s.subscribe_to_audio && s.stream.state().has_audio)
+    }
+}
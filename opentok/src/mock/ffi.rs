@@ -0,0 +1,822 @@
+//! Drop-in replacement for the `ffi` crate, backed by the fake server.
+//!
+//! Everything but the functions below, such as types, constants, video
+//! frames and logging, comes from the SDK bindings.
+
+pub use ::ffi::*;
+
+use super::{
+    call, delivering, enqueue, flush, is_dispatcher, new_handle, world, ConnectionData, Frame,
+    Handle, PublisherSettings, PublisherState, SessionSettings, SessionState, StreamData,
+    SubscriberState, MAX_BUFFERED_SAMPLES,
+};
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::slice;
+
+const SUCCESS: otc_status = otc_constant_OTC_SUCCESS as otc_status;
+const INVALID_PARAM: otc_status = otc_error_code_OTC_INVALID_PARAM as otc_status;
+const NOT_CONNECTED: otc_status = otc_session_error_code_OTC_SESSION_NOT_CONNECTED as otc_status;
+
+unsafe fn string(value: *const c_char) -> CString {
+    if value.is_null() {
+        return CString::default();
+    }
+    CStr::from_ptr(value).to_owned()
+}
+
+pub unsafe fn otc_init(_reserved: *mut c_void) -> otc_status {
+    SUCCESS
+}
+
+pub unsafe fn otc_destroy() -> otc_status {
+    if !is_dispatcher() {
+        flush();
+    }
+    let mut w = world();
+    w.audio.capturing = false;
+    w.audio.rendering = false;
+    w.audio.samples.clear();
+    SUCCESS
+}
+
+pub unsafe fn otc_set_audio_device(callbacks: *const otc_audio_device_callbacks) -> otc_status {
+    let callbacks = match callbacks.as_ref() {
+        Some(callbacks) => *callbacks,
+        None => return INVALID_PARAM,
+    };
+    let mut w = world();
+    w.audio.device = new_handle();
+    w.audio.callbacks = Some(callbacks);
+    SUCCESS
+}
+
+pub unsafe fn otc_audio_device_write_capture_data(
+    buffer: *const i16,
+    number_of_samples: size_t,
+) -> otc_status {
+    if buffer.is_null() {
+        return INVALID_PARAM;
+    }
+    let mut w = world();
+    if !w.is_listening() {
+        return SUCCESS;
+    }
+    let samples = &mut w.audio.samples;
+    samples.extend(slice::from_raw_parts(buffer, number_of_samples as usize));
+    let excess = samples.len().saturating_sub(MAX_BUFFERED_SAMPLES);
+    samples.drain(..excess);
+    SUCCESS
+}
+
+pub unsafe fn otc_audio_device_read_render_data(
+    buffer: *mut i16,
+    number_of_samples: size_t,
+) -> size_t {
+    if buffer.is_null() {
+        return 0;
+    }
+    let buffer = slice::from_raw_parts_mut(buffer, number_of_samples as usize);
+    let samples = &mut world().audio.samples;
+    for sample in buffer.iter_mut() {
+        *sample = samples.pop_front().unwrap_or_default();
+    }
+    number_of_samples
+}
+
+pub unsafe fn otc_connection_copy(connection: *const otc_connection) -> *mut otc_connection {
+    match Handle::<ConnectionData>::get(connection) {
+        Some(connection) => Handle::new(connection).into_ptr(),
+        None => ptr::null_mut(),
+    }
+}
+
+pub unsafe fn otc_connection_delete(connection: *mut otc_connection) -> otc_status {
+    Handle::<ConnectionData>::delete(connection);
+    SUCCESS
+}
+
+pub unsafe fn otc_connection_get_id(connection: *const otc_connection) -> *const c_char {
+    Handle::<ConnectionData>::get(connection).map_or(ptr::null(), |c| c.id.as_ptr())
+}
+
+pub unsafe fn otc_connection_get_session_id(connection: *const otc_connection) -> *const c_char {
+    Handle::<ConnectionData>::get(connection).map_or(ptr::null(), |c| c.session_id.as_ptr())
+}
+
+pub unsafe fn otc_connection_get_data(connection: *const otc_connection) -> *const c_char {
+    Handle::<ConnectionData>::get(connection).map_or(ptr::null(), |c| c.data.as_ptr())
+}
+
+pub unsafe fn otc_connection_get_creation_time(connection: *const otc_connection) -> i64 {
+    Handle::<ConnectionData>::get(connection).map_or(0, |c| c.creation_time)
+}
+
+pub unsafe fn otc_stream_copy(stream: *const otc_stream) -> *mut otc_stream {
+    match Handle::<StreamData>::get(stream) {
+        Some(stream) => Handle::new(stream).into_ptr(),
+        None => ptr::null_mut(),
+    }
+}
+
+pub unsafe fn otc_stream_delete(stream: *mut otc_stream) -> otc_status {
+    Handle::<StreamData>::delete(stream);
+    SUCCESS
+}
+
+pub unsafe fn otc_stream_get_id(stream: *const otc_stream) -> *const c_char {
+    Handle::<StreamData>::get(stream).map_or(ptr::null(), |s| s.id.as_ptr())
+}
+
+pub unsafe fn otc_stream_get_name(stream: *const otc_stream) -> *const c_char {
+    Handle::<StreamData>::get(stream).map_or(ptr::null(), |s| s.name.as_ptr())
+}
+
+pub unsafe fn otc_stream_has_audio(stream: *const otc_stream) -> otc_bool {
+    Handle::<StreamData>::get(stream).map_or(0, |s| s.state().has_audio as otc_bool)
+}
+
+pub unsafe fn otc_stream_has_audio_track(stream: *const otc_stream) -> otc_bool {
+    Handle::<StreamData>::get(stream).map_or(0, |s| s.has_audio_track as otc_bool)
+}
+
+pub unsafe fn otc_stream_has_video(stream: *const otc_stream) -> otc_bool {
+    Handle::<StreamData>::get(stream).map_or(0, |s| s.state().has_video as otc_bool)
+}
+
+pub unsafe fn otc_stream_has_video_track(stream: *const otc_stream) -> otc_bool {
+    Handle::<StreamData>::get(stream).map_or(0, |s| s.has_video_track as otc_bool)
+}
+
+pub unsafe fn otc_stream_get_video_width(stream: *const otc_stream) -> c_int {
+    Handle::<StreamData>::get(stream).map_or(0, |s| s.state().width)
+}
+
+pub unsafe fn otc_stream_get_video_height(stream: *const otc_stream) -> c_int {
+    Handle::<StreamData>::get(stream).map_or(0, |s| s.state().height)
+}
+
+pub unsafe fn otc_stream_get_creation_time(stream: *const otc_stream) -> i64 {
+    Handle::<StreamData>::get(stream).map_or(0, |s| s.creation_time)
+}
+
+pub unsafe fn otc_stream_get_video_type(stream: *const otc_stream) -> otc_stream_video_type {
+    Handle::<StreamData>::get(stream).map_or(0, |s| s.state().video_type)
+}
+
+pub unsafe fn otc_stream_get_connection(stream: *const otc_stream) -> *const otc_connection {
+    Handle::<StreamData>::get(stream).map_or(ptr::null(), |s| s.connection.as_ptr())
+}
+
+pub unsafe fn otc_session_settings_new() -> *mut otc_session_settings {
+    Box::into_raw(Box::new(SessionSettings::default())) as *mut _
+}
+
+pub unsafe fn otc_session_settings_delete(settings: *mut otc_session_settings) -> otc_status {
+    if !settings.is_null() {
+        drop(Box::from_raw(settings as *mut SessionSettings));
+    }
+    SUCCESS
+}
+
+pub unsafe fn otc_session_settings_set_connection_events_suppressed(
+    settings: *mut otc_session_settings,
+    suppress: otc_bool,
+) -> otc_status {
+    match (settings as *mut SessionSettings).as_mut() {
+        Some(settings) => {
+            settings.connection_events_suppressed = suppress != 0;
+            SUCCESS
+        }
+        None => INVALID_PARAM,
+    }
+}
+
+pub unsafe fn otc_session_settings_set_custom_ice_config(
+    _settings: *mut otc_session_settings,
+    _custom_ice_config: *const otc_custom_ice_config,
+) -> otc_status {
+    SUCCESS
+}
+
+pub unsafe fn otc_session_settings_set_proxy_url(
+    _settings: *mut otc_session_settings,
+    _proxy_url: *const c_char,
+) -> otc_status {
+    SUCCESS
+}
+
+pub unsafe fn otc_session_settings_set_ip_whitelist(
+    _settings: *mut otc_session_settings,
+    _ip_whitelist: otc_bool,
+) -> otc_status {
+    SUCCESS
+}
+
+pub unsafe fn otc_session_new(
+    apikey: *const c_char,
+    session_id: *const c_char,
+    callbacks: *const otc_session_callbacks,
+) -> *mut otc_session {
+    otc_session_new_with_settings(apikey, session_id, callbacks, ptr::null_mut())
+}
+
+pub unsafe fn otc_session_new_with_settings(
+    _apikey: *const c_char,
+    session_id: *const c_char,
+    callbacks: *const otc_session_callbacks,
+    settings: *mut otc_session_settings,
+) -> *mut otc_session {
+    let callbacks = match callbacks.as_ref() {
+        Some(callbacks) if !session_id.is_null() => *callbacks,
+        _ => return ptr::null_mut(),
+    };
+    let connection_events_suppressed = (settings as *const SessionSettings)
+        .as_ref()
+        .map(|settings| settings.connection_events_suppressed)
+        .unwrap_or_default();
+    let session = new_handle();
+    world().sessions.insert(
+        session,
+        SessionState {
+            id: string(session_id),
+            callbacks,
+            connection_events_suppressed,
+            connection: None,
+            reconnecting: false,
+        },
+    );
+    session as *mut _
+}
+
+pub unsafe fn otc_session_delete(session: *mut otc_session) -> otc_status {
+    let _delivering = delivering();
+    let session = session as usize;
+    let state = world().sessions.remove(&session);
+    match state {
+        Some(mut state) => {
+            enqueue(move |w| w.leave(session, &mut state));
+            SUCCESS
+        }
+        None => INVALID_PARAM,
+    }
+}
+
+pub unsafe fn otc_session_connect(session: *mut otc_session, _token: *const c_char) -> otc_status {
+    let session = session as usize;
+    if !world().sessions.contains_key(&session) {
+        return INVALID_PARAM;
+    }
+    enqueue(move |w| w.connect(session));
+    SUCCESS
+}
+
+pub unsafe fn otc_session_disconnect(session: *mut otc_session) -> otc_status {
+    let session = session as usize;
+    if !world().sessions.contains_key(&session) {
+        return INVALID_PARAM;
+    }
+    enqueue(move |w| w.disconnect(session));
+    SUCCESS
+}
+
+pub unsafe fn otc_session_get_id(session: *const otc_session) -> *mut c_char {
+    world()
+        .sessions
+        .get(&(session as usize))
+        .map_or(ptr::null_mut(), |session| session.id.as_ptr() as *mut _)
+}
+
+pub unsafe fn otc_session_get_connection(session: *const otc_session) -> *mut otc_connection {
+    world()
+        .sessions
+        .get(&(session as usize))
+        .and_then(|session| session.connection.as_ref())
+        .map_or(ptr::null_mut(), |connection| connection.as_ptr())
+}
+
+pub unsafe fn otc_session_publish(
+    session: *mut otc_session,
+    publisher: *mut otc_publisher,
+) -> otc_status {
+    let (session, publisher) = (session as usize, publisher as usize);
+    {
+        let mut w = world();
+        if !w.sessions.contains_key(&session) {
+            return INVALID_PARAM;
+        }
+        match w.publishers.get_mut(&publisher) {
+            Some(state) if state.session.is_none() => state.session = Some(session),
+            _ => return INVALID_PARAM,
+        }
+    }
+    enqueue(move |w| w.publish(session, publisher));
+    SUCCESS
+}
+
+pub unsafe fn otc_session_unpublish(
+    session: *mut otc_session,
+    publisher: *mut otc_publisher,
+) -> otc_status {
+    let publisher = publisher as usize;
+    match world().publishers.get(&publisher) {
+        Some(state) if state.session == Some(session as usize) => (),
+        _ => return INVALID_PARAM,
+    }
+    enqueue(move |w| w.unpublish(publisher));
+    SUCCESS
+}
+
+pub unsafe fn otc_session_subscribe(
+    session: *mut otc_session,
+    subscriber: *mut otc_subscriber,
+) -> otc_status {
+    let (session, subscriber) = (session as usize, subscriber as usize);
+    {
+        let mut w = world();
+        if !w.sessions.contains_key(&session) {
+            return INVALID_PARAM;
+        }
+        match w.subscribers.get_mut(&subscriber) {
+            Some(state) => state.session = Some(session),
+            None => return INVALID_PARAM,
+        }
+    }
+    enqueue(move |w| w.subscribe(session, subscriber));
+    SUCCESS
+}
+
+pub unsafe fn otc_session_unsubscribe(
+    session: *mut otc_session,
+    subscriber: *mut otc_subscriber,
+) -> otc_status {
+    match world().subscribers.get_mut(&(subscriber as usize)) {
+        Some(state) if state.session == Some(session as usize) => {
+            state.session = None;
+            state.connected = false;
+            SUCCESS
+        }
+        _ => INVALID_PARAM,
+    }
+}
+
+unsafe fn send_signal(
+    session: *mut otc_session,
+    type_: *const c_char,
+    signal: *const c_char,
+    to: Option<CString>,
+) -> otc_status {
+    let session = session as usize;
+    match world().sessions.get(&session) {
+        Some(state) if state.connection.is_some() => (),
+        Some(_) => return NOT_CONNECTED,
+        None => return INVALID_PARAM,
+    }
+    let (type_, signal) = (string(type_), string(signal));
+    enqueue(move |w| w.signal(session, type_, signal, to));
+    SUCCESS
+}
+
+pub unsafe fn otc_session_send_signal_with_options(
+    session: *mut otc_session,
+    type_: *const c_char,
+    signal: *const c_char,
+    _signal_options: otc_signal_options,
+) -> otc_status {
+    send_signal(session, type_, signal, None)
+}
+
+pub unsafe fn otc_session_send_signal_to_connection_with_options(
+    session: *mut otc_session,
+    type_: *const c_char,
+    signal: *const c_char,
+    connection: *const otc_connection,
+    _signal_options: otc_signal_options,
+) -> otc_status {
+    match Handle::<ConnectionData>::get(connection) {
+        Some(connection) => send_signal(session, type_, signal, Some(connection.id.clone())),
+        None => INVALID_PARAM,
+    }
+}
+
+pub unsafe fn otc_publisher_settings_new() -> *mut otc_publisher_settings {
+    Box::into_raw(Box::new(PublisherSettings::default())) as *mut _
+}
+
+pub unsafe fn otc_publisher_settings_delete(settings: *mut otc_publisher_settings) -> otc_status {
+    if !settings.is_null() {
+        drop(Box::from_raw(settings as *mut PublisherSettings));
+    }
+    SUCCESS
+}
+
+unsafe fn with_publisher_settings<F: FnOnce(&mut PublisherSettings)>(
+    settings: *mut otc_publisher_settings,
+    f: F,
+) -> otc_status {
+    match (settings as *mut PublisherSettings).as_mut() {
+        Some(settings) => {
+            f(settings);
+            SUCCESS
+        }
+        None => INVALID_PARAM,
+    }
+}
+
+pub unsafe fn otc_publisher_settings_set_name(
+    settings: *mut otc_publisher_settings,
+    name: *const c_char,
+) -> otc_status {
+    with_publisher_settings(settings, |settings| settings.name = string(name))
+}
+
+pub unsafe fn otc_publisher_settings_set_video_capturer(
+    settings: *mut otc_publisher_settings,
+    capturer: *const otc_video_capturer_callbacks,
+) -> otc_status {
+    let capturer = capturer.as_ref().copied();
+    with_publisher_settings(settings, |settings| settings.capturer = capturer)
+}
+
+pub unsafe fn otc_publisher_settings_set_audio_track(
+    settings: *mut otc_publisher_settings,
+    enabled: otc_bool,
+) -> otc_status {
+    with_publisher_settings(settings, |settings| settings.audio_track = enabled != 0)
+}
+
+pub unsafe fn otc_publisher_settings_set_video_track(
+    settings: *mut otc_publisher_settings,
+    enabled: otc_bool,
+) -> otc_status {
+    with_publisher_settings(settings, |settings| settings.video_track = enabled != 0)
+}
+
+pub unsafe fn otc_publisher_settings_set_stereo(
+    settings: *mut otc_publisher_settings,
+    _enabled: otc_bool,
+) -> otc_status {
+    with_publisher_settings(settings, |_| ())
+}
+
+pub unsafe fn otc_publisher_new(
+    name: *const c_char,
+    capturer: *const otc_video_capturer_callbacks,
+    callbacks: *const otc_publisher_callbacks,
+) -> *mut otc_publisher {
+    let settings = PublisherSettings {
+        name: string(name),
+        capturer: capturer.as_ref().copied(),
+        ..Default::default()
+    };
+    new_publisher(callbacks, settings)
+}
+
+pub unsafe fn otc_publisher_new_with_settings(
+    callbacks: *const otc_publisher_callbacks,
+    settings: *mut otc_publisher_settings,
+) -> *mut otc_publisher {
+    let settings = match (settings as *const PublisherSettings).as_ref() {
+        Some(settings) => PublisherSettings {
+            name: settings.name.clone(),
+            capturer: settings.capturer,
+            audio_track: settings.audio_track,
+            video_track: settings.video_track,
+        },
+        None => return ptr::null_mut(),
+    };
+    new_publisher(callbacks, settings)
+}
+
+unsafe fn new_publisher(
+    callbacks: *const otc_publisher_callbacks,
+    settings: PublisherSettings,
+) -> *mut otc_publisher {
+    let callbacks = match callbacks.as_ref() {
+        Some(callbacks) => *callbacks,
+        None => return ptr::null_mut(),
+    };
+    let publisher = new_handle();
+    world()
+        .publishers
+        .insert(publisher, PublisherState::new(callbacks, settings));
+    enqueue(move |w| w.start_capturer(publisher));
+    publisher as *mut _
+}
+
+pub unsafe fn otc_publisher_delete(publisher: *mut otc_publisher) -> otc_status {
+    let _delivering = delivering();
+    let publisher = publisher as usize;
+    let (state, session_id) = {
+        let mut w = world();
+        let state = match w.publishers.remove(&publisher) {
+            Some(state) => state,
+            None => return INVALID_PARAM,
+        };
+        let session_id = state.session.and_then(|session| w.session_id(session));
+        (state, session_id)
+    };
+    if let Some((capturer, callbacks)) = state.capturer {
+        let capturer = capturer as *const _;
+        if state.capturing {
+            invoke!(callbacks, stop, capturer);
+        }
+        invoke!(callbacks, destroy, capturer);
+    }
+    if let (Some(session), Some(session_id), Some(stream)) =
+        (state.session, session_id, state.stream)
+    {
+        enqueue(move |w| w.drop_stream(&session_id, session, stream.data()));
+    }
+    SUCCESS
+}
+
+unsafe fn with_publisher<T, F: FnOnce(&mut PublisherState) -> T>(
+    publisher: *const otc_publisher,
+    f: F,
+) -> Option<T> {
+    world().publishers.get_mut(&(publisher as usize)).map(f)
+}
+
+/// Changes the settings of `publisher` and then the state of its stream.
+unsafe fn update_publisher<F: FnOnce(&mut PublisherState)>(
+    publisher: *mut otc_publisher,
+    f: F,
+) -> otc_status {
+    if with_publisher(publisher, f).is_none() {
+        return INVALID_PARAM;
+    }
+    let publisher = publisher as usize;
+    enqueue(move |w| w.update_stream(publisher));
+    SUCCESS
+}
+
+pub unsafe fn otc_publisher_get_name(publisher: *const otc_publisher) -> *const c_char {
+    with_publisher(publisher, |state| state.name.as_ptr()).unwrap_or_else(ptr::null)
+}
+
+pub unsafe fn otc_publisher_get_session(publisher: *const otc_publisher) -> *mut otc_session {
+    with_publisher(publisher, |state| state.session)
+        .flatten()
+        .map_or(ptr::null_mut(), |session| session as *mut _)
+}
+
+pub unsafe fn otc_publisher_get_stream(publisher: *mut otc_publisher) -> *mut otc_stream {
+    with_publisher(publisher, |state| {
+        state.stream.as_ref().map(|stream| stream.as_ptr())
+    })
+    .flatten()
+    .unwrap_or_else(ptr::null_mut)
+}
+
+pub unsafe fn otc_publisher_get_publish_audio(publisher: *const otc_publisher) -> otc_bool {
+    with_publisher(publisher, |state| state.publish_audio as otc_bool).unwrap_or_default()
+}
+
+pub unsafe fn otc_publisher_get_publish_video(publisher: *const otc_publisher) -> otc_bool {
+    with_publisher(publisher, |state| state.publish_video as otc_bool).unwrap_or_default()
+}
+
+pub unsafe fn otc_publisher_set_publish_audio(
+    publisher: *mut otc_publisher,
+    publish_audio: otc_bool,
+) -> otc_status {
+    update_publisher(publisher, |state| state.publish_audio = publish_audio != 0)
+}
+
+pub unsafe fn otc_publisher_set_publish_video(
+    publisher: *mut otc_publisher,
+    publish_video: otc_bool,
+) -> otc_status {
+    update_publisher(publisher, |state| state.publish_video = publish_video != 0)
+}
+
+pub unsafe fn otc_publisher_get_video_type(
+    publisher: *const otc_publisher,
+) -> otc_publisher_video_type {
+    with_publisher(publisher, |state| state.video_type).unwrap_or_default()
+}
+
+pub unsafe fn otc_publisher_set_video_type(
+    publisher: *mut otc_publisher,
+    video_type: otc_publisher_video_type,
+) -> otc_status {
+    update_publisher(publisher, |state| state.video_type = video_type)
+}
+
+pub unsafe fn otc_publisher_set_max_audio_bitrate(
+    publisher: *mut otc_publisher,
+    bitrate: u32,
+) -> otc_status {
+    if !(6_000..=510_000).contains(&bitrate) {
+        return INVALID_PARAM;
+    }
+    with_publisher(publisher, |state| state.max_audio_bitrate = bitrate)
+        .map_or(INVALID_PARAM, |_| SUCCESS)
+}
+
+pub unsafe fn otc_publisher_set_audio_fallback_enabled(
+    publisher: *mut otc_publisher,
+    enabled: otc_bool,
+) -> otc_status {
+    with_publisher(publisher, |state| state.audio_fallback = enabled != 0)
+        .map_or(INVALID_PARAM, |_| SUCCESS)
+}
+
+pub unsafe fn otc_publisher_set_rtc_stats_report_cb(
+    publisher: *mut otc_publisher,
+    cb: otc_publisher_rtc_stats_report_cb,
+) -> otc_status {
+    with_publisher(publisher, |state| state.rtc_stats_report_cb = Some(cb))
+        .map_or(INVALID_PARAM, |_| SUCCESS)
+}
+
+pub unsafe fn otc_publisher_get_rtc_stats_report(publisher: *const otc_publisher) -> otc_status {
+    let publisher = publisher as usize;
+    if !world().publishers.contains_key(&publisher) {
+        return INVALID_PARAM;
+    }
+    enqueue(move |w| w.publisher_rtc_stats(publisher));
+    SUCCESS
+}
+
+pub unsafe fn otc_subscriber_new(
+    stream: *const otc_stream,
+    callbacks: *const otc_subscriber_callbacks,
+) -> *mut otc_subscriber {
+    let (stream, callbacks) = match (Handle::<StreamData>::get(stream), callbacks.as_ref()) {
+        (Some(stream), Some(callbacks)) => (stream.clone(), *callbacks),
+        _ => return ptr::null_mut(),
+    };
+    let subscriber = new_handle();
+    world()
+        .subscribers
+        .insert(subscriber, SubscriberState::new(stream, callbacks));
+    subscriber as *mut _
+}
+
+pub unsafe fn otc_subscriber_delete(subscriber: *mut otc_subscriber) -> otc_status {
+    let _delivering = delivering();
+    match world().subscribers.remove(&(subscriber as usize)) {
+        Some(_) => SUCCESS,
+        None => INVALID_PARAM,
+    }
+}
+
+unsafe fn with_subscriber<T, F: FnOnce(&mut SubscriberState) -> T>(
+    subscriber: *const otc_subscriber,
+    f: F,
+) -> Option<T> {
+    world().subscribers.get_mut(&(subscriber as usize)).map(f)
+}
+
+pub unsafe fn otc_subscriber_get_session(subscriber: *const otc_subscriber) -> *mut otc_session {
+    with_subscriber(subscriber, |state| state.session)
+        .flatten()
+        .map_or(ptr::null_mut(), |session| session as *mut _)
+}
+
+pub unsafe fn otc_subscriber_get_subscribe_to_audio(subscriber: *const otc_subscriber) -> otc_bool {
+    with_subscriber(subscriber, |state| state.subscribe_to_audio as otc_bool).unwrap_or_default()
+}
+
+pub unsafe fn otc_subscriber_get_subscribe_to_video(subscriber: *const otc_subscriber) -> otc_bool {
+    with_subscriber(subscriber, |state| state.subscribe_to_video as otc_bool).unwrap_or_default()
+}
+
+pub unsafe fn otc_subscriber_set_subscribe_to_audio(
+    subscriber: *mut otc_subscriber,
+    subscribe_to_audio: otc_bool,
+) -> otc_status {
+    with_subscriber(subscriber, |state| {
+        state.subscribe_to_audio = subscribe_to_audio != 0
+    })
+    .map_or(INVALID_PARAM, |_| SUCCESS)
+}
+
+pub unsafe fn otc_subscriber_set_subscribe_to_video(
+    subscriber: *mut otc_subscriber,
+    subscribe_to_video: otc_bool,
+) -> otc_status {
+    let subscribe_to_video = subscribe_to_video != 0;
+    let changed = with_subscriber(subscriber, |state| {
+        let changed = state.connected && state.subscribe_to_video != subscribe_to_video;
+        state.subscribe_to_video = subscribe_to_video;
+        changed
+    });
+    match changed {
+        Some(true) => (),
+        Some(false) => return SUCCESS,
+        None => return INVALID_PARAM,
+    }
+    let subscriber = subscriber as usize;
+    enqueue(move |w| {
+        let callbacks = match w.subscribers.get(&subscriber) {
+            Some(state) => state.callbacks,
+            None => return vec![],
+        };
+        vec![call(move || {
+            let (subscriber, reason) = (
+                subscriber as *mut _,
+                otc_video_reason_OTC_VIDEO_REASON_SUBSCRIBE_TO_VIDEO,
+            );
+            if subscribe_to_video {
+                invoke!(callbacks, on_video_enabled, subscriber, reason);
+            } else {
+                invoke!(callbacks, on_video_disabled, subscriber, reason);
+            }
+        })]
+    });
+    SUCCESS
+}
+
+pub unsafe fn otc_subscriber_get_preferred_resolution(
+    subscriber: *const otc_subscriber,
+    preferred_width: *mut u32,
+    preferred_height: *mut u32,
+) -> otc_status {
+    if preferred_width.is_null() || preferred_height.is_null() {
+        return INVALID_PARAM;
+    }
+    match with_subscriber(subscriber, |state| state.preferred_resolution) {
+        Some((width, height)) => {
+            *preferred_width = width;
+            *preferred_height = height;
+            SUCCESS
+        }
+        None => INVALID_PARAM,
+    }
+}
+
+pub unsafe fn otc_subscriber_set_preferred_resolution(
+    subscriber: *mut otc_subscriber,
+    preferred_width: u32,
+    preferred_height: u32,
+) -> otc_status {
+    with_subscriber(subscriber, |state| {
+        state.preferred_resolution = (preferred_width, preferred_height)
+    })
+    .map_or(INVALID_PARAM, |_| SUCCESS)
+}
+
+pub unsafe fn otc_subscriber_get_preferred_framerate(
+    subscriber: *const otc_subscriber,
+    preferred_framerate: *mut f32,
+) -> otc_status {
+    if preferred_framerate.is_null() {
+        return INVALID_PARAM;
+    }
+    match with_subscriber(subscriber, |state| state.preferred_framerate) {
+        Some(framerate) => {
+            *preferred_framerate = framerate;
+            SUCCESS
+        }
+        None => INVALID_PARAM,
+    }
+}
+
+pub unsafe fn otc_subscriber_set_preferred_framerate(
+    subscriber: *mut otc_subscriber,
+    preferred_framerate: f32,
+) -> otc_status {
+    with_subscriber(subscriber, |state| {
+        state.preferred_framerate = preferred_framerate
+    })
+    .map_or(INVALID_PARAM, |_| SUCCESS)
+}
+
+pub unsafe fn otc_subscriber_set_rtc_stats_report_cb(
+    subscriber: *mut otc_subscriber,
+    cb: otc_subscriber_rtc_stats_report_cb,
+) -> otc_status {
+    with_subscriber(subscriber, |state| state.rtc_stats_report_cb = Some(cb))
+        .map_or(INVALID_PARAM, |_| SUCCESS)
+}
+
+pub unsafe fn otc_subscriber_get_rtc_stats_report(subscriber: *const otc_subscriber) -> otc_status {
+    let subscriber = subscriber as usize;
+    if !world().subscribers.contains_key(&subscriber) {
+        return INVALID_PARAM;
+    }
+    enqueue(move |w| w.subscriber_rtc_stats(subscriber));
+    SUCCESS
+}
+
+pub unsafe fn otc_video_capturer_provide_frame(
+    capturer: *const otc_video_capturer,
+    _rotation: c_int,
+    frame: *const otc_video_frame,
+) -> otc_status {
+    if frame.is_null() {
+        return INVALID_PARAM;
+    }
+    let frame = otc_video_frame_copy(frame);
+    if frame.is_null() {
+        return otc_error_code_OTC_FATAL as otc_status;
+    }
+    let (capturer, frame) = (capturer as usize, Frame(frame));
+    enqueue(move |w| w.deliver_frame(capturer, frame));
+    SUCCESS
+}
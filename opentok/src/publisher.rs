@@ -1,21 +1,30 @@
-use crate::enums::{IntoResult, OtcError, OtcResult};
-use crate::stream::Stream;
+use crate::enums::{IntoResult, OtcBool, OtcError, OtcResult};
+#[cfg(feature = "futures")]
+use crate::events::AsyncError;
+use crate::events::EventSenders;
+#[cfg(feature = "mock")]
+use crate::mock::ffi;
+use crate::rtc_stats::RtcStatsReport;
+use crate::session::Session;
+use crate::stream::{Stream, StreamVideoType};
+use crate::user_data::{Registry, UserData};
 use crate::video_capturer::VideoCapturer;
 use crate::video_frame::VideoFrame;
 
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_void};
 use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Weak};
 
 lazy_static! {
-    pub static ref INSTANCES: Arc<Mutex<HashMap<usize, Publisher>>> = Default::default();
+    pub(crate) static ref LIVE: Registry<Publisher> = Default::default();
 }
 
 /// This enumeration represents all the possible error types
 /// associated with a publisher.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum PublisherError {
     /// Internal error.
     Internal,
@@ -49,40 +58,393 @@ impl From<ffi::otc_publisher_error_code> for PublisherError {
 }
 
 /// Type of video being published by the Publisher.
-enum VideoType {
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VideoType {
     /// Camera video stream.
     Camera,
     /// Screen capture video stream.
     Screen,
 }
 
-/// Audio and video publisher statistics.
-struct MediaStats {
-    /// The unique identifier for the client connection.
-    connection_id: String,
-    /// The unique identifier of the subscriber.
-    subscriber_id: String,
-    /// The total number of audio or video packets that did not reach
-    /// the subscriber (or the Opentok Media Router).
-    packets_lost: i64,
-    /// The total number of audio or video packets sent to the subscriber
-    /// (or to the OpenTok Media Router).
-    bytes_sent: i64,
+impl From<ffi::otc_publisher_video_type> for VideoType {
+    fn from(type_: ffi::otc_publisher_video_type) -> VideoType {
+        match type_ {
+            ffi::otc_publisher_video_type_OTC_PUBLISHER_VIDEO_TYPE_SCREEN => VideoType::Screen,
+            _ => VideoType::Camera,
+        }
+    }
+}
+
+impl From<VideoType> for ffi::otc_publisher_video_type {
+    fn from(type_: VideoType) -> ffi::otc_publisher_video_type {
+        match type_ {
+            VideoType::Camera => ffi::otc_publisher_video_type_OTC_PUBLISHER_VIDEO_TYPE_CAMERA,
+            VideoType::Screen => ffi::otc_publisher_video_type_OTC_PUBLISHER_VIDEO_TYPE_SCREEN,
+        }
+    }
+}
+
+/// Streams published as `StreamVideoType::Custom` cannot be converted, as
+/// publishers only declare camera or screen video.
+impl TryFrom<StreamVideoType> for VideoType {
+    type Error = OtcError;
+
+    fn try_from(type_: StreamVideoType) -> Result<VideoType, OtcError> {
+        match type_ {
+            StreamVideoType::Camera => Ok(VideoType::Camera),
+            StreamVideoType::Screen => Ok(VideoType::Screen),
+            _ => Err(OtcError::InvalidParam("video_type")),
+        }
+    }
+}
+
+impl From<VideoType> for StreamVideoType {
+    fn from(type_: VideoType) -> StreamVideoType {
+        match type_ {
+            VideoType::Camera => StreamVideoType::Camera,
+            VideoType::Screen => StreamVideoType::Screen,
+        }
+    }
+}
+
+/// Settings for a publisher, used with `Publisher::with_settings`.
+///
+/// Scalable video and video content hints are not exposed by the 2.19.1 SDK,
+/// so there are no settings for them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublisherSettings {
+    audio_track: bool,
+    video_track: bool,
+    stereo: bool,
+    max_audio_bitrate: Option<u32>,
+    audio_fallback: Option<bool>,
+    video_type: Option<VideoType>,
+}
+
+impl Default for PublisherSettings {
+    fn default() -> Self {
+        PublisherSettingsBuilder::default().build()
+    }
+}
+
+impl PublisherSettings {
+    pub fn builder() -> PublisherSettingsBuilder {
+        PublisherSettingsBuilder::default()
+    }
+
+    /// Whether the publisher has an audio track.
+    pub fn audio_track(&self) -> bool {
+        self.audio_track
+    }
+
+    /// Whether the publisher has a video track.
+    pub fn video_track(&self) -> bool {
+        self.video_track
+    }
+
+    /// Whether audio is published in stereo.
+    pub fn stereo(&self) -> bool {
+        self.stereo
+    }
+
+    /// The maximum audio bitrate, in bits per second, if any.
+    pub fn max_audio_bitrate(&self) -> Option<u32> {
+        self.max_audio_bitrate
+    }
+
+    /// Whether audio fallback is enabled, if set. The SDK enables it by
+    /// default.
+    pub fn audio_fallback(&self) -> Option<bool> {
+        self.audio_fallback
+    }
+
+    /// The video type, if set. The SDK defaults to `VideoType::Camera`.
+    pub fn video_type(&self) -> Option<VideoType> {
+        self.video_type
+    }
+
+    /// Creates the native settings instance. The caller owns the returned
+    /// pointer and must release it with `otc_publisher_settings_delete`.
+    fn to_ffi(
+        &self,
+        name: &CString,
+        capturer: *const ffi::otc_video_capturer_callbacks,
+    ) -> Result<*mut ffi::otc_publisher_settings, OtcError> {
+        let settings = unsafe { ffi::otc_publisher_settings_new() };
+        if settings.is_null() {
+            return Err(OtcError::NullError);
+        }
+
+        let result = unsafe {
+            ffi::otc_publisher_settings_set_name(settings, name.as_ptr())
+                .into_result()
+                .and_then(|_| {
+                    if capturer.is_null() {
+                        return Ok(());
+                    }
+                    ffi::otc_publisher_settings_set_video_capturer(settings, capturer).into_result()
+                })
+                .and_then(|_| {
+                    ffi::otc_publisher_settings_set_audio_track(
+                        settings,
+                        OtcBool::from(self.audio_track).0,
+                    )
+                    .into_result()
+                })
+                .and_then(|_| {
+                    ffi::otc_publisher_settings_set_video_track(
+                        settings,
+                        OtcBool::from(self.video_track).0,
+                    )
+                    .into_result()
+                })
+                .and_then(|_| {
+                    ffi::otc_publisher_settings_set_stereo(settings, OtcBool::from(self.stereo).0)
+                        .into_result()
+                })
+        };
+
+        if let Err(e) = result {
+            unsafe {
+                ffi::otc_publisher_settings_delete(settings);
+            }
+            return Err(e);
+        }
+
+        Ok(settings)
+    }
+
+    /// Applies the settings that the SDK only supports on publisher
+    /// instances.
+    fn apply(&self, publisher: &Publisher) -> OtcResult {
+        if let Some(bitrate) = self.max_audio_bitrate {
+            publisher.set_max_audio_bitrate(bitrate)?;
+        }
+        if let Some(enabled) = self.audio_fallback {
+            unsafe {
+                ffi::otc_publisher_set_audio_fallback_enabled(
+                    publisher.inner() as *mut _,
+                    OtcBool::from(enabled).0,
+                )
+            }
+            .into_result()?;
+        }
+        if let Some(video_type) = self.video_type {
+            publisher.set_video_type(video_type)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct PublisherSettingsBuilder {
+    audio_track: bool,
+    video_track: bool,
+    stereo: bool,
+    max_audio_bitrate: Option<u32>,
+    audio_fallback: Option<bool>,
+    video_type: Option<VideoType>,
+}
+
+impl Default for PublisherSettingsBuilder {
+    fn default() -> Self {
+        Self {
+            audio_track: true,
+            video_track: true,
+            stereo: false,
+            max_audio_bitrate: None,
+            audio_fallback: None,
+            video_type: None,
+        }
+    }
+}
+
+impl PublisherSettingsBuilder {
+    /// Whether the publisher has an audio track. Enabled by default.
+    pub fn audio_track(self, audio_track: bool) -> Self {
+        Self {
+            audio_track,
+            ..self
+        }
+    }
+
+    /// Whether the publisher has a video track. Enabled by default.
+    pub fn video_track(self, video_track: bool) -> Self {
+        Self {
+            video_track,
+            ..self
+        }
+    }
+
+    /// Whether audio is published in stereo. This requires an audio device
+    /// capturing stereo audio. Disabled by default.
+    pub fn stereo(self, stereo: bool) -> Self {
+        Self { stereo, ..self }
+    }
+
+    /// Sets the maximum audio bitrate, in bits per second. The SDK accepts
+    /// values from 6000 to 510000. Higher values suit music.
+    pub fn max_audio_bitrate(self, bitrate: u32) -> Self {
+        Self {
+            max_audio_bitrate: Some(bitrate),
+            ..self
+        }
+    }
+
+    /// Whether subscribers fall back to audio only when the stream quality
+    /// degrades, in sessions using the OpenTok Media Router.
+    pub fn audio_fallback(self, enabled: bool) -> Self {
+        Self {
+            audio_fallback: Some(enabled),
+            ..self
+        }
+    }
+
+    /// Sets whether the video comes from a camera or a screen capture.
+    pub fn video_type(self, video_type: VideoType) -> Self {
+        Self {
+            video_type: Some(video_type),
+            ..self
+        }
+    }
+
+    pub fn build(self) -> PublisherSettings {
+        PublisherSettings {
+            audio_track: self.audio_track,
+            video_track: self.video_track,
+            stereo: self.stereo,
+            max_audio_bitrate: self.max_audio_bitrate,
+            audio_fallback: self.audio_fallback,
+            video_type: self.video_type,
+        }
+    }
+}
+
+/// Audio statistics of a publisher for one of its subscribers, reported
+/// periodically by `PublisherCallbacks::on_audio_stats`.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PublisherAudioStats {
+    /// The connection ID of the client subscribing to the stream.
+    pub connection_id: String,
+    /// The subscriber ID of the client subscribing to the stream, in a
+    /// relayed session.
+    pub subscriber_id: String,
+    /// The total number of audio packets that did not reach the subscriber
+    /// (or the OpenTok Media Router).
+    pub packets_lost: i64,
+    /// The total number of audio packets sent to the subscriber (or to the
+    /// OpenTok Media Router).
+    pub packets_sent: i64,
+    /// The total number of audio bytes sent to the subscriber (or to the
+    /// OpenTok Media Router).
+    pub bytes_sent: i64,
     /// The audio level value, from 0 to 1.0.
-    audio_level: f32,
-    /// The time when these stats were gathered.
-    timestamp: f64,
-    /// The time when the cumulative totals started accumulating.
-    start_time: f64,
+    pub audio_level: f32,
+    /// The time when these stats were gathered, in milliseconds since the
+    /// Unix epoch.
+    pub timestamp: f64,
+    /// The time from which the cumulative totals started accumulating, in
+    /// milliseconds since the Unix epoch.
+    pub start_time: f64,
+}
+
+impl From<&ffi::otc_publisher_audio_stats> for PublisherAudioStats {
+    fn from(stats: &ffi::otc_publisher_audio_stats) -> PublisherAudioStats {
+        PublisherAudioStats {
+            connection_id: string_or_empty(stats.connection_id),
+            subscriber_id: string_or_empty(stats.subscriber_id),
+            packets_lost: stats.packets_lost,
+            packets_sent: stats.packets_sent,
+            bytes_sent: stats.bytes_sent,
+            audio_level: stats.audio_level,
+            timestamp: stats.timestamp,
+            start_time: stats.start_time,
+        }
+    }
+}
+
+/// Video statistics of a publisher for one of its subscribers, reported
+/// periodically by `PublisherCallbacks::on_video_stats`.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PublisherVideoStats {
+    /// The connection ID of the client subscribing to the stream.
+    pub connection_id: String,
+    /// The subscriber ID of the client subscribing to the stream, in a
+    /// relayed session.
+    pub subscriber_id: String,
+    /// The total number of video packets that did not reach the subscriber
+    /// (or the OpenTok Media Router).
+    pub packets_lost: i64,
+    /// The total number of video packets sent to the subscriber (or to the
+    /// OpenTok Media Router).
+    pub packets_sent: i64,
+    /// The total number of video bytes sent to the subscriber (or to the
+    /// OpenTok Media Router).
+    pub bytes_sent: i64,
+    /// The time when these stats were gathered, in milliseconds since the
+    /// Unix epoch.
+    pub timestamp: f64,
+    /// The time from which the cumulative totals started accumulating, in
+    /// milliseconds since the Unix epoch.
+    pub start_time: f64,
 }
 
-/// This enum represents the publisher audio and video stats.
-/// PublisherCallbacks includes a `on_audio_stats` and
-/// `on_video_stats` callback that are periodically called to
-/// report audio and video stats.
-enum PublisherStats {
-    Audio(MediaStats),
-    Video(MediaStats),
+impl From<&ffi::otc_publisher_video_stats> for PublisherVideoStats {
+    fn from(stats: &ffi::otc_publisher_video_stats) -> PublisherVideoStats {
+        PublisherVideoStats {
+            connection_id: string_or_empty(stats.connection_id),
+            subscriber_id: string_or_empty(stats.subscriber_id),
+            packets_lost: stats.packets_lost,
+            packets_sent: stats.packets_sent,
+            bytes_sent: stats.bytes_sent,
+            timestamp: stats.timestamp,
+            start_time: stats.start_time,
+        }
+    }
+}
+
+impl From<&ffi::otc_publisher_rtc_stats> for RtcStatsReport {
+    fn from(stats: &ffi::otc_publisher_rtc_stats) -> RtcStatsReport {
+        RtcStatsReport {
+            connection_id: string_or_empty(stats.connection_id),
+            json: string_or_empty(stats.json_array_of_reports),
+        }
+    }
+}
+
+/// Returns the ffi callbacks of `capturer` to hand to the SDK, or null to
+/// use the default capturer.
+fn capturer_callbacks(
+    capturer: &Option<VideoCapturer>,
+) -> *const ffi::otc_video_capturer_callbacks {
+    capturer.clone().map_or(std::ptr::null(), |mut capturer| {
+        &*capturer.callbacks().lock().unwrap() as *const ffi::otc_video_capturer_callbacks
+    })
+}
+
+fn string_or_empty(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Copies the `count` stats the SDK passes to a stats callback.
+///
+/// # Safety
+///
+/// `stats` must be null or point to `count` valid entries.
+unsafe fn stats_from_ffi<'a, T: 'a, S: From<&'a T>>(stats: *const T, count: ffi::size_t) -> Vec<S> {
+    if stats.is_null() {
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(stats, count as usize)
+        .iter()
+        .map(S::from)
+        .collect()
 }
 
 ffi_callback!(
@@ -113,8 +475,6 @@ ffi_callback!(
     f32
 );
 
-/*
-TODO
 ffi_callback!(
     on_audio_stats,
     *mut ffi::otc_publisher,
@@ -129,7 +489,15 @@ ffi_callback!(
     Publisher,
     *mut ffi::otc_publisher_video_stats,
     ffi::size_t
-);*/
+);
+
+ffi_callback!(
+    on_rtc_stats_report,
+    *mut ffi::otc_publisher,
+    Publisher,
+    *const ffi::otc_publisher_rtc_stats,
+    ffi::size_t
+);
 
 ffi_callback!(
     on_error,
@@ -154,8 +522,12 @@ pub struct PublisherCallbacks {
     on_stream_destroyed: Option<Box<dyn Fn(&Publisher, Stream) + Send + Sync + 'static>>,
     on_render_frame: Option<Box<dyn Fn(&Publisher, VideoFrame) + Send + Sync + 'static>>,
     on_audio_level_updated: Option<Box<dyn Fn(&Publisher, f32) + Send + Sync + 'static>>,
-    //TODO: on_audio_stats: Option<Box<dyn Fn(&Publisher, AudioStats)>>,
-    //TODO: on_video_stats: Option<Box<dyn Fn(&Publisher, VideoStats)>>,
+    on_audio_stats:
+        Option<Box<dyn Fn(&Publisher, Vec<PublisherAudioStats>) + Send + Sync + 'static>>,
+    on_video_stats:
+        Option<Box<dyn Fn(&Publisher, Vec<PublisherVideoStats>) + Send + Sync + 'static>>,
+    on_rtc_stats_report:
+        Option<Box<dyn Fn(&Publisher, Vec<RtcStatsReport>) + Send + Sync + 'static>>,
     on_error: Option<Box<dyn Fn(&Publisher, &str, PublisherError) + Send + Sync + 'static>>,
 }
 
@@ -168,6 +540,9 @@ impl PublisherCallbacks {
     callback!(on_stream_destroyed, &Publisher, Stream);
     callback!(on_render_frame, &Publisher, VideoFrame);
     callback!(on_audio_level_updated, &Publisher, f32);
+    callback!(on_audio_stats, &Publisher, Vec<PublisherAudioStats>);
+    callback!(on_video_stats, &Publisher, Vec<PublisherVideoStats>);
+    callback!(on_rtc_stats_report, &Publisher, Vec<RtcStatsReport>);
     callback!(on_error, &Publisher, &str, PublisherError);
 }
 
@@ -178,8 +553,12 @@ pub struct PublisherCallbacksBuilder {
     on_stream_destroyed: Option<Box<dyn Fn(&Publisher, Stream) + Send + Sync + 'static>>,
     on_render_frame: Option<Box<dyn Fn(&Publisher, VideoFrame) + Send + Sync + 'static>>,
     on_audio_level_updated: Option<Box<dyn Fn(&Publisher, f32) + Send + Sync + 'static>>,
-    //TODO: on_audio_stats: Option<Box<dyn Fn(&Publisher, AudioStats)>>,
-    //TODO: on_video_stats: Option<Box<dyn Fn(&Publisher, VideoStats)>>,
+    on_audio_stats:
+        Option<Box<dyn Fn(&Publisher, Vec<PublisherAudioStats>) + Send + Sync + 'static>>,
+    on_video_stats:
+        Option<Box<dyn Fn(&Publisher, Vec<PublisherVideoStats>) + Send + Sync + 'static>>,
+    on_rtc_stats_report:
+        Option<Box<dyn Fn(&Publisher, Vec<RtcStatsReport>) + Send + Sync + 'static>>,
     on_error: Option<Box<dyn Fn(&Publisher, &str, PublisherError) + Send + Sync + 'static>>,
 }
 
@@ -188,6 +567,9 @@ impl PublisherCallbacksBuilder {
     callback_setter!(on_stream_destroyed, &Publisher, Stream);
     callback_setter!(on_render_frame, &Publisher, VideoFrame);
     callback_setter!(on_audio_level_updated, &Publisher, f32);
+    callback_setter!(on_audio_stats, &Publisher, Vec<PublisherAudioStats>);
+    callback_setter!(on_video_stats, &Publisher, Vec<PublisherVideoStats>);
+    callback_setter!(on_rtc_stats_report, &Publisher, Vec<RtcStatsReport>);
     callback_setter!(on_error, &Publisher, &str, PublisherError);
 
     pub fn build(self) -> PublisherCallbacks {
@@ -196,17 +578,44 @@ impl PublisherCallbacksBuilder {
             on_stream_destroyed: self.on_stream_destroyed,
             on_render_frame: self.on_render_frame,
             on_audio_level_updated: self.on_audio_level_updated,
+            on_audio_stats: self.on_audio_stats,
+            on_video_stats: self.on_video_stats,
+            on_rtc_stats_report: self.on_rtc_stats_report,
             on_error: self.on_error,
         }
     }
 }
 
+/// Events related to an OpenTok publisher, one for each of the callbacks in
+/// `PublisherCallbacks`. They are delivered by the stream returned by
+/// `Publisher::events`, which requires the `futures` feature.
+#[derive(Clone)]
+pub enum PublisherEvent {
+    StreamCreated(Stream),
+    StreamDestroyed(Stream),
+    /// Not delivered to an event stream that has
+    /// `events::LOSSY_EVENT_QUEUE_LENGTH` frames pending already.
+    RenderFrame(VideoFrame),
+    AudioLevelUpdated(f32),
+    /// One entry per subscriber.
+    AudioStats(Vec<PublisherAudioStats>),
+    /// One entry per subscriber.
+    VideoStats(Vec<PublisherVideoStats>),
+    /// One report per subscriber in relayed sessions, a single one
+    /// otherwise.
+    RtcStatsReport(Vec<RtcStatsReport>),
+    /// The error description and code.
+    Error(String, PublisherError),
+}
+
 #[derive(Clone)]
 pub struct Publisher {
     ptr: Arc<AtomicPtr<*const ffi::otc_publisher>>,
     capturer: Option<VideoCapturer>,
-    callbacks: Arc<Mutex<PublisherCallbacks>>,
+    callbacks: Arc<PublisherCallbacks>,
     publishing: Arc<AtomicBool>,
+    events: Arc<EventSenders<PublisherEvent>>,
+    user_data: Arc<UserData<Publisher>>,
 }
 
 unsafe impl Sync for Publisher {}
@@ -215,33 +624,86 @@ unsafe impl Send for Publisher {}
 impl Publisher {
     pub fn new(name: &str, capturer: Option<VideoCapturer>, callbacks: PublisherCallbacks) -> Self {
         let name = CString::new(name).unwrap_or_default();
-        let capturer_callbacks = capturer.clone().map_or(std::ptr::null(), |mut capturer| {
-            &*capturer.callbacks().lock().unwrap() as *const ffi::otc_video_capturer_callbacks
+        let capturer_callbacks = capturer_callbacks(&capturer);
+        Publisher::create(capturer, callbacks, |ffi_callbacks| unsafe {
+            ffi::otc_publisher_new(name.as_ptr(), capturer_callbacks, ffi_callbacks)
+        })
+    }
+
+    /// Creates a publisher with advanced settings.
+    ///
+    /// * name: The name of the publisher.
+    /// * capturer: The video capturer, or `None` to use the default one.
+    /// * callbacks: An instance of PublisherCallbacks containing the handlers
+    ///   for events related to the publisher.
+    /// * settings: An instance of PublisherSettings, usually created with
+    ///   `PublisherSettings::builder()`.
+    pub fn with_settings(
+        name: &str,
+        capturer: Option<VideoCapturer>,
+        callbacks: PublisherCallbacks,
+        settings: &PublisherSettings,
+    ) -> Result<Publisher, OtcError> {
+        let name = CString::new(name).map_err(|_| OtcError::InvalidParam("name"))?;
+        let ffi_settings = match settings.to_ffi(&name, capturer_callbacks(&capturer)) {
+            Ok(ffi_settings) => ffi_settings,
+            Err(e) => {
+                if let Some(ref capturer) = capturer {
+                    capturer.release();
+                }
+                return Err(e);
+            }
+        };
+        let publisher = Publisher::create(capturer, callbacks, |ffi_callbacks| unsafe {
+            let ptr = ffi::otc_publisher_new_with_settings(ffi_callbacks, ffi_settings);
+            ffi::otc_publisher_settings_delete(ffi_settings);
+            ptr
         });
+        if publisher.inner().is_null() {
+            return Err(OtcError::NullError);
+        }
+        settings.apply(&publisher)?;
+        Ok(publisher)
+    }
 
+    fn create<F>(capturer: Option<VideoCapturer>, callbacks: PublisherCallbacks, new: F) -> Self
+    where
+        F: FnOnce(&ffi::otc_publisher_callbacks) -> *mut ffi::otc_publisher,
+    {
+        let publisher = Self {
+            ptr: Arc::new(AtomicPtr::new(std::ptr::null_mut())),
+            capturer,
+            callbacks: Arc::new(callbacks),
+            publishing: Default::default(),
+            events: Default::default(),
+            user_data: Default::default(),
+        };
         let ffi_callbacks = ffi::otc_publisher_callbacks {
             on_stream_created: Some(on_stream_created),
             on_stream_destroyed: Some(on_stream_destroyed),
             on_render_frame: Some(on_render_frame),
             on_audio_level_updated: Some(on_audio_level_updated),
-            on_audio_stats: None,
-            on_video_stats: None,
+            on_audio_stats: Some(on_audio_stats),
+            on_video_stats: Some(on_video_stats),
             on_error: Some(on_error),
-            user_data: std::ptr::null_mut(),
+            user_data: publisher.user_data.attach(&publisher, &LIVE),
             reserved: std::ptr::null_mut(),
         };
-        let ptr =
-            unsafe { ffi::otc_publisher_new(name.as_ptr(), capturer_callbacks, &ffi_callbacks) };
-        let publisher = Self {
-            ptr: Arc::new(AtomicPtr::new(ptr as *mut _)),
-            capturer,
-            callbacks: Arc::new(Mutex::new(callbacks)),
-            publishing: Default::default(),
-        };
-        INSTANCES
-            .lock()
-            .unwrap()
-            .insert(ptr as usize, publisher.clone());
+        let ptr = new(&ffi_callbacks);
+        if ptr.is_null() {
+            publisher.user_data.release(&LIVE);
+            if let Some(ref capturer) = publisher.capturer {
+                capturer.release();
+            }
+        } else {
+            let rtc_stats_report_cb = ffi::otc_publisher_rtc_stats_report_cb {
+                user_data: ffi_callbacks.user_data,
+                on_rtc_stats_report: Some(on_rtc_stats_report),
+                reserved: std::ptr::null_mut(),
+            };
+            unsafe { ffi::otc_publisher_set_rtc_stats_report_cb(ptr, rtc_stats_report_cb) };
+        }
+        publisher.ptr.store(ptr as *mut _, Ordering::Relaxed);
         publisher
     }
 
@@ -249,21 +711,61 @@ impl Publisher {
         self.ptr.load(Ordering::Relaxed) as *const _
     }
 
-    callback_call!(on_render_frame, *const ffi::otc_video_frame);
-    callback_call!(on_audio_level_updated, f32);
+    /// A handle to this publisher that does not keep it alive.
+    pub(crate) fn downgrade(&self) -> WeakPublisher {
+        WeakPublisher {
+            ptr: Arc::downgrade(&self.ptr),
+        }
+    }
+
+    /// Returns a stream of all the events related to this publisher that
+    /// happen from now on. Each event is sent right before the matching
+    /// callback in `PublisherCallbacks`, if any, is called.
+    #[cfg(feature = "futures")]
+    pub fn events(&self) -> impl futures::Stream<Item = PublisherEvent> + Unpin {
+        self.events.subscribe()
+    }
+
+    fn on_render_frame(&self, frame: *const ffi::otc_video_frame) {
+        self.events
+            .emit_lossy(|| PublisherEvent::RenderFrame(frame.into()));
+        self.callbacks.on_render_frame(self, frame.into());
+    }
+    callback_call!(on_audio_level_updated, f32 => PublisherEvent::AudioLevelUpdated);
+
+    fn on_audio_stats(&self, stats: *mut ffi::otc_publisher_audio_stats, count: ffi::size_t) {
+        let stats: Vec<PublisherAudioStats> = unsafe { stats_from_ffi(stats, count) };
+        self.events
+            .emit(|| PublisherEvent::AudioStats(stats.clone()));
+        self.callbacks.on_audio_stats(self, stats);
+    }
+
+    fn on_video_stats(&self, stats: *mut ffi::otc_publisher_video_stats, count: ffi::size_t) {
+        let stats: Vec<PublisherVideoStats> = unsafe { stats_from_ffi(stats, count) };
+        self.events
+            .emit(|| PublisherEvent::VideoStats(stats.clone()));
+        self.callbacks.on_video_stats(self, stats);
+    }
+
+    fn on_rtc_stats_report(&self, stats: *const ffi::otc_publisher_rtc_stats, count: ffi::size_t) {
+        let reports: Vec<RtcStatsReport> = unsafe { stats_from_ffi(stats, count) };
+        self.events
+            .emit(|| PublisherEvent::RtcStatsReport(reports.clone()));
+        self.callbacks.on_rtc_stats_report(self, reports);
+    }
 
     fn on_stream_created(&self, stream: *const ffi::otc_stream) {
         self.publishing.store(true, Ordering::Relaxed);
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_stream_created(self, stream.into());
-        }
+        self.events
+            .emit(|| PublisherEvent::StreamCreated(stream.into()));
+        self.callbacks.on_stream_created(self, stream.into());
     }
 
     fn on_stream_destroyed(&self, stream: *const ffi::otc_stream) {
         self.publishing.store(false, Ordering::Relaxed);
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_stream_destroyed(self, stream.into());
-        }
+        self.events
+            .emit(|| PublisherEvent::StreamDestroyed(stream.into()));
+        self.callbacks.on_stream_destroyed(self, stream.into());
     }
 
     fn on_error(&self, error_string: *const c_char, error_code: ffi::otc_publisher_error_code) {
@@ -271,13 +773,17 @@ impl Publisher {
             return;
         }
         let error_string = unsafe { CStr::from_ptr(error_string) };
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_error(
-                self,
-                error_string.to_str().unwrap_or_default(),
+        self.events.emit(|| {
+            PublisherEvent::Error(
+                error_string.to_string_lossy().into_owned(),
                 error_code.into(),
-            );
-        }
+            )
+        });
+        self.callbacks.on_error(
+            self,
+            error_string.to_str().unwrap_or_default(),
+            error_code.into(),
+        );
     }
 
     pub fn toggle_audio(&self, audio_enabled: bool) -> OtcResult {
@@ -306,6 +812,91 @@ impl Publisher {
         .into_result()
     }
 
+    /// Whether the publisher is publishing audio, as set with
+    /// `toggle_audio`.
+    pub fn is_publishing_audio(&self) -> Result<bool, OtcError> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
+        }
+        Ok(*OtcBool(unsafe {
+            ffi::otc_publisher_get_publish_audio(ptr as *const _)
+        }))
+    }
+
+    /// Whether the publisher is publishing video, as set with
+    /// `toggle_video`.
+    pub fn is_publishing_video(&self) -> Result<bool, OtcError> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
+        }
+        Ok(*OtcBool(unsafe {
+            ffi::otc_publisher_get_publish_video(ptr as *const _)
+        }))
+    }
+
+    /// Changes the type of the published video, for instance when a screen
+    /// share switches between slides and video. The SDK tunes encoding for
+    /// sharpness with `VideoType::Screen` and for motion with
+    /// `VideoType::Camera`.
+    ///
+    /// It takes a `VideoType` rather than a `StreamVideoType`, which also
+    /// has a `Custom` type publishers cannot set. A `StreamVideoType` can be
+    /// converted with `VideoType::try_from`.
+    ///
+    /// There is no `set_video_content_hint`: the 2.19.1 SDK has no video
+    /// content hints, and the video type is the closest control it offers.
+    pub fn set_video_type(&self, video_type: VideoType) -> OtcResult {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
+        }
+        unsafe { ffi::otc_publisher_set_video_type(ptr as *mut _, video_type.into()) }.into_result()
+    }
+
+    /// Returns the type of the published video.
+    pub fn video_type(&self) -> Result<VideoType, OtcError> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
+        }
+        Ok(unsafe { ffi::otc_publisher_get_video_type(ptr as *const _) }.into())
+    }
+
+    /// Sets the maximum audio bitrate, in bits per second. The SDK accepts
+    /// values from 6000 to 510000.
+    pub fn set_max_audio_bitrate(&self, bitrate: u32) -> OtcResult {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
+        }
+        unsafe { ffi::otc_publisher_set_max_audio_bitrate(ptr as *mut _, bitrate) }.into_result()
+    }
+
+    /// Returns the name of the publisher, or an empty string if it could not
+    /// be created.
+    pub fn name(&self) -> String {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return String::new();
+        }
+        string_or_empty(unsafe { ffi::otc_publisher_get_name(ptr as *const _) })
+    }
+
+    /// Returns the session the publisher is publishing to, if any.
+    pub fn session(&self) -> Option<Session> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return None;
+        }
+        let session = unsafe { ffi::otc_publisher_get_session(ptr as *const _) };
+        if session.is_null() {
+            return None;
+        }
+        Session::find(session)
+    }
+
     pub fn stream(&self) -> Option<Stream> {
         if self.ptr.load(Ordering::Relaxed).is_null() {
             return None;
@@ -318,6 +909,35 @@ impl Publisher {
         Some((stream_ptr as *const ffi::otc_stream).into())
     }
 
+    /// Requests the RTC stats report of the published stream. The report is
+    /// delivered asynchronously to `PublisherCallbacks::on_rtc_stats_report`.
+    pub fn get_rtc_stats_report(&self) -> OtcResult {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
+        }
+        unsafe { ffi::otc_publisher_get_rtc_stats_report(ptr as *const _) }.into_result()
+    }
+
+    /// Requests the RTC stats report of the published stream and waits until
+    /// it is delivered.
+    #[cfg(feature = "futures")]
+    pub async fn get_rtc_stats_report_async(&self) -> Result<Vec<RtcStatsReport>, AsyncError> {
+        use futures::StreamExt;
+
+        let mut events = self.events.subscribe();
+        self.get_rtc_stats_report()?;
+        while let Some(event) = events.next().await {
+            match event {
+                PublisherEvent::RtcStatsReport(reports) => return Ok(reports),
+                PublisherEvent::Error(_, error) => return Err(AsyncError::Publisher(error)),
+                PublisherEvent::StreamDestroyed(_) => break,
+                _ => {}
+            }
+        }
+        Err(AsyncError::Disconnected)
+    }
+
     pub fn unpublish(&self) -> OtcResult {
         if !self.publishing.load(Ordering::Relaxed) {
             return Ok(());
@@ -331,6 +951,9 @@ impl Publisher {
         unsafe {
             let session = ffi::otc_publisher_get_session(ptr as *const _);
             if !session.is_null() {
+                if let Some(session) = Session::find(session) {
+                    session.forget_publisher(self);
+                }
                 ffi::otc_session_unpublish(session, ptr as *mut _)
             } else {
                 return Ok(());
@@ -340,11 +963,41 @@ impl Publisher {
     }
 }
 
+/// A handle to a publisher that does not keep it alive, so that dropping
+/// the application's last clone still deletes it.
+#[derive(Clone)]
+pub(crate) struct WeakPublisher {
+    ptr: Weak<AtomicPtr<*const ffi::otc_publisher>>,
+}
+
+impl WeakPublisher {
+    /// A clone of the publisher, if it is still alive.
+    pub fn upgrade(&self) -> Option<Publisher> {
+        let mut found = None;
+        LIVE.for_each(|publisher| {
+            if self.is(publisher) {
+                found = Some(publisher.clone());
+            }
+        });
+        found
+    }
+
+    /// Whether this is a handle to `publisher`.
+    pub fn is(&self, publisher: &Publisher) -> bool {
+        std::ptr::eq(self.ptr.as_ptr(), Arc::as_ptr(&publisher.ptr))
+    }
+
+    /// Whether the publisher has been dropped.
+    pub fn is_dropped(&self) -> bool {
+        self.ptr.strong_count() == 0
+    }
+}
+
 impl Drop for Publisher {
     fn drop(&mut self) {
         let ptr = self.ptr.load(Ordering::Relaxed);
 
-        // 2 because we keep a reference in INSTANCES.
+        // 2 because the SDK holds a reference as user_data.
         if Arc::strong_count(&self.ptr) > 2 {
             return;
         }
@@ -359,10 +1012,7 @@ impl Drop for Publisher {
             ffi::otc_publisher_delete(ptr as *mut _);
         }
 
-        if let Ok(ref mut instances) = INSTANCES.try_lock() {
-            instances.remove(&(ptr as usize));
-        }
-
         self.ptr.store(std::ptr::null_mut(), Ordering::Relaxed);
+        self.user_data.release(&LIVE);
     }
 }
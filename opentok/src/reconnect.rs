@@ -0,0 +1,225 @@
+//! Automatic reconnection of sessions.
+//!
+//! A session with a `ReconnectPolicy`, set with
+//! `Session::set_reconnect_policy`, connects again on its own when its
+//! connection is lost, either because it reports
+//! `SessionError::ConnectionDropped` or because it is disconnected without
+//! `Session::disconnect` being called. Attempts are spaced with exponential
+//! backoff. Once connected again, the publishers the session was publishing
+//! are published again, and the subscribers it was subscribing resubscribe
+//! as soon as the session receives their streams again.
+//!
+//! The progress of the reconnection is reported with `ReconnectEvent`s, to
+//! the `on_reconnect` session callback and as `SessionEvent::Reconnect`.
+
+use crate::publisher::{Publisher, WeakPublisher};
+use crate::subscriber::{Subscriber, WeakSubscriber};
+
+use lazy_static::lazy_static;
+use log::warn;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SendError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+type Task = Box<dyn FnOnce() + Send>;
+
+lazy_static! {
+    /// Sends tasks to the thread running the attempts of every session.
+    static ref TIMER: Mutex<Option<Sender<(Instant, Task)>>> = Default::default();
+}
+
+/// How a session reconnects after losing its connection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Maximum number of attempts before giving up, or 0 to never give up.
+    /// Defaults to 5.
+    pub max_attempts: u32,
+    /// Delay before the first attempt. Defaults to 1 second.
+    pub initial_delay: Duration,
+    /// Upper bound of the delay between attempts, before jitter. Defaults
+    /// to 30 seconds.
+    pub max_delay: Duration,
+    /// Factor the delay is multiplied by after each attempt. Defaults to 2.
+    pub multiplier: f64,
+    /// Fraction of the delay by which it is randomly shortened or
+    /// lengthened, so that clients dropped together do not reconnect all at
+    /// once. It is clamped to [0, 1]. Defaults to 0.2.
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Whether `attempt`, counted from 1, is allowed.
+    pub fn allows(&self, attempt: u32) -> bool {
+        self.max_attempts == 0 || attempt <= self.max_attempts
+    }
+
+    /// The delay before `attempt`, counted from 1, jitter included.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = (self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent))
+            .min(self.max_delay.as_secs_f64());
+        let jitter = self.jitter.clamp(0., 1.);
+        let factor = 1. + jitter * (2. * random() - 1.);
+        let delay = (backoff * factor).max(0.);
+        // Only a `max_delay` close to `Duration::MAX` gets here.
+        if delay >= u64::MAX as f64 {
+            return self.max_delay;
+        }
+        Duration::from_secs_f64(delay)
+    }
+}
+
+/// A uniformly distributed number in [0, 1). Jitter needs no better
+/// generator.
+fn random() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Runs `task` after `delay`, on a thread shared by every session and
+/// started on first use. Sessions use it for their reconnection attempts,
+/// and to call into the SDK out of its own callbacks.
+pub(crate) fn schedule(delay: Duration, task: Task) -> io::Result<()> {
+    let mut timer = TIMER.lock().unwrap();
+    let mut task = (Instant::now() + delay, task);
+    if let Some(ref sender) = *timer {
+        match sender.send(task) {
+            Ok(()) => return Ok(()),
+            // The thread is gone.
+            Err(SendError(unsent)) => task = unsent,
+        }
+    }
+    let (sender, receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("opentok-reconnect".into())
+        .spawn(move || run_timer(receiver))?;
+    // The thread only returns once the sender is dropped.
+    let _ = sender.send(task);
+    *timer = Some(sender);
+    Ok(())
+}
+
+/// Runs the tasks received from `receiver` when due, in order.
+fn run_timer(receiver: Receiver<(Instant, Task)>) {
+    let mut pending: Vec<(Instant, Task)> = Vec::new();
+    loop {
+        let next = pending
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, (due, _))| *due)
+            .map(|(index, (due, _))| (index, *due));
+        let received = match next {
+            Some((index, due)) => {
+                let now = Instant::now();
+                if due <= now {
+                    let (_, task) = pending.remove(index);
+                    // A panicking callback must not cancel the tasks of the
+                    // other sessions.
+                    if panic::catch_unwind(AssertUnwindSafe(task)).is_err() {
+                        warn!("A scheduled task of a session panicked.");
+                    }
+                    continue;
+                }
+                receiver.recv_timeout(due - now)
+            }
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(task) => pending.push(task),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+/// Progress of the automatic reconnection of a session. Attempts are counted
+/// from 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReconnectEvent {
+    /// The attempt will start after the delay.
+    AttemptScheduled(u32, Duration),
+    /// The session is connecting.
+    AttemptStarted(u32),
+    /// The attempt failed. The error, if any, is reported to `on_error`.
+    AttemptFailed(u32),
+    /// The session is connected again, with this attempt. Its publishers
+    /// are being published again.
+    Reconnected(u32),
+    /// The policy allows no more attempts after this one, or the session
+    /// could not schedule them.
+    GaveUp(u32),
+}
+
+/// Reconnection state of a session.
+#[derive(Default)]
+pub(crate) struct Reconnection {
+    pub policy: Option<ReconnectPolicy>,
+    /// The token of the last connection.
+    pub token: String,
+    /// The attempt in progress, or 0.
+    pub attempt: u32,
+    /// Bumped to cancel the scheduled attempt.
+    pub generation: u64,
+    /// Whether the application asked to disconnect.
+    pub disconnect_requested: bool,
+    /// What to restore once reconnected, tracked while there is a policy.
+    /// The handles are weak, so that the application dropping a publisher
+    /// or subscriber still deletes it.
+    pub publishers: Vec<WeakPublisher>,
+    pub subscribers: Vec<WeakSubscriber>,
+}
+
+impl Reconnection {
+    /// Stops reconnecting and forgets what to restore.
+    pub fn cancel(&mut self) {
+        self.attempt = 0;
+        self.generation += 1;
+        self.publishers.clear();
+        self.subscribers.clear();
+    }
+
+    pub fn track_publisher(&mut self, publisher: &Publisher) {
+        self.publishers.retain(|p| !p.is_dropped());
+        if self.policy.is_some() && !self.publishers.iter().any(|p| p.is(publisher)) {
+            self.publishers.push(publisher.downgrade());
+        }
+    }
+
+    pub fn forget_publisher(&mut self, publisher: &Publisher) {
+        self.publishers.retain(|p| !p.is(publisher));
+    }
+
+    pub fn track_subscriber(&mut self, subscriber: &Subscriber) {
+        self.subscribers.retain(|s| !s.is_dropped());
+        if self.policy.is_some() && !self.subscribers.iter().any(|s| s.is(subscriber)) {
+            self.subscribers.push(subscriber.downgrade());
+        }
+    }
+
+    pub fn forget_subscriber(&mut self, subscriber: &Subscriber) {
+        self.subscribers.retain(|s| !s.is(subscriber));
+    }
+}
@@ -0,0 +1,299 @@
+//! Live map of the participants of a session.
+//!
+//! Every session keeps a `Roster`, returned by `Session::roster`, of the
+//! other clients connected to it and of the streams they publish. It is
+//! updated from the session callbacks before the application ones are
+//! called, so it is already up to date in them. The connection of the
+//! client itself and the streams it publishes are not part of it.
+//!
+//! Changes are reported with `RosterChange`s, to the watchers created with
+//! `Roster::watch` and as `SessionEvent::RosterChanged`.
+
+use crate::connection::Connection;
+use crate::stream::{Stream, StreamVideoType};
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A stream published to the session, as last reported.
+#[derive(Clone)]
+pub struct StreamInfo {
+    pub stream: Stream,
+    pub has_audio: bool,
+    pub has_video: bool,
+    pub width: i32,
+    pub height: i32,
+    pub video_type: StreamVideoType,
+}
+
+impl StreamInfo {
+    fn new(stream: &Stream) -> Self {
+        Self {
+            stream: stream.clone(),
+            has_audio: stream.has_audio(),
+            has_video: stream.has_video(),
+            width: stream.get_video_width(),
+            height: stream.get_video_height(),
+            video_type: stream.get_video_type(),
+        }
+    }
+
+    /// The identifier of the stream.
+    pub fn id(&self) -> String {
+        self.stream.id()
+    }
+
+    fn properties(&self) -> (bool, bool, i32, i32, StreamVideoType) {
+        (
+            self.has_audio,
+            self.has_video,
+            self.width,
+            self.height,
+            self.video_type,
+        )
+    }
+}
+
+/// A client connected to the session and the streams it publishes.
+#[derive(Clone)]
+pub struct Participant {
+    pub connection: Connection,
+    pub streams: Vec<StreamInfo>,
+    /// Whether the session reported the connection. It is not reported when
+    /// connection events are suppressed, in which case the participant is
+    /// only known through its streams.
+    announced: bool,
+}
+
+impl Participant {
+    /// The identifier of the connection of the participant.
+    pub fn id(&self) -> String {
+        self.connection.id()
+    }
+}
+
+/// A change of the roster.
+#[derive(Clone)]
+pub enum RosterChange {
+    ParticipantJoined(Connection),
+    ParticipantLeft(Connection),
+    StreamAdded(StreamInfo),
+    StreamRemoved(StreamInfo),
+    /// The audio or video flags, the dimensions or the video type of the
+    /// stream changed. It carries the updated stream.
+    StreamChanged(StreamInfo),
+}
+
+#[derive(Default)]
+struct RosterState {
+    participants: Vec<Participant>,
+    watchers: Vec<Sender<RosterChange>>,
+}
+
+impl RosterState {
+    fn participant(&mut self, connection_id: &str) -> Option<&mut Participant> {
+        self.participants
+            .iter_mut()
+            .find(|participant| participant.id() == connection_id)
+    }
+
+    fn stream(&mut self, stream_id: &str) -> Option<&mut StreamInfo> {
+        self.participants
+            .iter_mut()
+            .flat_map(|participant| participant.streams.iter_mut())
+            .find(|info| info.id() == stream_id)
+    }
+}
+
+/// The participants of a session. Clones share the same roster.
+#[derive(Clone, Default)]
+pub struct Roster {
+    state: Arc<Mutex<RosterState>>,
+}
+
+impl Roster {
+    /// A snapshot of the participants, in the order they joined.
+    pub fn participants(&self) -> Vec<Participant> {
+        self.state.lock().unwrap().participants.clone()
+    }
+
+    /// A snapshot of the participant with this connection identifier.
+    pub fn participant(&self, connection_id: &str) -> Option<Participant> {
+        self.state
+            .lock()
+            .unwrap()
+            .participant(connection_id)
+            .cloned()
+    }
+
+    /// A snapshot of the streams of every participant.
+    pub fn streams(&self) -> Vec<StreamInfo> {
+        self.state
+            .lock()
+            .unwrap()
+            .participants
+            .iter()
+            .flat_map(|participant| participant.streams.iter().cloned())
+            .collect()
+    }
+
+    /// A snapshot of the stream with this identifier.
+    pub fn stream(&self, stream_id: &str) -> Option<StreamInfo> {
+        self.state.lock().unwrap().stream(stream_id).cloned()
+    }
+
+    /// Returns a watcher receiving every change of the roster from now on.
+    pub fn watch(&self) -> RosterWatcher {
+        let (sender, receiver) = mpsc::channel();
+        self.state.lock().unwrap().watchers.push(sender);
+        RosterWatcher { receiver }
+    }
+
+    /// Applies `update` and reports the changes it makes, under the lock so
+    /// watchers get them in order. Returns the changes.
+    fn update<F: FnOnce(&mut RosterState, &mut Vec<RosterChange>)>(
+        &self,
+        update: F,
+    ) -> Vec<RosterChange> {
+        let mut state = self.state.lock().unwrap();
+        let mut changes = vec![];
+        update(&mut state, &mut changes);
+        for change in &changes {
+            state
+                .watchers
+                .retain(|watcher| watcher.send(change.clone()).is_ok());
+        }
+        changes
+    }
+
+    pub(crate) fn connection_created(&self, connection: &Connection) -> Vec<RosterChange> {
+        self.update(|state, changes| match state.participant(&connection.id()) {
+            Some(participant) => participant.announced = true,
+            None => {
+                state.participants.push(Participant {
+                    connection: connection.clone(),
+                    streams: vec![],
+                    announced: true,
+                });
+                changes.push(RosterChange::ParticipantJoined(connection.clone()));
+            }
+        })
+    }
+
+    pub(crate) fn connection_dropped(&self, connection: &Connection) -> Vec<RosterChange> {
+        let id = connection.id();
+        self.update(|state, changes| {
+            if let Some(index) = state.participants.iter().position(|p| p.id() == id) {
+                let participant = state.participants.remove(index);
+                changes.extend(
+                    participant
+                        .streams
+                        .into_iter()
+                        .map(RosterChange::StreamRemoved),
+                );
+                changes.push(RosterChange::ParticipantLeft(participant.connection));
+            }
+        })
+    }
+
+    pub(crate) fn stream_received(&self, stream: &Stream) -> Vec<RosterChange> {
+        let info = StreamInfo::new(stream);
+        let connection = stream.get_connection();
+        self.update(|state, changes| {
+            if state.stream(&info.id()).is_some() {
+                return;
+            }
+            if state.participant(&connection.id()).is_none() {
+                state.participants.push(Participant {
+                    connection: connection.clone(),
+                    streams: vec![],
+                    announced: false,
+                });
+                changes.push(RosterChange::ParticipantJoined(connection.clone()));
+            }
+            let participant = state.participant(&connection.id()).unwrap();
+            participant.streams.push(info.clone());
+            changes.push(RosterChange::StreamAdded(info));
+        })
+    }
+
+    pub(crate) fn stream_dropped(&self, stream: &Stream) -> Vec<RosterChange> {
+        let id = stream.id();
+        self.update(|state, changes| {
+            let position = state.participants.iter().enumerate().find_map(|(i, p)| {
+                p.streams
+                    .iter()
+                    .position(|info| info.id() == id)
+                    .map(|j| (i, j))
+            });
+            let (i, j) = match position {
+                Some(position) => position,
+                None => return,
+            };
+            let participant = &mut state.participants[i];
+            changes.push(RosterChange::StreamRemoved(participant.streams.remove(j)));
+            if !participant.announced && participant.streams.is_empty() {
+                let participant = state.participants.remove(i);
+                changes.push(RosterChange::ParticipantLeft(participant.connection));
+            }
+        })
+    }
+
+    /// Applies `change` to the known stream and reports it if it changed.
+    pub(crate) fn stream_changed<F: FnOnce(&mut StreamInfo)>(
+        &self,
+        stream: &Stream,
+        change: F,
+    ) -> Vec<RosterChange> {
+        let id = stream.id();
+        self.update(|state, changes| {
+            if let Some(info) = state.stream(&id) {
+                let before = info.properties();
+                change(info);
+                if before != info.properties() {
+                    changes.push(RosterChange::StreamChanged(info.clone()));
+                }
+            }
+        })
+    }
+
+    /// Removes every participant, once the session is disconnected.
+    pub(crate) fn clear(&self) -> Vec<RosterChange> {
+        self.update(|state, changes| {
+            for participant in std::mem::take(&mut state.participants) {
+                changes.extend(
+                    participant
+                        .streams
+                        .into_iter()
+                        .map(RosterChange::StreamRemoved),
+                );
+                changes.push(RosterChange::ParticipantLeft(participant.connection));
+            }
+        })
+    }
+}
+
+/// Receives the changes of a roster, in order, from the moment it is created
+/// with `Roster::watch`.
+pub struct RosterWatcher {
+    receiver: Receiver<RosterChange>,
+}
+
+impl RosterWatcher {
+    /// Waits for the next change. Returns `None` once the session and every
+    /// clone of its roster are dropped and every change has been received.
+    pub fn recv(&self) -> Option<RosterChange> {
+        self.receiver.recv().ok()
+    }
+
+    /// Waits for the next change for at most `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<RosterChange> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+
+    /// Returns the next change, if there is one already.
+    pub fn try_recv(&self) -> Option<RosterChange> {
+        self.receiver.try_recv().ok()
+    }
+}
@@ -0,0 +1,301 @@
+//! WebRTC statistics reports.
+//!
+//! Publishers and subscribers can be asked for the WebRTC statistics of their
+//! media streams with `Publisher::get_rtc_stats_report` and
+//! `Subscriber::get_rtc_stats_report`. The SDK delivers them as a JSON array
+//! similar to the browsers' `RTCStatsReport`, described in
+//! <https://w3c.github.io/webrtc-stats/>.
+//!
+//! `RtcStatsReport` keeps that JSON as is. With the `serde_json` feature it
+//! can also be parsed into typed entries for the most useful stats types,
+//! while any other entry is kept as raw JSON.
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RtcStatsError {
+    #[error("RTC stats report is not a valid JSON array: {0}")]
+    InvalidJson(String),
+}
+
+/// An RTC stats report for a media stream.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RtcStatsReport {
+    /// For a publisher in a relayed session, the connection ID of the
+    /// subscribing client this report is about. Empty otherwise.
+    pub connection_id: String,
+    /// The JSON array of stats reports, as delivered by the SDK.
+    pub json: String,
+}
+
+impl RtcStatsReport {
+    /// Parses the JSON report into its entries. This requires the
+    /// `serde_json` feature.
+    pub fn parse(&self) -> Result<Vec<RtcStats>, RtcStatsError> {
+        parse_json(&self.json)
+    }
+}
+
+/// An entry of an RTC stats report.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RtcStats {
+    OutboundRtp(OutboundRtpStats),
+    InboundRtp(InboundRtpStats),
+    RemoteInboundRtp(RemoteInboundRtpStats),
+    CandidatePair(CandidatePairStats),
+    Codec(CodecStats),
+    Transport(TransportStats),
+    /// Any other entry, as its JSON representation.
+    Other(String),
+}
+
+/// Statistics of an outgoing RTP stream (`outbound-rtp`).
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutboundRtpStats {
+    pub id: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: f64,
+    pub ssrc: Option<u64>,
+    /// `audio` or `video`.
+    pub kind: Option<String>,
+    pub codec_id: Option<String>,
+    pub packets_sent: Option<u64>,
+    pub bytes_sent: Option<u64>,
+    pub frames_encoded: Option<u64>,
+    pub frame_width: Option<u64>,
+    pub frame_height: Option<u64>,
+    pub frames_per_second: Option<f64>,
+    pub nack_count: Option<u64>,
+    pub pli_count: Option<u64>,
+    /// `none`, `cpu`, `bandwidth` or `other`.
+    pub quality_limitation_reason: Option<String>,
+}
+
+/// Statistics of an incoming RTP stream (`inbound-rtp`).
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InboundRtpStats {
+    pub id: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: f64,
+    pub ssrc: Option<u64>,
+    /// `audio` or `video`.
+    pub kind: Option<String>,
+    pub codec_id: Option<String>,
+    pub packets_received: Option<u64>,
+    pub packets_lost: Option<i64>,
+    pub bytes_received: Option<u64>,
+    /// Packet jitter, in seconds.
+    pub jitter: Option<f64>,
+    pub frames_decoded: Option<u64>,
+    pub frames_dropped: Option<u64>,
+    pub frame_width: Option<u64>,
+    pub frame_height: Option<u64>,
+    pub frames_per_second: Option<f64>,
+    pub audio_level: Option<f64>,
+    pub nack_count: Option<u64>,
+    pub pli_count: Option<u64>,
+}
+
+/// Statistics reported by the remote end about an outgoing RTP stream
+/// (`remote-inbound-rtp`).
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemoteInboundRtpStats {
+    pub id: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: f64,
+    pub ssrc: Option<u64>,
+    /// `audio` or `video`.
+    pub kind: Option<String>,
+    /// The id of the matching `outbound-rtp` entry.
+    pub local_id: Option<String>,
+    pub packets_lost: Option<i64>,
+    pub fraction_lost: Option<f64>,
+    /// Packet jitter, in seconds.
+    pub jitter: Option<f64>,
+    /// Latest round trip time, in seconds.
+    pub round_trip_time: Option<f64>,
+}
+
+/// Statistics of an ICE candidate pair (`candidate-pair`).
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CandidatePairStats {
+    pub id: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: f64,
+    pub transport_id: Option<String>,
+    pub local_candidate_id: Option<String>,
+    pub remote_candidate_id: Option<String>,
+    /// `frozen`, `waiting`, `in-progress`, `failed` or `succeeded`.
+    pub state: Option<String>,
+    pub nominated: Option<bool>,
+    /// Only reported by some implementations; see `selected_candidate_pair`.
+    pub selected: Option<bool>,
+    pub bytes_sent: Option<u64>,
+    pub bytes_received: Option<u64>,
+    /// Latest round trip time, in seconds.
+    pub current_round_trip_time: Option<f64>,
+    /// Bits per second.
+    pub available_outgoing_bitrate: Option<f64>,
+    /// Bits per second.
+    pub available_incoming_bitrate: Option<f64>,
+}
+
+/// A codec used by the RTP streams of the report (`codec`).
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CodecStats {
+    pub id: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: f64,
+    pub payload_type: Option<u64>,
+    /// For instance `video/VP8` or `audio/opus`.
+    pub mime_type: Option<String>,
+    pub clock_rate: Option<u64>,
+    pub channels: Option<u64>,
+    pub sdp_fmtp_line: Option<String>,
+}
+
+/// The transport carrying the RTP streams of the report (`transport`).
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransportStats {
+    pub id: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: f64,
+    pub selected_candidate_pair_id: Option<String>,
+    pub dtls_state: Option<String>,
+    pub bytes_sent: Option<u64>,
+    pub bytes_received: Option<u64>,
+}
+
+/// Returns the candidate pair in use, which is the one selected by a
+/// transport or, if no transport reports one, a pair flagged as selected.
+pub fn selected_candidate_pair(stats: &[RtcStats]) -> Option<&CandidatePairStats> {
+    let pairs = || {
+        stats.iter().filter_map(|stats| match stats {
+            RtcStats::CandidatePair(pair) => Some(pair),
+            _ => None,
+        })
+    };
+    stats
+        .iter()
+        .filter_map(|stats| match stats {
+            RtcStats::Transport(transport) => transport.selected_candidate_pair_id.as_ref(),
+            _ => None,
+        })
+        .find_map(|id| pairs().find(|pair| &pair.id == id))
+        .or_else(|| pairs().find(|pair| pair.selected == Some(true)))
+}
+
+#[cfg(feature = "serde_json")]
+fn parse_json(json: &str) -> Result<Vec<RtcStats>, RtcStatsError> {
+    let entries: Vec<serde_json::Value> =
+        serde_json::from_str(json).map_err(|e| RtcStatsError::InvalidJson(e.to_string()))?;
+    Ok(entries.iter().map(parse_entry).collect())
+}
+
+#[cfg(not(feature = "serde_json"))]
+fn parse_json(_: &str) -> Result<Vec<RtcStats>, RtcStatsError> {
+    Err(RtcStatsError::InvalidJson(
+        "JSON support requires the `serde_json` feature".into(),
+    ))
+}
+
+#[cfg(feature = "serde_json")]
+fn parse_entry(entry: &serde_json::Value) -> RtcStats {
+    let string = |key| entry.get(key).and_then(|v| v.as_str()).map(String::from);
+    let unsigned = |key| entry.get(key).and_then(|v| v.as_u64());
+    let signed = |key| entry.get(key).and_then(|v| v.as_i64());
+    let float = |key| entry.get(key).and_then(|v| v.as_f64());
+    let boolean = |key| entry.get(key).and_then(|v| v.as_bool());
+    let id = string("id").unwrap_or_default();
+    let timestamp = float("timestamp").unwrap_or_default();
+
+    match entry.get("type").and_then(|v| v.as_str()) {
+        Some("outbound-rtp") => RtcStats::OutboundRtp(OutboundRtpStats {
+            id,
+            timestamp,
+            ssrc: unsigned("ssrc"),
+            kind: string("kind"),
+            codec_id: string("codecId"),
+            packets_sent: unsigned("packetsSent"),
+            bytes_sent: unsigned("bytesSent"),
+            frames_encoded: unsigned("framesEncoded"),
+            frame_width: unsigned("frameWidth"),
+            frame_height: unsigned("frameHeight"),
+            frames_per_second: float("framesPerSecond"),
+            nack_count: unsigned("nackCount"),
+            pli_count: unsigned("pliCount"),
+            quality_limitation_reason: string("qualityLimitationReason"),
+        }),
+        Some("inbound-rtp") => RtcStats::InboundRtp(InboundRtpStats {
+            id,
+            timestamp,
+            ssrc: unsigned("ssrc"),
+            kind: string("kind"),
+            codec_id: string("codecId"),
+            packets_received: unsigned("packetsReceived"),
+            packets_lost: signed("packetsLost"),
+            bytes_received: unsigned("bytesReceived"),
+            jitter: float("jitter"),
+            frames_decoded: unsigned("framesDecoded"),
+            frames_dropped: unsigned("framesDropped"),
+            frame_width: unsigned("frameWidth"),
+            frame_height: unsigned("frameHeight"),
+            frames_per_second: float("framesPerSecond"),
+            audio_level: float("audioLevel"),
+            nack_count: unsigned("nackCount"),
+            pli_count: unsigned("pliCount"),
+        }),
+        Some("remote-inbound-rtp") => RtcStats::RemoteInboundRtp(RemoteInboundRtpStats {
+            id,
+            timestamp,
+            ssrc: unsigned("ssrc"),
+            kind: string("kind"),
+            local_id: string("localId"),
+            packets_lost: signed("packetsLost"),
+            fraction_lost: float("fractionLost"),
+            jitter: float("jitter"),
+            round_trip_time: float("roundTripTime"),
+        }),
+        Some("candidate-pair") => RtcStats::CandidatePair(CandidatePairStats {
+            id,
+            timestamp,
+            transport_id: string("transportId"),
+            local_candidate_id: string("localCandidateId"),
+            remote_candidate_id: string("remoteCandidateId"),
+            state: string("state"),
+            nominated: boolean("nominated"),
+            selected: boolean("selected"),
+            bytes_sent: unsigned("bytesSent"),
+            bytes_received: unsigned("bytesReceived"),
+            current_round_trip_time: float("currentRoundTripTime"),
+            available_outgoing_bitrate: float("availableOutgoingBitrate"),
+            available_incoming_bitrate: float("availableIncomingBitrate"),
+        }),
+        Some("codec") => RtcStats::Codec(CodecStats {
+            id,
+            timestamp,
+            payload_type: unsigned("payloadType"),
+            mime_type: string("mimeType"),
+            clock_rate: unsigned("clockRate"),
+            channels: unsigned("channels"),
+            sdp_fmtp_line: string("sdpFmtpLine"),
+        }),
+        Some("transport") => RtcStats::Transport(TransportStats {
+            id,
+            timestamp,
+            selected_candidate_pair_id: string("selectedCandidatePairId"),
+            dtls_state: string("dtlsState"),
+            bytes_sent: unsigned("bytesSent"),
+            bytes_received: unsigned("bytesReceived"),
+        }),
+        _ => RtcStats::Other(entry.to_string()),
+    }
+}
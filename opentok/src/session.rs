@@ -1,20 +1,34 @@
 use crate::connection::Connection;
 use crate::enums::{IntoResult, OtcBool, OtcError, OtcResult};
-use crate::publisher::Publisher;
+#[cfg(feature = "futures")]
+use crate::events::AsyncError;
+use crate::events::EventSenders;
+#[cfg(feature = "mock")]
+use crate::mock::ffi;
+#[cfg(feature = "futures")]
+use crate::publisher::PublisherEvent;
+use crate::publisher::{Publisher, WeakPublisher};
+use crate::reconnect::{self, ReconnectEvent, ReconnectPolicy, Reconnection};
+use crate::roster::{Roster, RosterChange};
 use crate::stream::{Stream, StreamVideoType};
-use crate::subscriber::Subscriber;
+#[cfg(feature = "futures")]
+use crate::subscriber::SubscriberEvent;
+use crate::subscriber::{self, Subscriber, WeakSubscriber};
+use crate::user_data::{Registry, UserData};
 
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use log::warn;
 use std::ffi::{CStr, CString};
+use std::io;
 use std::os::raw::{c_char, c_void};
 use std::sync::atomic::{AtomicPtr, Ordering};
-use std::sync::mpsc::{self, Sender};
-use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 
 lazy_static! {
-    pub static ref INSTANCES: Arc<Mutex<HashMap<usize, Session>>> = Default::default();
+    pub(crate) static ref LIVE: Registry<Session> = Default::default();
 }
 
 /// Errors associated with an OpenTok session.
@@ -127,6 +141,64 @@ impl From<ffi::otc_session_error_code> for SessionError {
     }
 }
 
+fn into_session_result(status: ffi::otc_status) -> Result<(), SessionError> {
+    match status as u32 {
+        ffi::otc_constant_OTC_SUCCESS => Ok(()),
+        code => Err(code.into()),
+    }
+}
+
+/// Maximum length, in characters, of the type of a signal.
+pub const MAX_SIGNAL_TYPE_LENGTH: usize = 128;
+/// Maximum length, in bytes, of the data of a signal.
+pub const MAX_SIGNAL_DATA_LENGTH: usize = 8192;
+
+/// Options associated with an OpenTok signal.
+#[derive(Clone, Copy, Debug)]
+pub struct SignalOptions {
+    /// Upon reconnecting to the session, whether to send any signals that
+    /// were initiated while disconnected. Defaults to `true`.
+    pub retry_after_reconnect: bool,
+}
+
+impl Default for SignalOptions {
+    fn default() -> Self {
+        Self {
+            retry_after_reconnect: true,
+        }
+    }
+}
+
+impl From<SignalOptions> for ffi::otc_signal_options {
+    fn from(options: SignalOptions) -> ffi::otc_signal_options {
+        ffi::otc_signal_options {
+            retry_after_reconnect: OtcBool::from(options.retry_after_reconnect).0,
+        }
+    }
+}
+
+/// Validates the type and data of a signal before handing them to the SDK.
+///
+/// The type must be at most 128 characters long and contain only letters,
+/// numbers, '-', '_' and '~'. The data must be at most 8kB.
+fn signal_cstrings(type_: &str, data: &str) -> Result<(CString, CString), SessionError> {
+    if type_.chars().count() > MAX_SIGNAL_TYPE_LENGTH {
+        return Err(SessionError::SignalTypeTooLong);
+    }
+    if !type_
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '~')
+    {
+        return Err(SessionError::InvalidSignalType);
+    }
+    if data.len() > MAX_SIGNAL_DATA_LENGTH {
+        return Err(SessionError::SignalDataTooLong);
+    }
+    let type_ = CString::new(type_).map_err(|_| SessionError::InvalidSignalType)?;
+    let data = CString::new(data).map_err(|_| SessionError::NullOrInvalidParameter)?;
+    Ok((type_, data))
+}
+
 ffi_callback!(on_connected, *mut ffi::otc_session, Session);
 ffi_callback!(on_reconnection_started, *mut ffi::otc_session, Session);
 ffi_callback!(on_reconnected, *mut ffi::otc_session, Session);
@@ -236,6 +308,7 @@ pub struct SessionCallbacks {
     on_archive_started: Option<Box<dyn Fn(&Session, &str, &str) + Send + Sync + 'static>>,
     on_archive_stopped: Option<Box<dyn Fn(&Session, &str) + Send + Sync + 'static>>,
     on_error: Option<Box<dyn Fn(&Session, &str, SessionError) + Send + Sync + 'static>>,
+    on_reconnect: Option<Box<dyn Fn(&Session, ReconnectEvent) + Send + Sync + 'static>>,
 }
 
 impl SessionCallbacks {
@@ -307,6 +380,8 @@ impl SessionCallbacks {
             callback(session, error_string, error);
         }
     }
+
+    callback!(on_reconnect, &Session, ReconnectEvent);
 }
 
 #[derive(Default)]
@@ -333,6 +408,7 @@ pub struct SessionCallbacksBuilder {
     on_archive_started: Option<Box<dyn Fn(&Session, &str, &str) + Send + Sync + 'static>>,
     on_archive_stopped: Option<Box<dyn Fn(&Session, &str) + Send + Sync + 'static>>,
     on_error: Option<Box<dyn Fn(&Session, &str, SessionError) + Send + Sync + 'static>>,
+    on_reconnect: Option<Box<dyn Fn(&Session, ReconnectEvent) + Send + Sync + 'static>>,
 }
 
 impl SessionCallbacksBuilder {
@@ -363,6 +439,7 @@ impl SessionCallbacksBuilder {
     callback_setter!(on_archive_started, &Session, &str, &str);
     callback_setter!(on_archive_stopped, &Session, &str);
     callback_setter!(on_error, &Session, &str, SessionError);
+    callback_setter!(on_reconnect, &Session, ReconnectEvent);
 
     pub fn build(self) -> SessionCallbacks {
         SessionCallbacks {
@@ -382,15 +459,290 @@ impl SessionCallbacksBuilder {
             on_archive_started: self.on_archive_started,
             on_archive_stopped: self.on_archive_stopped,
             on_error: self.on_error,
+            on_reconnect: self.on_reconnect,
+        }
+    }
+}
+
+/// Events related to an OpenTok session, one for each of the callbacks in
+/// `SessionCallbacks`. They are delivered by the stream returned by
+/// `Session::events`, which requires the `futures` feature.
+#[derive(Clone)]
+pub enum SessionEvent {
+    Connected,
+    ReconnectionStarted,
+    Reconnected,
+    Disconnected,
+    ConnectionCreated(Connection),
+    ConnectionDropped(Connection),
+    StreamReceived(Stream),
+    StreamDropped(Stream),
+    StreamHasAudioChanged(Stream, bool),
+    StreamHasVideoChanged(Stream, bool),
+    /// The stream and its new width and height.
+    StreamVideoDimensionsChanged(Stream, i32, i32),
+    StreamVideoTypeChanged(Stream, StreamVideoType),
+    /// The type and data of the signal and the connection that sent it.
+    SignalReceived(String, String, Connection),
+    /// The archive ID and name.
+    ArchiveStarted(String, String),
+    /// The archive ID.
+    ArchiveStopped(String),
+    /// The error description and code.
+    Error(String, SessionError),
+    /// Progress of the automatic reconnection, see
+    /// `Session::set_reconnect_policy`.
+    Reconnect(ReconnectEvent),
+    /// A change of the connection state, see `Session::watch_state`.
+    StateChanged(StateTransition),
+    /// A change of the participants of the session, see `Session::roster`.
+    RosterChanged(RosterChange),
+}
+
+/// A custom ICE (TURN) server.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IceServer {
+    /// The URL of the ICE server.
+    pub url: String,
+    /// The username for the TURN server.
+    pub user: String,
+    /// The credential for the TURN server.
+    pub credential: String,
+}
+
+/// Custom ICE server configuration. This is part of the configurable TURN
+/// feature. Check <https://tokbox.com/developer/guides/configurable-turn-servers>
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IceConfig {
+    /// The list of custom ICE servers.
+    pub servers: Vec<IceServer>,
+    /// Whether the client will force connectivity through TURN always and
+    /// ignore all other ICE candidates.
+    pub force_turn: bool,
+    /// Whether the client will use custom TURN servers only, instead of
+    /// both OpenTok TURN servers and the custom TURN servers.
+    pub use_custom_turn_only: bool,
+}
+
+/// Advanced settings for an OpenTok session.
+///
+/// Single peer connection mode is not exposed by the 2.19.1 SDK, so there
+/// is no setting for it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionSettings {
+    connection_events_suppressed: bool,
+    proxy_url: Option<String>,
+    ip_whitelist: bool,
+    ice_config: Option<IceConfig>,
+}
+
+impl SessionSettings {
+    pub fn builder() -> SessionSettingsBuilder {
+        SessionSettingsBuilder::default()
+    }
+
+    /// Whether connection events are suppressed.
+    pub fn connection_events_suppressed(&self) -> bool {
+        self.connection_events_suppressed
+    }
+
+    /// The IP proxy URL, if any.
+    pub fn proxy_url(&self) -> Option<&str> {
+        self.proxy_url.as_deref()
+    }
+
+    /// Whether the client uses the IP address white list.
+    pub fn ip_whitelist(&self) -> bool {
+        self.ip_whitelist
+    }
+
+    /// The custom ICE server configuration, if any.
+    pub fn ice_config(&self) -> Option<&IceConfig> {
+        self.ice_config.as_ref()
+    }
+
+    /// Creates the native settings instance. The caller owns the returned
+    /// pointer and must release it with `otc_session_settings_delete`.
+    fn to_ffi(&self) -> Result<*mut ffi::otc_session_settings, OtcError> {
+        let proxy_url = match self.proxy_url {
+            Some(ref proxy_url) => Some(
+                CString::new(proxy_url.as_str())
+                    .map_err(|_| OtcError::InvalidParam("proxy_url"))?,
+            ),
+            None => None,
+        };
+
+        let mut urls = vec![];
+        let mut users = vec![];
+        let mut credentials = vec![];
+        if let Some(ref ice_config) = self.ice_config {
+            for server in ice_config.servers.iter() {
+                urls.push(
+                    CString::new(server.url.as_str())
+                        .map_err(|_| OtcError::InvalidParam("ice_url"))?,
+                );
+                users.push(
+                    CString::new(server.user.as_str())
+                        .map_err(|_| OtcError::InvalidParam("ice_user"))?,
+                );
+                credentials.push(
+                    CString::new(server.credential.as_str())
+                        .map_err(|_| OtcError::InvalidParam("ice_credential"))?,
+                );
+            }
+        }
+        let mut url_ptrs: Vec<*mut c_char> = urls.iter().map(|s| s.as_ptr() as *mut _).collect();
+        let mut user_ptrs: Vec<*mut c_char> = users.iter().map(|s| s.as_ptr() as *mut _).collect();
+        let mut credential_ptrs: Vec<*mut c_char> =
+            credentials.iter().map(|s| s.as_ptr() as *mut _).collect();
+
+        let settings = unsafe { ffi::otc_session_settings_new() };
+        if settings.is_null() {
+            return Err(OtcError::NullError);
+        }
+
+        let result = unsafe {
+            ffi::otc_session_settings_set_connection_events_suppressed(
+                settings,
+                OtcBool::from(self.connection_events_suppressed).0,
+            )
+            .into_result()
+            .and_then(|_| {
+                ffi::otc_session_settings_set_ip_whitelist(
+                    settings,
+                    OtcBool::from(self.ip_whitelist).0,
+                )
+                .into_result()
+            })
+            .and_then(|_| match proxy_url {
+                Some(ref proxy_url) => {
+                    ffi::otc_session_settings_set_proxy_url(settings, proxy_url.as_ptr())
+                        .into_result()
+                }
+                None => Ok(()),
+            })
+            .and_then(|_| match self.ice_config {
+                Some(ref ice_config) => {
+                    let ffi_ice_config = ffi::otc_custom_ice_config {
+                        num_ice_servers: url_ptrs.len() as i32,
+                        ice_url: url_ptrs.as_mut_ptr(),
+                        ice_user: user_ptrs.as_mut_ptr(),
+                        ice_credential: credential_ptrs.as_mut_ptr(),
+                        force_turn: OtcBool::from(ice_config.force_turn).0,
+                        use_custom_turn_only: OtcBool::from(ice_config.use_custom_turn_only).0,
+                    };
+                    ffi::otc_session_settings_set_custom_ice_config(settings, &ffi_ice_config)
+                        .into_result()
+                }
+                None => Ok(()),
+            })
+        };
+
+        if let Err(e) = result {
+            unsafe {
+                ffi::otc_session_settings_delete(settings);
+            }
+            return Err(e);
+        }
+
+        Ok(settings)
+    }
+}
+
+#[derive(Default)]
+pub struct SessionSettingsBuilder {
+    connection_events_suppressed: bool,
+    proxy_url: Option<String>,
+    ip_whitelist: bool,
+    ice_config: Option<IceConfig>,
+}
+
+impl SessionSettingsBuilder {
+    /// Prevents connection events, to support large interactive video
+    /// sessions. The `on_connection_created` and `on_connection_dropped`
+    /// callbacks won't be called when other clients connect to or
+    /// disconnect from the session.
+    pub fn connection_events_suppressed(self, suppress: bool) -> Self {
+        Self {
+            connection_events_suppressed: suppress,
+            ..self
+        }
+    }
+
+    /// Sets the IP proxy URL. Check <https://tokbox.com/developer/guides/ip-proxy/>
+    pub fn proxy_url(self, proxy_url: &str) -> Self {
+        Self {
+            proxy_url: Some(proxy_url.to_owned()),
+            ..self
+        }
+    }
+
+    /// Whether the client should use the IP address white list. This is
+    /// an add-on feature for Enterprise partners.
+    pub fn ip_whitelist(self, ip_whitelist: bool) -> Self {
+        Self {
+            ip_whitelist,
+            ..self
+        }
+    }
+
+    /// Adds a custom ICE server.
+    pub fn ice_server(mut self, url: &str, user: &str, credential: &str) -> Self {
+        self.ice_config
+            .get_or_insert_with(Default::default)
+            .servers
+            .push(IceServer {
+                url: url.to_owned(),
+                user: user.to_owned(),
+                credential: credential.to_owned(),
+            });
+        self
+    }
+
+    /// Whether the client should force connectivity through TURN always
+    /// and ignore all other ICE candidates.
+    pub fn force_turn(mut self, force_turn: bool) -> Self {
+        self.ice_config
+            .get_or_insert_with(Default::default)
+            .force_turn = force_turn;
+        self
+    }
+
+    /// Whether the client should use the custom TURN servers only, instead
+    /// of both the OpenTok TURN servers and the custom ones.
+    pub fn use_custom_turn_only(mut self, use_custom_turn_only: bool) -> Self {
+        self.ice_config
+            .get_or_insert_with(Default::default)
+            .use_custom_turn_only = use_custom_turn_only;
+        self
+    }
+
+    /// Sets the whole custom ICE server configuration, replacing any
+    /// previously added server.
+    pub fn ice_config(self, ice_config: IceConfig) -> Self {
+        Self {
+            ice_config: Some(ice_config),
+            ..self
+        }
+    }
+
+    pub fn build(self) -> SessionSettings {
+        SessionSettings {
+            connection_events_suppressed: self.connection_events_suppressed,
+            proxy_url: self.proxy_url,
+            ip_whitelist: self.ip_whitelist,
+            ice_config: self.ice_config,
         }
     }
 }
 
 /// Enumeration of all possible connection states.
-#[derive(Clone, Debug, PartialEq)]
-enum ConnectionState {
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ConnectionState {
     Connected,
     Connecting,
+    /// The connection was interrupted and the SDK is restoring it.
+    Reconnecting,
     Disconnected,
     Disconnecting,
 }
@@ -401,12 +753,80 @@ impl Default for ConnectionState {
     }
 }
 
+/// Why the connection state of a session changed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum StateCause {
+    /// The application called `Session::connect`.
+    ConnectRequested,
+    /// The application called `Session::disconnect`.
+    DisconnectRequested,
+    /// The automatic reconnection started this attempt, counted from 1.
+    ReconnectAttempt(u32),
+    /// The SDK reported the session connected.
+    Connected,
+    /// The SDK reported the connection interrupted and started restoring it.
+    ConnectionInterrupted,
+    /// The SDK restored the interrupted connection.
+    Reconnected,
+    /// The SDK reported the session disconnected, as requested.
+    Disconnected,
+    /// The SDK reported the session disconnected without being asked to.
+    ConnectionLost,
+    /// The session failed to connect with this error.
+    Error(SessionError),
+}
+
+/// A change of the connection state of a session.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StateTransition {
+    pub from: ConnectionState,
+    pub to: ConnectionState,
+    /// When the state changed.
+    pub time: SystemTime,
+    pub cause: StateCause,
+}
+
+/// Receives the connection state transitions of a session, in order, from
+/// the moment it is created with `Session::watch_state`.
+pub struct StateWatcher {
+    state: Arc<Mutex<ConnectionState>>,
+    receiver: Receiver<StateTransition>,
+}
+
+impl StateWatcher {
+    /// The current connection state of the session.
+    pub fn state(&self) -> ConnectionState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Waits for the next transition. Returns `None` once the session is
+    /// dropped and every transition has been received.
+    pub fn recv(&self) -> Option<StateTransition> {
+        self.receiver.recv().ok()
+    }
+
+    /// Waits for the next transition for at most `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<StateTransition> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+
+    /// Returns the next transition, if there is one already.
+    pub fn try_recv(&self) -> Option<StateTransition> {
+        self.receiver.try_recv().ok()
+    }
+}
+
 #[derive(Clone)]
 pub struct Session {
     ptr: Arc<AtomicPtr<*mut ffi::otc_session>>,
-    callbacks: Arc<Mutex<SessionCallbacks>>,
+    callbacks: Arc<SessionCallbacks>,
     connection_state: Arc<Mutex<ConnectionState>>,
+    state_watchers: Arc<Mutex<Vec<Sender<StateTransition>>>>,
     disconnect_watcher: Arc<Mutex<Option<Sender<()>>>>,
+    events: Arc<EventSenders<SessionEvent>>,
+    reconnection: Arc<Mutex<Reconnection>>,
+    roster: Roster,
+    user_data: Arc<UserData<Session>>,
 }
 
 unsafe impl Send for Session {}
@@ -423,10 +843,48 @@ impl Session {
         api_key: &str,
         session_id: &str,
         callbacks: SessionCallbacks,
+    ) -> Result<Session, OtcError> {
+        Session::create(api_key, session_id, callbacks, None)
+    }
+
+    /// Creates a new OpenTok session with advanced settings.
+    ///
+    /// * api_key: Your OpenTok API key. You can get it from <https://tokbox.com/account>
+    /// * session_id: The identifier of the session.
+    /// * callbacks: An instance of SessionCallbacks containing the handlers for events
+    /// related to the session.
+    /// * settings: An instance of SessionSettings, usually created with
+    /// `SessionSettings::builder()`.
+    pub fn with_settings(
+        api_key: &str,
+        session_id: &str,
+        callbacks: SessionCallbacks,
+        settings: &SessionSettings,
+    ) -> Result<Session, OtcError> {
+        Session::create(api_key, session_id, callbacks, Some(settings))
+    }
+
+    fn create(
+        api_key: &str,
+        session_id: &str,
+        callbacks: SessionCallbacks,
+        settings: Option<&SessionSettings>,
     ) -> Result<Session, OtcError> {
         let api_key = CString::new(api_key).map_err(|_| OtcError::InvalidParam("api_key"))?;
         let session_id =
             CString::new(session_id).map_err(|_| OtcError::InvalidParam("session_id"))?;
+        let ffi_settings = settings.map(SessionSettings::to_ffi).transpose()?;
+        let session = Session {
+            ptr: Arc::new(AtomicPtr::new(std::ptr::null_mut())),
+            callbacks: Arc::new(callbacks),
+            connection_state: Arc::new(Mutex::new(ConnectionState::Disconnected)),
+            state_watchers: Default::default(),
+            disconnect_watcher: Default::default(),
+            events: Default::default(),
+            reconnection: Default::default(),
+            roster: Default::default(),
+            user_data: Default::default(),
+        };
         let ffi_callbacks = ffi::otc_session_callbacks {
             on_connected: Some(on_connected),
             on_reconnection_started: Some(on_reconnection_started),
@@ -444,50 +902,154 @@ impl Session {
             on_archive_started: Some(on_archive_started),
             on_archive_stopped: Some(on_archive_stopped),
             on_error: Some(on_error),
-            user_data: std::ptr::null_mut(),
+            user_data: session.user_data.attach(&session, &LIVE),
             reserved: std::ptr::null_mut(),
         };
-        let session_ptr =
-            unsafe { ffi::otc_session_new(api_key.as_ptr(), session_id.as_ptr(), &ffi_callbacks) };
+        let session_ptr = match ffi_settings {
+            Some(ffi_settings) => {
+                let session_ptr = unsafe {
+                    ffi::otc_session_new_with_settings(
+                        api_key.as_ptr(),
+                        session_id.as_ptr(),
+                        &ffi_callbacks,
+                        ffi_settings,
+                    )
+                };
+                unsafe {
+                    ffi::otc_session_settings_delete(ffi_settings);
+                }
+                session_ptr
+            }
+            None => unsafe {
+                ffi::otc_session_new(api_key.as_ptr(), session_id.as_ptr(), &ffi_callbacks)
+            },
+        };
         if session_ptr.is_null() {
+            session.user_data.release(&LIVE);
             return Err(OtcError::NullError);
         }
-        let session = Session {
-            ptr: Arc::new(AtomicPtr::new(session_ptr as *mut _)),
-            callbacks: Arc::new(Mutex::new(callbacks)),
-            connection_state: Arc::new(Mutex::new(ConnectionState::Disconnected)),
-            disconnect_watcher: Default::default(),
-        };
-        INSTANCES
-            .lock()
-            .unwrap()
-            .insert(session_ptr as usize, session.clone());
+        session.ptr.store(session_ptr as *mut _, Ordering::Relaxed);
         Ok(session)
     }
 
+    pub fn inner(&self) -> *const ffi::otc_session {
+        self.ptr.load(Ordering::Relaxed) as *const _
+    }
+
     string_getter!(
         /// Gets the uniquer identifier for this session.
         => (id, otc_session_get_id)
     );
 
+    /// Gets the connection of this client to the session, or `None` if
+    /// the client is not connected.
+    pub fn connection(&self) -> Option<Connection> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return None;
+        }
+        let connection = unsafe { ffi::otc_session_get_connection(ptr as *const _) };
+        if connection.is_null() {
+            return None;
+        }
+        Some((connection as *const ffi::otc_connection).into())
+    }
+
+    /// The connection state of the session.
+    pub fn state(&self) -> ConnectionState {
+        *self.connection_state.lock().unwrap()
+    }
+
+    /// Returns a watcher receiving every connection state transition of the
+    /// session from now on.
+    pub fn watch_state(&self) -> StateWatcher {
+        let (sender, receiver) = mpsc::channel();
+        // Registered under the state lock so no transition is missed.
+        let state = self.connection_state.lock().unwrap();
+        self.state_watchers.lock().unwrap().push(sender);
+        drop(state);
+        StateWatcher {
+            state: self.connection_state.clone(),
+            receiver,
+        }
+    }
+
+    /// The other clients connected to the session and the streams they
+    /// publish, kept up to date from the session callbacks.
+    pub fn roster(&self) -> Roster {
+        self.roster.clone()
+    }
+
+    fn report_roster(&self, changes: Vec<RosterChange>) {
+        for change in changes {
+            self.events.emit(|| SessionEvent::RosterChanged(change));
+        }
+    }
+
+    fn set_state(&self, state: ConnectionState, cause: StateCause) {
+        self.set_state_if(state, cause, |_| true);
+    }
+
+    /// Changes the connection state to `state` if `condition` holds for the
+    /// current one, and reports the transition. Returns whether it changed.
+    fn set_state_if<F: FnOnce(ConnectionState) -> bool>(
+        &self,
+        state: ConnectionState,
+        cause: StateCause,
+        condition: F,
+    ) -> bool {
+        let mut current = self.connection_state.lock().unwrap();
+        if *current == state || !condition(*current) {
+            return false;
+        }
+        let transition = StateTransition {
+            from: std::mem::replace(&mut *current, state),
+            to: state,
+            time: SystemTime::now(),
+            cause,
+        };
+        // Reported under the state lock, so watchers get the transitions in
+        // order.
+        self.state_watchers
+            .lock()
+            .unwrap()
+            .retain(|watcher| watcher.send(transition).is_ok());
+        self.events.emit(|| SessionEvent::StateChanged(transition));
+        true
+    }
+
     /// Connects a client to an OpenTok session.
     ///
     /// * token - The client token for connecting to the session. Check
     /// <https://tokbox.com/developer/guides/create-token/>
     pub fn connect(&self, token: &str) -> OtcResult {
-        let connection_state = self.connection_state.lock().unwrap().clone();
-        if connection_state == ConnectionState::Connected
-            || connection_state == ConnectionState::Connecting
-        {
+        if matches!(
+            self.state(),
+            ConnectionState::Connected
+                | ConnectionState::Connecting
+                | ConnectionState::Reconnecting
+        ) {
             return Ok(());
         }
         if self.ptr.load(Ordering::Relaxed).is_null() {
             return Err(OtcError::NullError);
         }
-        let token = std::ffi::CString::new(token).map_err(|_| OtcError::InvalidParam("token"))?;
-        *self.connection_state.lock().unwrap() = ConnectionState::Connecting;
+        let ffi_token =
+            std::ffi::CString::new(token).map_err(|_| OtcError::InvalidParam("token"))?;
+        let cause = {
+            let mut reconnection = self.reconnection.lock().unwrap();
+            reconnection.token = token.to_owned();
+            match reconnection.attempt {
+                0 => StateCause::ConnectRequested,
+                attempt => StateCause::ReconnectAttempt(attempt),
+            }
+        };
+        self.set_state(ConnectionState::Connecting, cause);
         unsafe {
-            ffi::otc_session_connect(self.ptr.load(Ordering::Relaxed) as *mut _, token.as_ptr())
+            ffi::otc_session_connect(
+                self.ptr.load(Ordering::Relaxed) as *mut _,
+                ffi_token.as_ptr(),
+            )
         }
         .into_result()
     }
@@ -495,20 +1057,62 @@ impl Session {
     /// Disconnects the client from this session. All of the client's subscribers
     /// and publishers will also be disconnected from the session.
     pub fn disconnect(&self) -> OtcResult {
-        let connection_state = self.connection_state.lock().unwrap().clone();
-        if connection_state == ConnectionState::Disconnected
-            || connection_state == ConnectionState::Disconnecting
-        {
+        self.reconnection.lock().unwrap().cancel();
+        if matches!(
+            self.state(),
+            ConnectionState::Disconnected | ConnectionState::Disconnecting
+        ) {
             return Ok(());
         }
         if self.ptr.load(Ordering::Relaxed).is_null() {
             return Err(OtcError::NullError);
         }
-        *self.connection_state.lock().unwrap() = ConnectionState::Disconnecting;
+        self.reconnection.lock().unwrap().disconnect_requested = true;
+        self.set_state(
+            ConnectionState::Disconnecting,
+            StateCause::DisconnectRequested,
+        );
         unsafe { ffi::otc_session_disconnect(self.ptr.load(Ordering::Relaxed) as *mut _) }
             .into_result()
     }
 
+    /// A handle to this session that does not keep it alive.
+    fn downgrade(&self) -> WeakSession {
+        WeakSession {
+            ptr: Arc::downgrade(&self.ptr),
+        }
+    }
+
+    /// Runs `task` with the session after `delay`, on the reconnection
+    /// timer thread rather than in an SDK callback, unless the session has
+    /// been dropped by then.
+    fn schedule<F: FnOnce(&Session) + Send + 'static>(
+        &self,
+        delay: Duration,
+        task: F,
+    ) -> io::Result<()> {
+        let session = self.downgrade();
+        reconnect::schedule(
+            delay,
+            Box::new(move || {
+                if let Some(session) = session.upgrade() {
+                    task(&session);
+                }
+            }),
+        )
+    }
+
+    /// Returns the live session wrapping the native session `ptr`, if any.
+    pub(crate) fn find(ptr: *const ffi::otc_session) -> Option<Session> {
+        let mut found = None;
+        LIVE.for_each(|session| {
+            if session.ptr.load(Ordering::Relaxed) as *const ffi::otc_session == ptr {
+                found = Some(session.clone());
+            }
+        });
+        found
+    }
+
     /// Starts a publisher streaming to the session.
     pub fn publish(&self, publisher: &Publisher) -> OtcResult {
         if self.ptr.load(Ordering::Relaxed).is_null() {
@@ -520,7 +1124,9 @@ impl Session {
                 publisher.inner() as *mut _,
             )
         }
-        .into_result()
+        .into_result()?;
+        self.reconnection.lock().unwrap().track_publisher(publisher);
+        Ok(())
     }
 
     /// Stops publishing from a session, causing the publisher stream to stop.
@@ -529,6 +1135,7 @@ impl Session {
             return Err(OtcError::NullError);
         }
 
+        self.forget_publisher(publisher);
         let publisher = publisher.inner();
         if publisher.is_null() {
             return Err(OtcError::NullError);
@@ -550,12 +1157,18 @@ impl Session {
             return Err(OtcError::NullError);
         }
 
-        let subscriber = subscriber.inner();
-        if subscriber.is_null() {
+        let subscriber_ptr = subscriber.inner();
+        if subscriber_ptr.is_null() {
             return Err(OtcError::NullError);
         }
 
-        unsafe { ffi::otc_session_subscribe(ptr as *mut _, subscriber as *mut _) }.into_result()
+        unsafe { ffi::otc_session_subscribe(ptr as *mut _, subscriber_ptr as *mut _) }
+            .into_result()?;
+        self.reconnection
+            .lock()
+            .unwrap()
+            .track_subscriber(subscriber);
+        Ok(())
     }
 
     /// Stops subscribing to a specific audio/video stream in this session.
@@ -565,6 +1178,7 @@ impl Session {
             return Err(OtcError::NullError);
         }
 
+        self.forget_subscriber(subscriber);
         let subscriber = subscriber.inner();
         if subscriber.is_null() {
             return Err(OtcError::NullError);
@@ -573,51 +1187,452 @@ impl Session {
         unsafe { ffi::otc_session_unsubscribe(ptr as *mut _, subscriber as *mut _) }.into_result()
     }
 
-    callback_call!(on_connection_created, *const ffi::otc_connection);
-    callback_call!(on_connection_dropped, *const ffi::otc_connection);
-    callback_call!(on_stream_received, *const ffi::otc_stream);
-    callback_call!(on_stream_dropped, *const ffi::otc_stream);
-    callback_call!(
-        on_stream_video_dimensions_changed,
-        *const ffi::otc_stream,
-        i32,
-        i32
-    );
-    callback_call!(
-        on_stream_video_type_changed,
-        *const ffi::otc_stream,
-        ffi::otc_stream_video_type
-    );
+    /// Sets how the session reconnects when its connection is lost, or
+    /// disables automatic reconnection with `None`, the default. See the
+    /// `reconnect` module.
+    ///
+    /// While there is a policy, the session keeps track of the publishers
+    /// and subscribers published and subscribed with it from then on, to
+    /// restore them once reconnected, until they are unpublished or
+    /// unsubscribed or the session is disconnected. It does not keep them
+    /// alive: dropping them still deletes them.
+    pub fn set_reconnect_policy(&self, policy: Option<ReconnectPolicy>) {
+        let mut reconnection = self.reconnection.lock().unwrap();
+        reconnection.policy = policy;
+        if policy.is_none() {
+            reconnection.cancel();
+        }
+    }
 
-    fn on_connected(&self) {
-        *self.connection_state.lock().unwrap() = ConnectionState::Connected;
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_connected(self);
+    /// The policy set with `set_reconnect_policy`, if any.
+    pub fn reconnect_policy(&self) -> Option<ReconnectPolicy> {
+        self.reconnection.lock().unwrap().policy
+    }
+
+    pub(crate) fn forget_publisher(&self, publisher: &Publisher) {
+        self.reconnection
+            .lock()
+            .unwrap()
+            .forget_publisher(publisher);
+    }
+
+    pub(crate) fn forget_subscriber(&self, subscriber: &Subscriber) {
+        self.reconnection
+            .lock()
+            .unwrap()
+            .forget_subscriber(subscriber);
+    }
+
+    fn notify_reconnect(&self, event: ReconnectEvent) {
+        self.events.emit(|| SessionEvent::Reconnect(event));
+        self.callbacks.on_reconnect(self, event);
+    }
+
+    /// Schedules `attempt` to reconnect, or gives up if the policy does not
+    /// allow it.
+    fn schedule_reconnect(&self, attempt: u32) {
+        let (policy, generation) = {
+            let mut reconnection = self.reconnection.lock().unwrap();
+            match reconnection.policy {
+                Some(policy) if policy.allows(attempt) => {
+                    reconnection.attempt = attempt;
+                    (policy, reconnection.generation)
+                }
+                Some(_) => {
+                    reconnection.attempt = 0;
+                    drop(reconnection);
+                    self.notify_reconnect(ReconnectEvent::GaveUp(attempt - 1));
+                    return;
+                }
+                None => return,
+            }
+        };
+        let delay = policy.delay(attempt);
+        self.notify_reconnect(ReconnectEvent::AttemptScheduled(attempt, delay));
+        let scheduled = self.schedule(delay, move |session| {
+            session.attempt_reconnect(attempt, generation)
+        });
+        if let Err(e) = scheduled {
+            warn!("Could not schedule reconnection. {}", e);
+            self.reconnection.lock().unwrap().attempt = 0;
+            self.notify_reconnect(ReconnectEvent::GaveUp(attempt - 1));
         }
     }
 
-    fn on_reconnection_started(&self) {
-        *self.connection_state.lock().unwrap() = ConnectionState::Connecting;
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_reconnection_started(self);
+    fn attempt_reconnect(&self, attempt: u32, generation: u64) {
+        let token = {
+            let mut reconnection = self.reconnection.lock().unwrap();
+            if reconnection.generation != generation {
+                return;
+            }
+            // The application connected again meanwhile.
+            if self.state() != ConnectionState::Disconnected {
+                reconnection.attempt = 0;
+                return;
+            }
+            reconnection.token.clone()
+        };
+        self.notify_reconnect(ReconnectEvent::AttemptStarted(attempt));
+        if let Err(e) = self.connect(&token) {
+            warn!("Could not reconnect. {}", e);
+            self.notify_reconnect(ReconnectEvent::AttemptFailed(attempt));
+            self.schedule_reconnect(attempt + 1);
         }
     }
 
-    fn on_reconnected(&self) {
-        *self.connection_state.lock().unwrap() = ConnectionState::Connected;
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_reconnected(self);
+    /// Publishes again the publishers kept for reconnection, if the session
+    /// just reconnected.
+    fn resume_publishers(&self) {
+        let (attempt, publishers) = {
+            let mut reconnection = self.reconnection.lock().unwrap();
+            if reconnection.attempt == 0 {
+                return;
+            }
+            (
+                std::mem::take(&mut reconnection.attempt),
+                reconnection.publishers.clone(),
+            )
+        };
+        self.notify_reconnect(ReconnectEvent::Reconnected(attempt));
+        for publisher in publishers.iter().filter_map(WeakPublisher::upgrade) {
+            // Already published again by the application.
+            if publisher.session().is_some() {
+                continue;
+            }
+            if let Err(e) = self.publish(&publisher) {
+                warn!("Could not publish again after reconnecting. {}", e);
+            }
         }
     }
 
-    fn on_disconnected(&self) {
-        *self.connection_state.lock().unwrap() = ConnectionState::Disconnected;
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_disconnected(self);
+    /// Resubscribes the subscribers kept for reconnection to `stream`, if
+    /// it is their stream, received again after reconnecting.
+    fn resume_subscribers(&self, stream: &Stream) {
+        let subscribers = self.reconnection.lock().unwrap().subscribers.clone();
+        for subscriber in subscribers.iter().filter_map(WeakSubscriber::upgrade) {
+            if subscriber.get_stream().map(|current| current.id()) != Some(stream.id()) {
+                continue;
+            }
+            let session = self.ptr.load(Ordering::Relaxed) as *mut _;
+            if let Err(e) = subscriber.retarget(stream.clone(), session) {
+                warn!("Could not resubscribe to stream {}. {}", stream.id(), e);
+            }
+        }
+    }
+
+    /// Updates the connection state after `error`, and reconnects if the
+    /// error is a lost connection or a failed attempt.
+    fn handle_error(&self, error: SessionError) {
+        let failed = self.set_state_if(
+            ConnectionState::Disconnected,
+            StateCause::Error(error),
+            |state| state == ConnectionState::Connecting && self.connection().is_none(),
+        );
+        let reconnection = self.reconnection.lock().unwrap();
+        if failed {
+            let attempt = reconnection.attempt;
+            drop(reconnection);
+            if attempt != 0 {
+                self.notify_reconnect(ReconnectEvent::AttemptFailed(attempt));
+                self.schedule_reconnect(attempt + 1);
+            }
+        } else if error == SessionError::ConnectionDropped
+            && reconnection.policy.is_some()
+            && matches!(
+                self.state(),
+                ConnectionState::Connected | ConnectionState::Reconnecting
+            )
+        {
+            drop(reconnection);
+            // The SDK may not report the disconnection, which starts the
+            // reconnection. It is not called back into from its callback.
+            if let Err(e) = self.schedule(Duration::from_secs(0), Session::drop_connection) {
+                warn!("Could not disconnect after losing the connection. {}", e);
+            }
+        }
+    }
+
+    /// Disconnects after the connection was lost, unless the session was
+    /// disconnected meanwhile. Unlike `disconnect`, this reconnects.
+    fn drop_connection(&self) {
+        if !matches!(
+            self.state(),
+            ConnectionState::Connected | ConnectionState::Reconnecting
+        ) {
+            return;
+        }
+        if let Err(e) =
+            unsafe { ffi::otc_session_disconnect(self.ptr.load(Ordering::Relaxed) as *mut _) }
+                .into_result()
+        {
+            warn!("Could not disconnect after losing the connection. {}", e);
+        }
+    }
+
+    /// Sends a signal to all clients connected to the session.
+    ///
+    /// * type_: The type of the signal. It can be empty.
+    /// * data: The data of the signal. It can be empty.
+    pub fn send_signal(&self, type_: &str, data: &str) -> Result<(), SessionError> {
+        self.send_signal_with_options(type_, data, SignalOptions::default())
+    }
+
+    /// Sends a signal to all clients connected to the session, with the
+    /// given options.
+    pub fn send_signal_with_options(
+        &self,
+        type_: &str,
+        data: &str,
+        options: SignalOptions,
+    ) -> Result<(), SessionError> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return Err(SessionError::NullOrInvalidParameter);
         }
+        let (type_, data) = signal_cstrings(type_, data)?;
+        into_session_result(unsafe {
+            ffi::otc_session_send_signal_with_options(
+                ptr as *mut _,
+                type_.as_ptr(),
+                data.as_ptr(),
+                options.into(),
+            )
+        })
+    }
+
+    /// Sends a signal to a specific client connected to the session.
+    ///
+    /// * connection: The connection of the client that should receive the signal.
+    /// * type_: The type of the signal. It can be empty.
+    /// * data: The data of the signal. It can be empty.
+    pub fn send_signal_to_connection(
+        &self,
+        connection: &Connection,
+        type_: &str,
+        data: &str,
+    ) -> Result<(), SessionError> {
+        self.send_signal_to_connection_with_options(
+            connection,
+            type_,
+            data,
+            SignalOptions::default(),
+        )
+    }
+
+    /// Sends a signal to a specific client connected to the session, with
+    /// the given options.
+    pub fn send_signal_to_connection_with_options(
+        &self,
+        connection: &Connection,
+        type_: &str,
+        data: &str,
+        options: SignalOptions,
+    ) -> Result<(), SessionError> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() || connection.inner().is_null() {
+            return Err(SessionError::NullOrInvalidParameter);
+        }
+        let (type_, data) = signal_cstrings(type_, data)?;
+        into_session_result(unsafe {
+            ffi::otc_session_send_signal_to_connection_with_options(
+                ptr as *mut _,
+                type_.as_ptr(),
+                data.as_ptr(),
+                connection.inner(),
+                options.into(),
+            )
+        })
+    }
+
+    /// Returns a stream of all the events related to this session that
+    /// happen from now on. Each event is sent right before the matching
+    /// callback in `SessionCallbacks`, if any, is called.
+    #[cfg(feature = "futures")]
+    pub fn events(&self) -> impl futures::Stream<Item = SessionEvent> + Unpin {
+        self.events.subscribe()
+    }
+
+    /// Connects a client to an OpenTok session and waits until the connection
+    /// is established.
+    ///
+    /// * token - The client token for connecting to the session.
+    #[cfg(feature = "futures")]
+    pub async fn connect_async(&self, token: &str) -> Result<(), AsyncError> {
+        use futures::StreamExt;
+
+        if self.state() == ConnectionState::Connected {
+            return Ok(());
+        }
+        let mut events = self.events.subscribe();
+        self.connect(token)?;
+        while let Some(event) = events.next().await {
+            match event {
+                SessionEvent::Connected => return Ok(()),
+                SessionEvent::Error(_, error) => return Err(error.into()),
+                SessionEvent::Disconnected => break,
+                _ => {}
+            }
+        }
+        Err(AsyncError::Disconnected)
+    }
+
+    /// Starts a publisher streaming to the session and waits until its stream
+    /// is created.
+    #[cfg(feature = "futures")]
+    pub async fn publish_async(&self, publisher: &Publisher) -> Result<Stream, AsyncError> {
+        use futures::StreamExt;
+
+        let mut events = publisher.events();
+        self.publish(publisher)?;
+        while let Some(event) = events.next().await {
+            match event {
+                PublisherEvent::StreamCreated(stream) => return Ok(stream),
+                PublisherEvent::Error(_, error) => return Err(AsyncError::Publisher(error)),
+                PublisherEvent::StreamDestroyed(_) => break,
+                _ => {}
+            }
+        }
+        Err(AsyncError::Disconnected)
+    }
+
+    /// Starts subscribing to an audio/video stream in this session and waits
+    /// until the subscriber is connected to it.
+    #[cfg(feature = "futures")]
+    pub async fn subscribe_async(&self, subscriber: &Subscriber) -> Result<Stream, AsyncError> {
+        use futures::StreamExt;
+
+        let mut events = subscriber.events();
+        self.subscribe(subscriber)?;
+        while let Some(event) = events.next().await {
+            match event {
+                SubscriberEvent::Connected(stream) => return Ok(stream),
+                SubscriberEvent::Error(_, error) => return Err(AsyncError::Subscriber(error)),
+                SubscriberEvent::Disconnected => break,
+                _ => {}
+            }
+        }
+        Err(AsyncError::Disconnected)
+    }
+
+    fn on_connection_created(&self, connection: *const ffi::otc_connection) {
+        let connection: Connection = connection.into();
+        self.report_roster(self.roster.connection_created(&connection));
+        self.events
+            .emit(|| SessionEvent::ConnectionCreated(connection.clone()));
+        self.callbacks.on_connection_created(self, connection);
+    }
+
+    fn on_connection_dropped(&self, connection: *const ffi::otc_connection) {
+        let connection: Connection = connection.into();
+        self.report_roster(self.roster.connection_dropped(&connection));
+        self.events
+            .emit(|| SessionEvent::ConnectionDropped(connection.clone()));
+        self.callbacks.on_connection_dropped(self, connection);
+    }
+
+    fn on_stream_dropped(&self, stream: *const ffi::otc_stream) {
+        let stream: Stream = stream.into();
+        self.report_roster(self.roster.stream_dropped(&stream));
+        self.events
+            .emit(|| SessionEvent::StreamDropped(stream.clone()));
+        self.callbacks.on_stream_dropped(self, stream);
+    }
+
+    fn on_stream_video_dimensions_changed(
+        &self,
+        stream: *const ffi::otc_stream,
+        width: i32,
+        height: i32,
+    ) {
+        let stream: Stream = stream.into();
+        self.report_roster(self.roster.stream_changed(&stream, |info| {
+            info.width = width;
+            info.height = height;
+        }));
+        self.events
+            .emit(|| SessionEvent::StreamVideoDimensionsChanged(stream.clone(), width, height));
+        self.callbacks
+            .on_stream_video_dimensions_changed(self, stream, width, height);
+    }
+
+    fn on_stream_video_type_changed(
+        &self,
+        stream: *const ffi::otc_stream,
+        video_type: ffi::otc_stream_video_type,
+    ) {
+        let stream: Stream = stream.into();
+        let video_type: StreamVideoType = video_type.into();
+        self.report_roster(
+            self.roster
+                .stream_changed(&stream, |info| info.video_type = video_type),
+        );
+        self.events
+            .emit(|| SessionEvent::StreamVideoTypeChanged(stream.clone(), video_type));
+        self.callbacks
+            .on_stream_video_type_changed(self, stream, video_type);
+    }
+
+    fn on_stream_received(&self, stream: *const ffi::otc_stream) {
+        let stream: Stream = stream.into();
+        // Subscribing creates native subscribers, which is not done from
+        // the SDK's callback.
+        let received = stream.clone();
+        let scheduled = self.schedule(Duration::from_secs(0), move |session| {
+            subscriber::resubscribe(session.ptr.load(Ordering::Relaxed) as *mut _, &received);
+            session.resume_subscribers(&received);
+        });
+        if let Err(e) = scheduled {
+            warn!("Could not resubscribe to stream {}. {}", stream.id(), e);
+        }
+        self.report_roster(self.roster.stream_received(&stream));
+        self.events
+            .emit(|| SessionEvent::StreamReceived(stream.clone()));
+        self.callbacks.on_stream_received(self, stream);
+    }
+
+    fn on_connected(&self) {
+        self.set_state(ConnectionState::Connected, StateCause::Connected);
+        self.events.emit(|| SessionEvent::Connected);
+        self.callbacks.on_connected(self);
+        self.resume_publishers();
+    }
+
+    fn on_reconnection_started(&self) {
+        self.set_state(
+            ConnectionState::Reconnecting,
+            StateCause::ConnectionInterrupted,
+        );
+        self.events.emit(|| SessionEvent::ReconnectionStarted);
+        self.callbacks.on_reconnection_started(self);
+    }
+
+    fn on_reconnected(&self) {
+        self.set_state(ConnectionState::Connected, StateCause::Reconnected);
+        self.events.emit(|| SessionEvent::Reconnected);
+        self.callbacks.on_reconnected(self);
+    }
+
+    fn on_disconnected(&self) {
+        let requested = std::mem::take(&mut self.reconnection.lock().unwrap().disconnect_requested);
+        let cause = if requested {
+            StateCause::Disconnected
+        } else {
+            StateCause::ConnectionLost
+        };
+        self.set_state(ConnectionState::Disconnected, cause);
+        self.report_roster(self.roster.clear());
+        self.events.emit(|| SessionEvent::Disconnected);
+        self.callbacks.on_disconnected(self);
         if let Some(ref disconnect_watcher) = *self.disconnect_watcher.lock().unwrap() {
             let _ = disconnect_watcher.send(());
         }
+        let reconnect = {
+            let reconnection = self.reconnection.lock().unwrap();
+            !requested && reconnection.policy.is_some() && reconnection.attempt == 0
+        };
+        if reconnect {
+            self.schedule_reconnect(1);
+        }
     }
 
     fn on_stream_has_audio_changed(
@@ -628,14 +1643,19 @@ impl Session {
         if stream.is_null() {
             return;
         }
+        let has_audio = *OtcBool(has_audio);
+        self.report_roster(
+            self.roster
+                .stream_changed(&stream.into(), |info| info.has_audio = has_audio),
+        );
+        self.events
+            .emit(|| SessionEvent::StreamHasAudioChanged(stream.into(), has_audio));
         let stream = unsafe { ffi::otc_stream_copy(stream) };
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_stream_has_audio_changed(
-                self,
-                (stream as *const ffi::otc_stream).into(),
-                *OtcBool(has_audio),
-            )
-        }
+        self.callbacks.on_stream_has_audio_changed(
+            self,
+            (stream as *const ffi::otc_stream).into(),
+            has_audio,
+        );
     }
 
     fn on_stream_has_video_changed(
@@ -646,14 +1666,19 @@ impl Session {
         if stream.is_null() {
             return;
         }
+        let has_video = *OtcBool(has_video);
+        self.report_roster(
+            self.roster
+                .stream_changed(&stream.into(), |info| info.has_video = has_video),
+        );
+        self.events
+            .emit(|| SessionEvent::StreamHasVideoChanged(stream.into(), has_video));
         let stream = unsafe { ffi::otc_stream_copy(stream) };
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_stream_has_video_changed(
-                self,
-                (stream as *const ffi::otc_stream).into(),
-                *OtcBool(has_video),
-            )
-        }
+        self.callbacks.on_stream_has_video_changed(
+            self,
+            (stream as *const ffi::otc_stream).into(),
+            has_video,
+        );
     }
 
     fn on_signal_received(
@@ -667,14 +1692,19 @@ impl Session {
         }
         let type_ = unsafe { CStr::from_ptr(type_) };
         let signal = unsafe { CStr::from_ptr(signal) };
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_signal_received(
-                self,
-                type_.to_str().unwrap_or_default(),
-                signal.to_str().unwrap_or_default(),
-                (connection as *const ffi::otc_connection).into(),
-            );
-        }
+        self.events.emit(|| {
+            SessionEvent::SignalReceived(
+                type_.to_string_lossy().into_owned(),
+                signal.to_string_lossy().into_owned(),
+                connection.into(),
+            )
+        });
+        self.callbacks.on_signal_received(
+            self,
+            type_.to_str().unwrap_or_default(),
+            signal.to_str().unwrap_or_default(),
+            (connection as *const ffi::otc_connection).into(),
+        );
     }
 
     fn on_archive_started(&self, archive_id: *const c_char, name: *const c_char) {
@@ -683,13 +1713,17 @@ impl Session {
         }
         let archive_id = unsafe { CStr::from_ptr(archive_id) };
         let name = unsafe { CStr::from_ptr(name) };
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_archive_started(
-                self,
-                archive_id.to_str().unwrap_or_default(),
-                name.to_str().unwrap_or_default(),
-            );
-        }
+        self.events.emit(|| {
+            SessionEvent::ArchiveStarted(
+                archive_id.to_string_lossy().into_owned(),
+                name.to_string_lossy().into_owned(),
+            )
+        });
+        self.callbacks.on_archive_started(
+            self,
+            archive_id.to_str().unwrap_or_default(),
+            name.to_str().unwrap_or_default(),
+        );
     }
 
     fn on_archive_stopped(&self, archive_id: *const c_char) {
@@ -697,9 +1731,10 @@ impl Session {
             return;
         }
         let archive_id = unsafe { CStr::from_ptr(archive_id) };
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_archive_stopped(self, archive_id.to_str().unwrap_or_default());
-        }
+        self.events
+            .emit(|| SessionEvent::ArchiveStopped(archive_id.to_string_lossy().into_owned()));
+        self.callbacks
+            .on_archive_stopped(self, archive_id.to_str().unwrap_or_default());
     }
 
     fn on_error(&self, error_string: *const c_char, error: ffi::otc_session_error_code) {
@@ -707,13 +1742,34 @@ impl Session {
             return;
         }
         let error_string = unsafe { CStr::from_ptr(error_string) };
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_error(
-                self,
-                error_string.to_str().unwrap_or_default(),
-                error.into(),
-            );
-        }
+        self.events.emit(|| {
+            SessionEvent::Error(error_string.to_string_lossy().into_owned(), error.into())
+        });
+        self.callbacks.on_error(
+            self,
+            error_string.to_str().unwrap_or_default(),
+            error.into(),
+        );
+        self.handle_error(error.into());
+    }
+}
+
+/// A handle to a session that does not keep it alive, so that the tasks the
+/// session schedules do not delay its deletion.
+struct WeakSession {
+    ptr: Weak<AtomicPtr<*mut ffi::otc_session>>,
+}
+
+impl WeakSession {
+    /// A clone of the session, if it is still alive.
+    fn upgrade(&self) -> Option<Session> {
+        let mut found = None;
+        LIVE.for_each(|session| {
+            if std::ptr::eq(self.ptr.as_ptr(), Arc::as_ptr(&session.ptr)) {
+                found = Some(session.clone());
+            }
+        });
+        found
     }
 }
 
@@ -721,7 +1777,7 @@ impl Drop for Session {
     fn drop(&mut self) {
         let ptr = self.ptr.load(Ordering::Relaxed);
 
-        // 2 because we keep a reference in INSTANCES.
+        // 2 because the SDK holds a reference as user_data.
         if Arc::strong_count(&self.ptr) > 2 {
             return;
         }
@@ -730,8 +1786,10 @@ impl Drop for Session {
             return;
         }
 
-        let connection_state = self.connection_state.lock().unwrap().clone();
-        if connection_state == ConnectionState::Connected {
+        if matches!(
+            self.state(),
+            ConnectionState::Connected | ConnectionState::Reconnecting
+        ) {
             let (sender, receiver) = mpsc::channel();
             *self.disconnect_watcher.lock().unwrap() = Some(sender);
             if self.disconnect().is_ok() {
@@ -743,10 +1801,7 @@ impl Drop for Session {
             ffi::otc_session_delete(ptr as *mut _);
         }
 
-        if let Ok(ref mut instances) = INSTANCES.try_lock() {
-            instances.remove(&(ptr as usize));
-        }
-
         self.ptr.store(std::ptr::null_mut(), Ordering::Relaxed);
+        self.user_data.release(&LIVE);
     }
 }
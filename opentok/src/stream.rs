@@ -1,9 +1,12 @@
 use crate::connection::Connection;
+#[cfg(feature = "mock")]
+use crate::mock::ffi;
 
 use std::ffi::CStr;
 use std::sync::atomic::{AtomicPtr, Ordering};
 
 /// Different type of video streams supported.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum StreamVideoType {
     /// This is a video stream coming from a camera.
     Camera,
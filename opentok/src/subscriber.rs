@@ -1,20 +1,28 @@
 use crate::enums::{IntoResult, OtcBool, OtcError, OtcResult};
+#[cfg(feature = "futures")]
+use crate::events::AsyncError;
+use crate::events::EventSenders;
+#[cfg(feature = "mock")]
+use crate::mock::ffi;
+use crate::rtc_stats::RtcStatsReport;
+use crate::session::Session;
 use crate::stream::Stream;
+use crate::user_data::{Registry, UserData};
 use crate::video_frame::VideoFrame;
 
 use lazy_static::lazy_static;
-use once_cell::sync::OnceCell;
-use std::collections::HashMap;
+use log::warn;
 use std::ffi::CStr;
 use std::os::raw::{c_char, c_void};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
 
 lazy_static! {
-    pub static ref INSTANCES: Arc<Mutex<HashMap<usize, Subscriber>>> = Default::default();
+    pub(crate) static ref LIVE: Registry<Subscriber> = Default::default();
 }
 
 /// All possible Subscriber errors.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum SubscriberError {
     /// Internal error.
     Internal,
@@ -60,6 +68,7 @@ impl From<ffi::otc_subscriber_error_code> for SubscriberError {
 }
 
 /// Reasons for a video to be started, stopped, resumed, etc.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum VideoReason {
     Publish,
     Subscribe,
@@ -82,6 +91,63 @@ impl From<ffi::otc_video_reason> for VideoReason {
     }
 }
 
+/// Audio statistics of a subscriber, reported periodically by
+/// `SubscriberCallbacks::on_audio_stats`.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubscriberAudioStats {
+    /// The total number of audio packets lost by the subscriber.
+    pub packets_lost: u64,
+    /// The total number of audio packets received by the subscriber.
+    pub packets_received: u64,
+    /// The total number of audio bytes received by the subscriber.
+    pub bytes_received: u64,
+    /// The audio level value, from 0 to 1.0.
+    pub audio_level: f32,
+    /// The time when these stats were gathered, in milliseconds since the
+    /// Unix epoch.
+    pub timestamp: f64,
+}
+
+impl From<ffi::otc_subscriber_audio_stats> for SubscriberAudioStats {
+    fn from(stats: ffi::otc_subscriber_audio_stats) -> SubscriberAudioStats {
+        SubscriberAudioStats {
+            packets_lost: stats.packets_lost,
+            packets_received: stats.packets_received,
+            bytes_received: stats.bytes_received,
+            audio_level: stats.audio_level,
+            timestamp: stats.timestamp,
+        }
+    }
+}
+
+/// Video statistics of a subscriber, reported periodically by
+/// `SubscriberCallbacks::on_video_stats`.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubscriberVideoStats {
+    /// The total number of video packets lost by the subscriber.
+    pub packets_lost: u64,
+    /// The total number of video packets received by the subscriber.
+    pub packets_received: u64,
+    /// The total number of video bytes received by the subscriber.
+    pub bytes_received: u64,
+    /// The time when these stats were gathered, in milliseconds since the
+    /// Unix epoch.
+    pub timestamp: f64,
+}
+
+impl From<ffi::otc_subscriber_video_stats> for SubscriberVideoStats {
+    fn from(stats: ffi::otc_subscriber_video_stats) -> SubscriberVideoStats {
+        SubscriberVideoStats {
+            packets_lost: stats.packets_lost,
+            packets_received: stats.packets_received,
+            bytes_received: stats.bytes_received,
+            timestamp: stats.timestamp,
+        }
+    }
+}
+
 ffi_callback!(
     on_connected,
     *mut ffi::otc_subscriber,
@@ -139,6 +205,27 @@ ffi_callback!(
     f32
 );
 
+ffi_callback!(
+    on_audio_stats,
+    *mut ffi::otc_subscriber,
+    Subscriber,
+    ffi::otc_subscriber_audio_stats
+);
+
+ffi_callback!(
+    on_video_stats,
+    *mut ffi::otc_subscriber,
+    Subscriber,
+    ffi::otc_subscriber_video_stats
+);
+
+ffi_callback!(
+    on_rtc_stats_report,
+    *mut ffi::otc_subscriber,
+    Subscriber,
+    *const c_char
+);
+
 ffi_callback!(
     on_error,
     *mut ffi::otc_subscriber,
@@ -161,6 +248,9 @@ pub struct SubscriberCallbacks {
     on_video_disable_warning: Option<Box<dyn Fn(&Subscriber) + Send + Sync + 'static>>,
     on_video_disable_warning_lifted: Option<Box<dyn Fn(&Subscriber) + Send + Sync + 'static>>,
     on_audio_level_updated: Option<Box<dyn Fn(&Subscriber, f32) + Send + Sync + 'static>>,
+    on_audio_stats: Option<Box<dyn Fn(&Subscriber, SubscriberAudioStats) + Send + Sync + 'static>>,
+    on_video_stats: Option<Box<dyn Fn(&Subscriber, SubscriberVideoStats) + Send + Sync + 'static>>,
+    on_rtc_stats_report: Option<Box<dyn Fn(&Subscriber, RtcStatsReport) + Send + Sync + 'static>>,
     on_error: Option<Box<dyn Fn(&Subscriber, &str, SubscriberError) + Send + Sync + 'static>>,
 }
 
@@ -181,6 +271,9 @@ impl SubscriberCallbacks {
     callback!(on_video_disable_warning, &Subscriber);
     callback!(on_video_disable_warning_lifted, &Subscriber);
     callback!(on_audio_level_updated, &Subscriber, f32);
+    callback!(on_audio_stats, &Subscriber, SubscriberAudioStats);
+    callback!(on_video_stats, &Subscriber, SubscriberVideoStats);
+    callback!(on_rtc_stats_report, &Subscriber, RtcStatsReport);
     callback!(on_error, &Subscriber, &str, SubscriberError);
 }
 
@@ -199,6 +292,9 @@ pub struct SubscriberCallbacksBuilder {
     on_video_disable_warning: Option<Box<dyn Fn(&Subscriber) + Send + Sync + 'static>>,
     on_video_disable_warning_lifted: Option<Box<dyn Fn(&Subscriber) + Send + Sync + 'static>>,
     on_audio_level_updated: Option<Box<dyn Fn(&Subscriber, f32) + Send + Sync + 'static>>,
+    on_audio_stats: Option<Box<dyn Fn(&Subscriber, SubscriberAudioStats) + Send + Sync + 'static>>,
+    on_video_stats: Option<Box<dyn Fn(&Subscriber, SubscriberVideoStats) + Send + Sync + 'static>>,
+    on_rtc_stats_report: Option<Box<dyn Fn(&Subscriber, RtcStatsReport) + Send + Sync + 'static>>,
     on_error: Option<Box<dyn Fn(&Subscriber, &str, SubscriberError) + Send + Sync + 'static>>,
 }
 
@@ -215,6 +311,9 @@ impl SubscriberCallbacksBuilder {
     callback_setter!(on_video_disable_warning, &Subscriber);
     callback_setter!(on_video_disable_warning_lifted, &Subscriber);
     callback_setter!(on_audio_level_updated, &Subscriber, f32);
+    callback_setter!(on_audio_stats, &Subscriber, SubscriberAudioStats);
+    callback_setter!(on_video_stats, &Subscriber, SubscriberVideoStats);
+    callback_setter!(on_rtc_stats_report, &Subscriber, RtcStatsReport);
     callback_setter!(on_error, &Subscriber, &str, SubscriberError);
 
     pub fn build(self) -> SubscriberCallbacks {
@@ -231,17 +330,50 @@ impl SubscriberCallbacksBuilder {
             on_video_disable_warning: self.on_video_disable_warning,
             on_video_disable_warning_lifted: self.on_video_disable_warning_lifted,
             on_audio_level_updated: self.on_audio_level_updated,
+            on_audio_stats: self.on_audio_stats,
+            on_video_stats: self.on_video_stats,
+            on_rtc_stats_report: self.on_rtc_stats_report,
             on_error: self.on_error,
         }
     }
 }
 
+/// Events related to an OpenTok subscriber, one for each of the callbacks in
+/// `SubscriberCallbacks`. They are delivered by the stream returned by
+/// `Subscriber::events`, which requires the `futures` feature.
+#[derive(Clone)]
+pub enum SubscriberEvent {
+    Connected(Stream),
+    Disconnected,
+    Reconnected,
+    /// Not delivered to an event stream that has
+    /// `events::LOSSY_EVENT_QUEUE_LENGTH` frames pending already.
+    RenderFrame(VideoFrame),
+    VideoDisabled(VideoReason),
+    VideoEnabled(VideoReason),
+    AudioDisabled,
+    AudioEnabled,
+    VideoDataReceived,
+    VideoDisableWarning,
+    VideoDisableWarningLifted,
+    AudioLevelUpdated(f32),
+    AudioStats(SubscriberAudioStats),
+    VideoStats(SubscriberVideoStats),
+    RtcStatsReport(RtcStatsReport),
+    /// The error description and code.
+    Error(String, SubscriberError),
+}
+
 #[derive(Clone)]
 pub struct Subscriber {
     ptr: Arc<Mutex<Option<*const ffi::otc_subscriber>>>,
-    callbacks: Arc<Mutex<SubscriberCallbacks>>,
-    stream: OnceCell<Stream>,
+    callbacks: Arc<SubscriberCallbacks>,
+    stream: Arc<Mutex<Option<Stream>>>,
     subscribing: Arc<AtomicBool>,
+    audio_muted: Arc<AtomicBool>,
+    auto_resubscribe: Arc<AtomicBool>,
+    events: Arc<EventSenders<SubscriberEvent>>,
+    user_data: Arc<UserData<Subscriber>>,
 }
 
 unsafe impl Send for Subscriber {}
@@ -251,9 +383,13 @@ impl Subscriber {
     pub fn new(callbacks: SubscriberCallbacks) -> Self {
         Self {
             ptr: Default::default(),
-            callbacks: Arc::new(Mutex::new(callbacks)),
+            callbacks: Arc::new(callbacks),
             stream: Default::default(),
             subscribing: Default::default(),
+            audio_muted: Default::default(),
+            auto_resubscribe: Default::default(),
+            events: Default::default(),
+            user_data: Default::default(),
         }
     }
 
@@ -264,35 +400,70 @@ impl Subscriber {
         }
     }
 
-    callback_call!(on_render_frame, *const ffi::otc_video_frame);
-    callback_call!(on_video_disabled, ffi::otc_video_reason);
-    callback_call!(on_video_enabled, ffi::otc_video_reason);
-    callback_call!(on_audio_disabled);
-    callback_call!(on_audio_enabled);
-    callback_call!(on_video_data_received);
-    callback_call!(on_video_disable_warning);
-    callback_call!(on_video_disable_warning_lifted);
-    callback_call!(on_audio_level_updated, f32);
+    /// A handle to this subscriber that does not keep it alive.
+    pub(crate) fn downgrade(&self) -> WeakSubscriber {
+        WeakSubscriber {
+            ptr: Arc::downgrade(&self.ptr),
+        }
+    }
+
+    /// Returns a stream of all the events related to this subscriber that
+    /// happen from now on. Each event is sent right before the matching
+    /// callback in `SubscriberCallbacks`, if any, is called.
+    #[cfg(feature = "futures")]
+    pub fn events(&self) -> impl futures::Stream<Item = SubscriberEvent> + Unpin {
+        self.events.subscribe()
+    }
+
+    fn on_render_frame(&self, frame: *const ffi::otc_video_frame) {
+        self.events
+            .emit_lossy(|| SubscriberEvent::RenderFrame(frame.into()));
+        self.callbacks.on_render_frame(self, frame.into());
+    }
+    callback_call!(on_video_disabled, ffi::otc_video_reason => SubscriberEvent::VideoDisabled);
+    callback_call!(on_video_enabled, ffi::otc_video_reason => SubscriberEvent::VideoEnabled);
+    callback_call!(on_audio_disabled => SubscriberEvent::AudioDisabled);
+    callback_call!(on_audio_enabled => SubscriberEvent::AudioEnabled);
+    callback_call!(on_video_data_received => SubscriberEvent::VideoDataReceived);
+    callback_call!(on_video_disable_warning => SubscriberEvent::VideoDisableWarning);
+    callback_call!(on_video_disable_warning_lifted => SubscriberEvent::VideoDisableWarningLifted);
+    callback_call!(on_audio_level_updated, f32 => SubscriberEvent::AudioLevelUpdated);
+    callback_call!(on_audio_stats, ffi::otc_subscriber_audio_stats => SubscriberEvent::AudioStats);
+    callback_call!(on_video_stats, ffi::otc_subscriber_video_stats => SubscriberEvent::VideoStats);
+
+    fn on_rtc_stats_report(&self, json: *const c_char) {
+        let report = RtcStatsReport {
+            connection_id: String::new(),
+            json: if json.is_null() {
+                String::new()
+            } else {
+                unsafe { CStr::from_ptr(json) }
+                    .to_string_lossy()
+                    .into_owned()
+            },
+        };
+        self.events
+            .emit(|| SubscriberEvent::RtcStatsReport(report.clone()));
+        self.callbacks.on_rtc_stats_report(self, report);
+    }
 
     fn on_connected(&self, stream: *const ffi::otc_stream) {
         self.subscribing.store(true, Ordering::Relaxed);
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_connected(self, stream.into());
-        }
+        self.events
+            .emit(|| SubscriberEvent::Connected(stream.into()));
+        self.callbacks.on_connected(self, stream.into());
     }
 
     fn on_reconnected(&self) {
         self.subscribing.store(true, Ordering::Relaxed);
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_reconnected(self);
-        }
+        self.events.emit(|| SubscriberEvent::Reconnected);
+        self.callbacks.on_reconnected(self);
     }
 
     fn on_disconnected(&self) {
         self.subscribing.store(false, Ordering::Relaxed);
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_disconnected(self);
-        }
+        self.events.emit(|| SubscriberEvent::Disconnected);
+        self.callbacks.on_disconnected(self);
     }
 
     fn on_error(&self, error_string: *const c_char, error_code: ffi::otc_subscriber_error_code) {
@@ -300,20 +471,118 @@ impl Subscriber {
             return;
         }
         let error_string = unsafe { CStr::from_ptr(error_string) };
-        if let Ok(callbacks) = self.callbacks.try_lock() {
-            callbacks.on_error(
-                self,
-                error_string.to_str().unwrap_or_default(),
+        self.events.emit(|| {
+            SubscriberEvent::Error(
+                error_string.to_string_lossy().into_owned(),
                 error_code.into(),
-            );
-        }
+            )
+        });
+        self.callbacks.on_error(
+            self,
+            error_string.to_str().unwrap_or_default(),
+            error_code.into(),
+        );
     }
 
+    /// Sets the stream to subscribe to. The subscriber can then subscribe
+    /// to it with `Session::subscribe`. Use `switch_stream` to change the
+    /// stream afterwards.
     pub fn set_stream(&self, stream: Stream) -> OtcResult {
-        if self.stream.get().is_some() || self.stream.set(stream.clone()).is_err() {
+        if self.stream.lock().unwrap().is_some() {
             return Err(OtcError::AlreadyInitialized("stream"));
         }
+        self.attach(stream)
+    }
+
+    /// Subscribes to `stream` instead of the current stream, keeping the
+    /// same callbacks, event streams and audio mute setting. If the
+    /// subscriber was subscribed in a session, `stream` is subscribed to in
+    /// that same session, so it must belong to it.
+    ///
+    /// If the subscriber cannot be created for `stream`, it stays on its
+    /// current stream and the error is returned. If it is created but
+    /// subscribing in the session fails, it is left on `stream` and can be
+    /// subscribed again with `Session::subscribe`.
+    ///
+    /// This must not be called from the subscriber's own callbacks.
+    pub fn switch_stream(&self, stream: Stream) -> OtcResult {
+        let session = match *self.ptr.lock().unwrap() {
+            Some(ptr) => unsafe { ffi::otc_subscriber_get_session(ptr) },
+            None => std::ptr::null_mut(),
+        };
+        self.retarget(stream, session)
+    }
+
+    /// Whether the subscriber automatically subscribes to a new stream with
+    /// the same connection, name and video type as its current one, shortly
+    /// after the session receives it. This happens when a participant
+    /// publishes again after their stream was dropped. Streams without a
+    /// name are never matched, as nothing tells them apart from the other
+    /// streams of the participant. Disabled by default.
+    pub fn set_auto_resubscribe(&self, auto_resubscribe: bool) {
+        self.auto_resubscribe
+            .store(auto_resubscribe, Ordering::Relaxed);
+    }
+
+    /// Whether automatic resubscription is enabled with
+    /// `set_auto_resubscribe`.
+    pub fn auto_resubscribe(&self) -> bool {
+        self.auto_resubscribe.load(Ordering::Relaxed)
+    }
+
+    /// Whether `stream` is a new stream from the same connection and with
+    /// the same non-empty name and video type as the current stream.
+    fn is_republished(&self, stream: &Stream) -> bool {
+        match *self.stream.lock().unwrap() {
+            Some(ref current) => {
+                let name = current.name();
+                current.id() != stream.id()
+                    && !name.is_empty()
+                    && name == stream.name()
+                    && current.get_video_type() == stream.get_video_type()
+                    && current.get_connection().id() == stream.get_connection().id()
+            }
+            None => false,
+        }
+    }
+
+    /// Subscribes to `stream` instead of the current stream, in `session`
+    /// unless it is null. If no native subscriber can be created for
+    /// `stream`, the subscriber goes back to its previous stream and
+    /// session, so that it is left as it was.
+    pub(crate) fn retarget(&self, stream: Stream, session: *mut ffi::otc_session) -> OtcResult {
+        let previous = self.get_stream();
+        self.detach();
+        if let Err(e) = self.attach(stream) {
+            if let Some(previous) = previous {
+                if let Err(e) = self.attach_in(previous, session) {
+                    warn!("Could not go back to the previous stream. {}", e);
+                }
+            }
+            return Err(e);
+        }
+        self.subscribe_in(session)
+    }
+
+    fn attach_in(&self, stream: Stream, session: *mut ffi::otc_session) -> OtcResult {
+        self.attach(stream)?;
+        self.subscribe_in(session)
+    }
+
+    fn subscribe_in(&self, session: *mut ffi::otc_session) -> OtcResult {
+        if session.is_null() {
+            return Ok(());
+        }
+        unsafe { ffi::otc_session_subscribe(session, self.inner() as *mut _) }.into_result()
+    }
 
+    /// Creates the native subscriber for `stream`. The subscriber is locked
+    /// throughout, so that concurrent calls do not both create one.
+    fn attach(&self, stream: Stream) -> OtcResult {
+        let mut slot = self.ptr.lock().unwrap();
+        if slot.is_some() {
+            return Err(OtcError::AlreadyInitialized("subscriber"));
+        }
         let ffi_callbacks = ffi::otc_subscriber_callbacks {
             on_connected: Some(on_connected),
             on_disconnected: Some(on_disconnected),
@@ -328,20 +597,55 @@ impl Subscriber {
             on_video_disable_warning_lifted: Some(on_video_disable_warning_lifted),
             on_audio_level_updated: Some(on_audio_level_updated),
             on_error: Some(on_error),
-            on_audio_stats: None, // TODO
-            on_video_stats: None, // TODO
-            user_data: std::ptr::null_mut(),
+            on_audio_stats: Some(on_audio_stats),
+            on_video_stats: Some(on_video_stats),
+            user_data: self.user_data.attach(self, &LIVE),
             reserved: std::ptr::null_mut(),
         };
-        assert!(self.ptr.lock().unwrap().is_none());
         let ptr = unsafe { ffi::otc_subscriber_new(stream.inner(), &ffi_callbacks) };
-        INSTANCES.lock().unwrap().insert(ptr as usize, self.clone());
-        *self.ptr.lock().unwrap() = Some(ptr);
+        if ptr.is_null() {
+            self.user_data.release(&LIVE);
+            return Err(OtcError::NullError);
+        }
+        let rtc_stats_report_cb = ffi::otc_subscriber_rtc_stats_report_cb {
+            user_data: ffi_callbacks.user_data,
+            on_rtc_stats_report: Some(on_rtc_stats_report),
+            reserved: std::ptr::null_mut(),
+        };
+        unsafe { ffi::otc_subscriber_set_rtc_stats_report_cb(ptr, rtc_stats_report_cb) };
+        *self.stream.lock().unwrap() = Some(stream);
+        *slot = Some(ptr);
+        drop(slot);
+        if self.is_audio_muted() {
+            if let Err(e) = self.set_subscribe_to_audio(false) {
+                warn!("Could not mute subscriber audio. {}", e);
+            }
+        }
         Ok(())
     }
 
+    /// Unsubscribes and deletes the native subscriber, if any.
+    fn detach(&self) {
+        let _ = self.leave_session();
+        // Taken along with the native subscriber, so that a concurrent
+        // `attach` does not get its stream cleared or its user_data
+        // released.
+        let (ptr, user_data) = {
+            let mut slot = self.ptr.lock().unwrap();
+            *self.stream.lock().unwrap() = None;
+            (slot.take(), self.user_data.take())
+        };
+        if let Some(ptr) = ptr {
+            unsafe {
+                ffi::otc_subscriber_delete(ptr as *mut _);
+            }
+        }
+        UserData::release_taken(user_data, &LIVE);
+        self.subscribing.store(false, Ordering::Relaxed);
+    }
+
     pub fn get_stream(&self) -> Option<Stream> {
-        self.stream.get().cloned()
+        self.stream.lock().unwrap().clone()
     }
 
     pub fn set_subscribe_to_video(&self, subscribe_to_video: bool) -> OtcResult {
@@ -364,16 +668,34 @@ impl Subscriber {
             return Err(OtcError::NullError);
         }
 
-        let subscribe_to_video: OtcBool = subscribe_to_audio.into();
+        let subscribe_to_audio: OtcBool = subscribe_to_audio.into();
         unsafe {
-            ffi::otc_subscriber_set_subscribe_to_video(
+            ffi::otc_subscriber_set_subscribe_to_audio(
                 *self.ptr.lock().unwrap().as_ref().unwrap() as *mut ffi::otc_subscriber,
-                subscribe_to_video.into(),
+                subscribe_to_audio.into(),
             )
         }
         .into_result()
     }
 
+    /// Mutes or unmutes the audio of the participant this subscriber is
+    /// subscribed to. Unlike `set_subscribe_to_audio`, the setting belongs
+    /// to the subscriber rather than to the current subscription, so it can
+    /// be set before subscribing and is applied again whenever the
+    /// subscriber subscribes.
+    pub fn set_audio_muted(&self, muted: bool) -> OtcResult {
+        self.audio_muted.store(muted, Ordering::Relaxed);
+        if self.ptr.lock().unwrap().is_none() {
+            return Ok(());
+        }
+        self.set_subscribe_to_audio(!muted)
+    }
+
+    /// Whether the participant's audio is muted with `set_audio_muted`.
+    pub fn is_audio_muted(&self) -> bool {
+        self.audio_muted.load(Ordering::Relaxed)
+    }
+
     pub fn get_subscribe_to_video(&self) -> Result<bool, OtcError> {
         if self.ptr.lock().unwrap().is_none() {
             return Err(OtcError::NullError);
@@ -456,7 +778,50 @@ impl Subscriber {
         .map(|_| framerate)
     }
 
+    /// Requests the RTC stats report of the subscribed stream. The report is
+    /// delivered asynchronously to `SubscriberCallbacks::on_rtc_stats_report`.
+    pub fn get_rtc_stats_report(&self) -> OtcResult {
+        match *self.ptr.lock().unwrap() {
+            Some(ptr) => unsafe { ffi::otc_subscriber_get_rtc_stats_report(ptr) }.into_result(),
+            None => Err(OtcError::NullError),
+        }
+    }
+
+    /// Requests the RTC stats report of the subscribed stream and waits until
+    /// it is delivered.
+    #[cfg(feature = "futures")]
+    pub async fn get_rtc_stats_report_async(&self) -> Result<RtcStatsReport, AsyncError> {
+        use futures::StreamExt;
+
+        let mut events = self.events.subscribe();
+        self.get_rtc_stats_report()?;
+        while let Some(event) = events.next().await {
+            match event {
+                SubscriberEvent::RtcStatsReport(report) => return Ok(report),
+                SubscriberEvent::Error(_, error) => return Err(AsyncError::Subscriber(error)),
+                SubscriberEvent::Disconnected => break,
+                _ => {}
+            }
+        }
+        Err(AsyncError::Disconnected)
+    }
+
     pub fn unsubscribe(&self) -> OtcResult {
+        let ptr = *self.ptr.lock().unwrap();
+        if let Some(ptr) = ptr {
+            let session = unsafe { ffi::otc_subscriber_get_session(ptr) };
+            if !session.is_null() {
+                if let Some(session) = Session::find(session) {
+                    session.forget_subscriber(self);
+                }
+            }
+        }
+        self.leave_session()
+    }
+
+    /// Stops subscribing, but keeps the subscriber in the reconnection state
+    /// of its session.
+    fn leave_session(&self) -> OtcResult {
         if !self.subscribing.load(Ordering::Relaxed) {
             return Ok(());
         }
@@ -478,6 +843,63 @@ impl Subscriber {
     }
 }
 
+/// Moves the subscribers with automatic resubscription enabled over to
+/// `stream`, if it replaces their current stream. Called after `session`
+/// receives a stream, out of the SDK's callback.
+pub(crate) fn resubscribe(session: *mut ffi::otc_session, stream: &Stream) {
+    let mut subscribers = vec![];
+    LIVE.for_each(|subscriber| {
+        // Subscribers that nobody holds anymore are left alone.
+        if Arc::strong_count(&subscriber.ptr) > 1
+            && subscriber.auto_resubscribe()
+            && subscriber.is_republished(stream)
+        {
+            subscribers.push(subscriber.clone());
+        }
+    });
+    for subscriber in subscribers {
+        let current_session = unsafe { ffi::otc_subscriber_get_session(subscriber.inner()) };
+        if !current_session.is_null() && current_session != session {
+            continue;
+        }
+        if let Err(e) = subscriber.retarget(stream.clone(), session) {
+            warn!("Could not resubscribe to stream {}. {}", stream.id(), e);
+        }
+    }
+}
+
+/// A handle to a subscriber that does not keep it alive, so that dropping
+/// the application's last clone still deletes it. Unlike the native
+/// subscriber, it stays the same when the subscriber switches streams.
+#[derive(Clone)]
+pub(crate) struct WeakSubscriber {
+    ptr: Weak<Mutex<Option<*const ffi::otc_subscriber>>>,
+}
+
+impl WeakSubscriber {
+    /// A clone of the subscriber, if it is still alive and has a native
+    /// subscriber.
+    pub fn upgrade(&self) -> Option<Subscriber> {
+        let mut found = None;
+        LIVE.for_each(|subscriber| {
+            if self.is(subscriber) {
+                found = Some(subscriber.clone());
+            }
+        });
+        found
+    }
+
+    /// Whether this is a handle to `subscriber`.
+    pub fn is(&self, subscriber: &Subscriber) -> bool {
+        std::ptr::eq(self.ptr.as_ptr(), Arc::as_ptr(&subscriber.ptr))
+    }
+
+    /// Whether the subscriber has been dropped.
+    pub fn is_dropped(&self) -> bool {
+        self.ptr.strong_count() == 0
+    }
+}
+
 impl Drop for Subscriber {
     fn drop(&mut self) {
         match self.ptr.try_lock() {
@@ -489,6 +911,7 @@ impl Drop for Subscriber {
             Err(_) => return,
         }
 
+        // 2 because the SDK holds a reference as user_data.
         if Arc::strong_count(&self.ptr) != 2 {
             return;
         }
@@ -500,8 +923,6 @@ impl Drop for Subscriber {
             ffi::otc_subscriber_delete(ptr as *mut _);
         }
 
-        if let Ok(ref mut instances) = INSTANCES.try_lock() {
-            instances.remove(&(ptr as usize));
-        }
+        self.user_data.release(&LIVE);
     }
 }
@@ -0,0 +1,106 @@
+//! Callback dispatch through the `user_data` field of the ffi callback structs.
+//!
+//! Sessions, publishers, subscribers and video capturers hand a boxed clone
+//! of themselves to the SDK as `user_data`. Callbacks reach their target
+//! through that pointer, without any global lookup. The clone is released
+//! once the SDK object is deleted, or the capturer destroyed, which is when
+//! the SDK stops calling back.
+
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Mutex;
+
+/// Set of the clones currently handed to the SDK, so they can be torn down
+/// on `deinit` or inspected. It is never used when dispatching callbacks.
+pub(crate) struct Registry<T> {
+    live: Mutex<HashSet<usize>>,
+    _target: PhantomData<T>,
+}
+
+impl<T> Default for Registry<T> {
+    fn default() -> Self {
+        Self {
+            live: Default::default(),
+            _target: PhantomData,
+        }
+    }
+}
+
+impl<T> Registry<T> {
+    /// Calls `f` with every live target.
+    pub fn for_each<F: FnMut(&T)>(&self, mut f: F) {
+        let live = self.live.lock().unwrap();
+        for user_data in live.iter() {
+            f(unsafe { &*(*user_data as *const T) });
+        }
+    }
+}
+
+impl<T: Clone> Registry<T> {
+    /// Clones of every live target, to call into the SDK with once the
+    /// registry is unlocked again, as its callbacks may need it.
+    pub fn clones(&self) -> Vec<T> {
+        let mut clones = vec![];
+        self.for_each(|target| clones.push(target.clone()));
+        clones
+    }
+}
+
+/// The clone of a target handed to the SDK, if any.
+pub(crate) struct UserData<T> {
+    ptr: AtomicPtr<T>,
+}
+
+impl<T> Default for UserData<T> {
+    fn default() -> Self {
+        Self {
+            ptr: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+impl<T: Clone> UserData<T> {
+    /// Boxes a clone of `target` and returns it as a pointer suitable for the
+    /// `user_data` field of ffi callbacks.
+    pub fn attach(&self, target: &T, registry: &Registry<T>) -> *mut c_void {
+        let user_data = Box::into_raw(Box::new(target.clone()));
+        self.ptr.store(user_data, Ordering::Release);
+        registry.live.lock().unwrap().insert(user_data as usize);
+        user_data as *mut c_void
+    }
+
+    /// Drops the clone handed to the SDK. This must only be called once the
+    /// SDK object has been deleted or failed to be created.
+    pub fn release(&self, registry: &Registry<T>) {
+        Self::release_taken(self.take(), registry);
+    }
+
+    /// Takes the clone handed to the SDK, so that another one can be
+    /// attached before it is released with `release_taken`.
+    pub fn take(&self) -> *mut T {
+        self.ptr.swap(ptr::null_mut(), Ordering::AcqRel)
+    }
+
+    /// Drops a clone returned by `take`. This must only be called once its
+    /// SDK object has been deleted.
+    pub fn release_taken(user_data: *mut T, registry: &Registry<T>) {
+        if user_data.is_null() {
+            return;
+        }
+        registry.live.lock().unwrap().remove(&(user_data as usize));
+        drop(unsafe { Box::from_raw(user_data) });
+    }
+}
+
+/// Returns the target of a callback from its `user_data`.
+///
+/// # Safety
+///
+/// `user_data` must be null or a pointer returned by `UserData::attach` that
+/// has not been released yet.
+pub(crate) unsafe fn target<'a, T>(user_data: *mut c_void) -> Option<&'a T> {
+    (user_data as *const T).as_ref()
+}
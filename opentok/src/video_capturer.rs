@@ -1,15 +1,20 @@
 use crate::enums::{IntoResult, OtcBool, OtcError, OtcResult};
+#[cfg(feature = "mock")]
+use crate::mock::ffi;
+use crate::user_data::{Registry, UserData};
 use crate::video_frame::{FrameFormat, VideoFrame};
 
 use lazy_static::lazy_static;
-use std::collections::HashMap;
 use std::os::raw::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 lazy_static! {
-    static ref INSTANCES: Arc<Mutex<HashMap<usize, VideoCapturer>>> = Default::default();
+    pub(crate) static ref LIVE: Registry<VideoCapturer> = Default::default();
 }
 
+static NEXT_INSTANCE_ID: AtomicUsize = AtomicUsize::new(1);
+
 /// Settings for a VideoCapturer.
 #[derive(Clone, Copy)]
 pub struct VideoCapturerSettings {
@@ -28,6 +33,44 @@ pub struct VideoCapturerSettings {
     pub mirror_on_local_render: bool,
 }
 
+impl VideoCapturerSettings {
+    /// Checks that the settings describe a format the SDK can capture.
+    ///
+    /// The frame rate and the dimensions must be positive. Chroma
+    /// subsampled formats also need even dimensions: both of them for the
+    /// 4:2:0 formats (`Yuv420P`, `Nv12`, `Nv21`) and the width for the packed
+    /// 4:2:2 formats (`Yuy2`, `Uyvy`). The SDK documents no upper bound: it
+    /// scales down what the network or the codec cannot carry.
+    pub fn validate(&self) -> OtcResult {
+        let (even_width, even_height) = match self.format {
+            FrameFormat::Yuv420P | FrameFormat::Nv12 | FrameFormat::Nv21 => (true, true),
+            FrameFormat::Yuy2 | FrameFormat::Uyvy => (true, false),
+            FrameFormat::Abgr32
+            | FrameFormat::Argb32
+            | FrameFormat::Bgra32
+            | FrameFormat::Rgba32
+            | FrameFormat::Rgb24
+            | FrameFormat::Mjpeg => (false, false),
+            FrameFormat::Compressed | FrameFormat::Max | FrameFormat::__Unknown => {
+                return Err(OtcError::InvalidParam("format"))
+            }
+        };
+        if self.width < 1 || (even_width && self.width % 2 != 0) {
+            return Err(OtcError::InvalidParam("width"));
+        }
+        if self.height < 1 || (even_height && self.height % 2 != 0) {
+            return Err(OtcError::InvalidParam("height"));
+        }
+        if self.fps < 1 {
+            return Err(OtcError::InvalidParam("fps"));
+        }
+        if self.expected_delay < 0 {
+            return Err(OtcError::InvalidParam("expected_delay"));
+        }
+        Ok(())
+    }
+}
+
 impl Default for VideoCapturerSettings {
     fn default() -> Self {
         Self {
@@ -43,25 +86,54 @@ impl Default for VideoCapturerSettings {
 
 unsafe extern "C" fn init(
     capturer: *const ffi::otc_video_capturer,
-    data: *mut c_void,
+    user_data: *mut c_void,
 ) -> ffi::otc_bool {
-    let instance_id = data as usize;
-    let result: OtcBool = INSTANCES
-        .lock()
-        .unwrap()
-        .get(&instance_id)
-        .unwrap()
-        .init(capturer)
-        .into();
+    let result: OtcBool = match crate::user_data::target::<VideoCapturer>(user_data) {
+        Some(target) => target.init(capturer).into(),
+        None => {
+            crate::count_late_event();
+            false.into()
+        }
+    };
+    result.0
+}
+
+unsafe extern "C" fn destroy(
+    _: *const ffi::otc_video_capturer,
+    user_data: *mut c_void,
+) -> ffi::otc_bool {
+    // The SDK is done with this capturer, so the reference it holds can be
+    // released, once it is no longer borrowed.
+    let capturer = crate::user_data::target::<VideoCapturer>(user_data).cloned();
+    let result: OtcBool = match capturer {
+        Some(capturer) => {
+            capturer.release();
+            capturer.destroy().into()
+        }
+        None => {
+            crate::count_late_event();
+            false.into()
+        }
+    };
     result.0
 }
 
-ffi_callback_with_return_user_data!(destroy, *const ffi::otc_video_capturer, ffi::otc_bool);
-ffi_callback_with_return_user_data!(start, *const ffi::otc_video_capturer, ffi::otc_bool);
-ffi_callback_with_return_user_data!(stop, *const ffi::otc_video_capturer, ffi::otc_bool);
+ffi_callback_with_return_user_data!(
+    start,
+    *const ffi::otc_video_capturer,
+    VideoCapturer,
+    ffi::otc_bool
+);
+ffi_callback_with_return_user_data!(
+    stop,
+    *const ffi::otc_video_capturer,
+    VideoCapturer,
+    ffi::otc_bool
+);
 ffi_callback_with_return_user_data!(
     get_capture_settings,
     *const ffi::otc_video_capturer,
+    VideoCapturer,
     *mut ffi::otc_video_capturer_settings,
     ffi::otc_bool
 );
@@ -113,22 +185,26 @@ impl VideoCapturerCallbacksBuilder {
 pub struct VideoCapturer {
     instance_id: usize,
     ptr: Arc<Mutex<Option<*const ffi::otc_video_capturer>>>,
-    settings: VideoCapturerSettings,
-    callbacks: Arc<Mutex<VideoCapturerCallbacks>>,
+    settings: Arc<Mutex<VideoCapturerSettings>>,
+    callbacks: Arc<VideoCapturerCallbacks>,
     ffi_callbacks: Arc<Mutex<ffi::otc_video_capturer_callbacks>>,
+    user_data: Arc<UserData<VideoCapturer>>,
 }
 
 unsafe impl Send for VideoCapturer {}
 unsafe impl Sync for VideoCapturer {}
 
 impl VideoCapturer {
+    /// Creates a capturer with the given settings. They are not validated,
+    /// and the SDK fails to start capturing if they are invalid. See
+    /// `try_new`.
     pub fn new(settings: VideoCapturerSettings, callbacks: VideoCapturerCallbacks) -> Self {
-        let instance_id = INSTANCES.lock().unwrap().len() + 1;
-        let capturer = Self {
+        let instance_id = NEXT_INSTANCE_ID.fetch_add(1, Ordering::Relaxed);
+        Self {
             instance_id,
             ptr: Default::default(),
-            settings,
-            callbacks: Arc::new(Mutex::new(callbacks)),
+            settings: Arc::new(Mutex::new(settings)),
+            callbacks: Arc::new(callbacks),
             ffi_callbacks: Arc::new(Mutex::new(ffi::otc_video_capturer_callbacks {
                 init: None,
                 destroy: None,
@@ -138,29 +214,78 @@ impl VideoCapturer {
                 user_data: std::ptr::null_mut(),
                 reserved: std::ptr::null_mut(),
             })),
-        };
-        INSTANCES
-            .lock()
-            .unwrap()
-            .insert(instance_id, capturer.clone());
-        capturer
+            user_data: Default::default(),
+        }
     }
 
+    /// Creates a capturer with the given settings, which are validated with
+    /// `VideoCapturerSettings::validate`.
+    pub fn try_new(
+        settings: VideoCapturerSettings,
+        callbacks: VideoCapturerCallbacks,
+    ) -> Result<Self, OtcError> {
+        settings.validate()?;
+        Ok(Self::new(settings, callbacks))
+    }
+
+    /// Returns the identifier of this capturer. Identifiers are never
+    /// reused within a process.
+    pub fn id(&self) -> usize {
+        self.instance_id
+    }
+
+    /// Returns the ffi callbacks to hand to the SDK. From now on and until
+    /// the SDK destroys the capturer, the SDK holds a reference to it as
+    /// `user_data`, through which the callbacks reach it. The reference is
+    /// only dropped when the SDK destroys the capturer, so the callbacks
+    /// must be handed to the SDK. `Publisher` takes care of that.
     pub fn callbacks(&mut self) -> Arc<Mutex<ffi::otc_video_capturer_callbacks>> {
-        {
-            *self.ffi_callbacks.lock().unwrap() = ffi::otc_video_capturer_callbacks {
+        let mut ffi_callbacks = self.ffi_callbacks.lock().unwrap();
+        if ffi_callbacks.user_data.is_null() {
+            *ffi_callbacks = ffi::otc_video_capturer_callbacks {
                 init: Some(init),
                 destroy: Some(destroy),
                 start: Some(start),
                 stop: Some(stop),
-                get_capture_settings: None, //Some(get_capture_settings),
-                user_data: self.instance_id as *mut c_void,
+                get_capture_settings: Some(get_capture_settings),
+                user_data: self.user_data.attach(self, &LIVE),
                 reserved: std::ptr::null_mut(),
             };
         }
+        drop(ffi_callbacks);
         self.ffi_callbacks.clone()
     }
 
+    /// Drops the reference handed to the SDK with `callbacks`, once the SDK
+    /// destroyed the capturer or failed to use it.
+    pub(crate) fn release(&self) {
+        self.ffi_callbacks.lock().unwrap().user_data = std::ptr::null_mut();
+        self.user_data.release(&LIVE);
+    }
+
+    /// Returns the current capture settings.
+    pub fn settings(&self) -> VideoCapturerSettings {
+        *self.settings.lock().unwrap()
+    }
+
+    /// Changes the capture settings, before the capturer is in use.
+    ///
+    /// The SDK only asks for the settings once, after it initializes the
+    /// capturer, and has no call to renegotiate them. So from then on and
+    /// until the SDK destroys the capturer, this fails with
+    /// `OtcError::AlreadyInitialized` rather than accepting settings that
+    /// would never take effect. Frames are sent at the size they are
+    /// provided with, so a new size alone needs no renegotiation.
+    pub fn set_settings(&self, settings: VideoCapturerSettings) -> OtcResult {
+        settings.validate()?;
+        let ptr = self.ptr.lock().unwrap();
+        if ptr.is_some() {
+            return Err(OtcError::AlreadyInitialized("video capturer"));
+        }
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+
     pub fn provide_frame(&self, rotation: i32, frame: &VideoFrame) -> OtcResult {
         let ptr = self.ptr.lock().unwrap();
         if ptr.is_none() || ptr.as_ref().unwrap().is_null() {
@@ -172,12 +297,30 @@ impl VideoCapturer {
         .into_result()
     }
 
+    /// Provides a frame carrying `metadata`, which subscribers can read with
+    /// `VideoFrame::metadata`. The metadata is set on a copy of `frame`,
+    /// which shares its pixel data if the frame owns its buffer.
+    pub fn provide_frame_with_metadata(
+        &self,
+        rotation: i32,
+        frame: &VideoFrame,
+        metadata: &[u8],
+    ) -> OtcResult {
+        let mut frame = frame.clone();
+        frame.set_metadata(metadata)?;
+        self.provide_frame(rotation, &frame)
+    }
+
     fn init(&self, capturer: *const ffi::otc_video_capturer) -> OtcResult {
         *self.ptr.lock().unwrap() = Some(capturer);
-        self.callbacks.lock().unwrap().init(self)
+        self.callbacks.init(self)
+    }
+
+    fn destroy(&self) -> OtcResult {
+        *self.ptr.lock().unwrap() = None;
+        self.callbacks.destroy(self)
     }
 
-    callback_call_with_return!(destroy, OtcResult);
     callback_call_with_return!(start, OtcResult);
     callback_call_with_return!(stop, OtcResult);
 
@@ -185,13 +328,16 @@ impl VideoCapturer {
         if settings.is_null() {
             return Err(OtcError::NullError);
         }
+        let current = self.settings();
+        current.validate()?;
+        let format: ffi::otc_video_frame_format = current.format.into();
         unsafe {
-            (*settings).format = self.settings.format as i32;
-            (*settings).width = self.settings.width;
-            (*settings).height = self.settings.height;
-            (*settings).fps = self.settings.fps;
-            (*settings).expected_delay = self.settings.expected_delay;
-            (*settings).mirror_on_local_render = self.settings.mirror_on_local_render as i32;
+            (*settings).format = format as i32;
+            (*settings).width = current.width;
+            (*settings).height = current.height;
+            (*settings).fps = current.fps;
+            (*settings).expected_delay = current.expected_delay;
+            (*settings).mirror_on_local_render = current.mirror_on_local_render as i32;
         }
         Ok(())
     }
@@ -1,11 +1,20 @@
-use crate::{OtcError, OtcResult};
+pub mod ops;
+
+use crate::enums::OtcBool;
+use crate::{IntoResult, OtcError, OtcResult};
 
 use std::convert::TryInto;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::os::raw::c_void;
 use std::slice;
 use std::sync::atomic::{AtomicPtr, Ordering};
 
+/// Maximum size in bytes of the metadata of a frame.
+pub const METADATA_MAX_SIZE: usize = ffi::OTC_VIDEO_FRAME_METADATA_MAX_SIZE as usize;
+
 /// Video frame format enumeration.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum FrameFormat {
     Abgr32,
     Argb32,
@@ -69,7 +78,60 @@ impl From<FrameFormat> for ffi::otc_video_frame_format {
     }
 }
 
+impl FrameFormat {
+    /// Returns the size in bytes of a contiguous frame with this format and
+    /// the given dimensions, or `None` for formats without a fixed size.
+    pub fn buffer_size(self, width: i32, height: i32) -> Option<usize> {
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+        let layouts = self.plane_layouts(width as usize, height as usize)?;
+        Some(
+            layouts
+                .iter()
+                .map(|(_, width, height, sample_size)| width * height * sample_size)
+                .sum(),
+        )
+    }
+
+    /// Returns the layout of the planes of a frame with this format, or
+    /// `None` for formats without a fixed layout.
+    fn plane_layouts(self, width: usize, height: usize) -> Option<Vec<PlaneLayout>> {
+        let (chroma_width, chroma_height) = (half(width), half(height));
+        let layouts = match self {
+            FrameFormat::Yuv420P => vec![
+                (FramePlane::Y, width, height, 1),
+                (FramePlane::U, chroma_width, chroma_height, 1),
+                (FramePlane::V, chroma_width, chroma_height, 1),
+            ],
+            FrameFormat::Nv12 => vec![
+                (FramePlane::Y, width, height, 1),
+                (FramePlane::UvInterleaved, chroma_width, chroma_height, 2),
+            ],
+            FrameFormat::Nv21 => vec![
+                (FramePlane::Y, width, height, 1),
+                (FramePlane::VuInterleaved, chroma_width, chroma_height, 2),
+            ],
+            // Each pair of pixels is stored as four bytes.
+            FrameFormat::Yuy2 | FrameFormat::Uyvy => {
+                vec![(FramePlane::Packed, chroma_width, height, 4)]
+            }
+            FrameFormat::Abgr32
+            | FrameFormat::Argb32
+            | FrameFormat::Bgra32
+            | FrameFormat::Rgba32 => vec![(FramePlane::Packed, width, height, 4)],
+            FrameFormat::Rgb24 => vec![(FramePlane::Packed, width, height, 3)],
+            FrameFormat::Compressed
+            | FrameFormat::Max
+            | FrameFormat::Mjpeg
+            | FrameFormat::__Unknown => return None,
+        };
+        Some(layouts)
+    }
+}
+
 /// Video frame video plane enumeration.
+#[derive(Clone, Copy, Debug)]
 pub enum FramePlane {
     Packed,
     U,
@@ -116,8 +178,71 @@ impl From<FramePlane> for ffi::otc_video_frame_plane {
     }
 }
 
+/// A view of one plane of a video frame.
+#[derive(Clone, Copy, Debug)]
+pub struct Plane<'a> {
+    /// Which plane of the frame this is.
+    pub plane: FramePlane,
+    /// The plane data, starting at its first row.
+    pub data: &'a [u8],
+    /// The distance in bytes between the starts of two rows.
+    pub stride: usize,
+    /// The width of the plane, in samples.
+    pub width: usize,
+    /// The height of the plane, in rows.
+    pub height: usize,
+    /// The size of a sample in bytes, e.g. 2 for interleaved chroma planes
+    /// or 4 for 32 bit RGB formats.
+    pub sample_size: usize,
+}
+
+impl<'a> Plane<'a> {
+    /// Returns row `y` of the plane, without the stride padding.
+    pub fn row(&self, y: usize) -> &'a [u8] {
+        let start = y * self.stride;
+        &self.data[start..start + self.width * self.sample_size]
+    }
+}
+
+/// A mutable view of one plane of a video frame.
+#[derive(Debug)]
+pub struct PlaneMut<'a> {
+    /// Which plane of the frame this is.
+    pub plane: FramePlane,
+    /// The plane data, starting at its first row.
+    pub data: &'a mut [u8],
+    /// The distance in bytes between the starts of two rows.
+    pub stride: usize,
+    /// The width of the plane, in samples.
+    pub width: usize,
+    /// The height of the plane, in rows.
+    pub height: usize,
+    /// The size of a sample in bytes.
+    pub sample_size: usize,
+}
+
+impl<'a> PlaneMut<'a> {
+    /// Returns row `y` of the plane, without the stride padding.
+    pub fn row_mut(&mut self, y: usize) -> &mut [u8] {
+        let start = y * self.stride;
+        &mut self.data[start..start + self.width * self.sample_size]
+    }
+}
+
+/// Layout of a plane: which one it is, its width and height in samples and
+/// its sample size.
+type PlaneLayout = (FramePlane, usize, usize, usize);
+
+/// Returns half of `n`, rounded up, e.g. the width of a 4:2:0 chroma plane.
+fn half(n: usize) -> usize {
+    n / 2 + n % 2
+}
+
 pub struct VideoFrame {
     ptr: AtomicPtr<*const ffi::otc_video_frame>,
+    /// Whether this frame exclusively owns its pixel data, which can then
+    /// be modified through `planes_mut`.
+    writable: bool,
 }
 
 impl VideoFrame {
@@ -125,41 +250,201 @@ impl VideoFrame {
         self.ptr.load(Ordering::Relaxed) as *const _
     }
 
-    pub fn new(format: FrameFormat, width: i32, height: i32, buffer: Vec<u8>) -> Self {
+    fn null() -> Self {
         Self {
-            ptr: AtomicPtr::new(unsafe {
-                ffi::otc_video_frame_new(format.into(), width, height, buffer.as_ptr()) as *mut _
-            }),
+            ptr: AtomicPtr::new(std::ptr::null_mut()),
+            writable: false,
         }
     }
 
-    pub fn new_mjpeg(width: i32, height: i32, buffer: Vec<u8>, size: usize) -> Self {
-        Self {
-            ptr: AtomicPtr::new(unsafe {
-                ffi::otc_video_frame_new_MJPEG(
-                    width,
-                    height,
-                    buffer.as_ptr(),
-                    size.try_into().expect("usize to size_t cast"),
-                ) as *mut _
-            }),
-        }
+    /// Creates a frame from a contiguous buffer.
+    ///
+    /// If the buffer is too small for the given format and dimensions, the
+    /// returned frame has no native frame and all its methods fail with
+    /// `OtcError::NullError`.
+    #[deprecated(note = "use `VideoFrame::from_buffer`, which reports invalid input")]
+    pub fn new(format: FrameFormat, width: i32, height: i32, buffer: Vec<u8>) -> Self {
+        Self::from_buffer(format, width, height, buffer).unwrap_or_else(|_| Self::null())
     }
 
-    pub fn new_compressed(width: i32, height: i32, buffer: Vec<u8>, size: usize) -> Self {
-        Self {
-            ptr: AtomicPtr::new(unsafe {
-                ffi::otc_video_frame_new_compressed(
-                    width,
-                    height,
-                    buffer.as_ptr(),
-                    size.try_into().expect("usize to size_t cast"),
-                )
-            } as *mut _),
+    /// Creates an MJPEG frame from the first `size` bytes of `buffer`.
+    #[deprecated(note = "use `VideoFrame::from_buffer`, which reports invalid input")]
+    pub fn new_mjpeg(width: i32, height: i32, mut buffer: Vec<u8>, size: usize) -> Self {
+        buffer.truncate(size);
+        Self::from_buffer(FrameFormat::Mjpeg, width, height, buffer)
+            .unwrap_or_else(|_| Self::null())
+    }
+
+    /// Creates a compressed frame from the first `size` bytes of `buffer`.
+    #[deprecated(note = "use `VideoFrame::from_buffer`, which reports invalid input")]
+    pub fn new_compressed(width: i32, height: i32, mut buffer: Vec<u8>, size: usize) -> Self {
+        buffer.truncate(size);
+        Self::from_buffer(FrameFormat::Compressed, width, height, buffer)
+            .unwrap_or_else(|_| Self::null())
+    }
+
+    /// Creates a frame owning a contiguous buffer, without copying it.
+    ///
+    /// The buffer is dropped once the SDK is done with the frame, which may
+    /// be after the returned `VideoFrame` is dropped.
+    pub fn from_buffer<B>(
+        format: FrameFormat,
+        width: i32,
+        height: i32,
+        buffer: B,
+    ) -> Result<Self, OtcError>
+    where
+        B: AsRef<[u8]> + Send + 'static,
+    {
+        let size = check_buffer(format, width, height, buffer.as_ref())?;
+        let storage: Box<Storage> = Box::new(Box::new(buffer));
+        let data = (**storage).as_ref().as_ptr();
+        let storage = Box::into_raw(storage);
+        let ptr = unsafe {
+            ffi::otc_video_frame_new_contiguous_memory_wrapper(
+                format.into(),
+                width,
+                height,
+                OtcBool::from(true).0,
+                data,
+                size.try_into().expect("usize to size_t cast"),
+                Some(release_buffer),
+                storage as *mut c_void,
+            )
+        };
+        if ptr.is_null() {
+            drop(unsafe { Box::from_raw(storage) });
+            return Err(OtcError::NullError);
         }
+        Ok(Self {
+            ptr: AtomicPtr::new(ptr as *mut _),
+            writable: false,
+        })
+    }
+
+    /// Creates a frame from a `bytes::Bytes` buffer, without copying it.
+    #[cfg(feature = "bytes")]
+    pub fn from_bytes(
+        format: FrameFormat,
+        width: i32,
+        height: i32,
+        bytes: bytes::Bytes,
+    ) -> Result<Self, OtcError> {
+        Self::from_buffer(format, width, height, bytes)
+    }
+
+    /// Creates a frame borrowing a contiguous buffer.
+    pub fn wrap(
+        format: FrameFormat,
+        width: i32,
+        height: i32,
+        buffer: &[u8],
+    ) -> Result<VideoFrameRef<'_>, OtcError> {
+        let size = check_buffer(format, width, height, buffer)?;
+        let ptr = unsafe {
+            ffi::otc_video_frame_new_contiguous_memory_wrapper(
+                format.into(),
+                width,
+                height,
+                OtcBool::from(false).0,
+                buffer.as_ptr(),
+                size.try_into().expect("usize to size_t cast"),
+                None,
+                std::ptr::null_mut(),
+            )
+        };
+        VideoFrameRef::new(ptr)
+    }
+
+    /// Creates an I420 frame borrowing its Y, U and V planes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn wrap_i420<'a>(
+        width: i32,
+        height: i32,
+        y_plane: &'a [u8],
+        y_stride: i32,
+        u_plane: &'a [u8],
+        u_stride: i32,
+        v_plane: &'a [u8],
+        v_stride: i32,
+    ) -> Result<VideoFrameRef<'a>, OtcError> {
+        check_dimensions(width, height)?;
+        let (chroma_width, chroma_height) = ((width + 1) / 2, (height + 1) / 2);
+        check_plane("y_plane", y_plane, y_stride, width, height)?;
+        check_plane("u_plane", u_plane, u_stride, chroma_width, chroma_height)?;
+        check_plane("v_plane", v_plane, v_stride, chroma_width, chroma_height)?;
+
+        let mut planes = PlanarMemory::default();
+        planes.set(FramePlane::Y, y_plane.as_ptr(), y_stride);
+        planes.set(FramePlane::U, u_plane.as_ptr(), u_stride);
+        planes.set(FramePlane::V, v_plane.as_ptr(), v_stride);
+        planes.wrap(FrameFormat::Yuv420P, width, height)
     }
 
-    // FIXME: implement more constructors as needed.
+    /// Creates an NV12 frame borrowing its Y and interleaved UV planes.
+    pub fn wrap_nv12<'a>(
+        width: i32,
+        height: i32,
+        y_plane: &'a [u8],
+        y_stride: i32,
+        uv_plane: &'a [u8],
+        uv_stride: i32,
+    ) -> Result<VideoFrameRef<'a>, OtcError> {
+        Self::wrap_semi_planar(
+            FrameFormat::Nv12,
+            width,
+            height,
+            (y_plane, y_stride),
+            (uv_plane, uv_stride),
+        )
+    }
+
+    /// Creates an NV21 frame borrowing its Y and interleaved VU planes.
+    pub fn wrap_nv21<'a>(
+        width: i32,
+        height: i32,
+        y_plane: &'a [u8],
+        y_stride: i32,
+        vu_plane: &'a [u8],
+        vu_stride: i32,
+    ) -> Result<VideoFrameRef<'a>, OtcError> {
+        Self::wrap_semi_planar(
+            FrameFormat::Nv21,
+            width,
+            height,
+            (y_plane, y_stride),
+            (vu_plane, vu_stride),
+        )
+    }
+
+    fn wrap_semi_planar<'a>(
+        format: FrameFormat,
+        width: i32,
+        height: i32,
+        (y_plane, y_stride): (&'a [u8], i32),
+        (chroma_plane, chroma_stride): (&'a [u8], i32),
+    ) -> Result<VideoFrameRef<'a>, OtcError> {
+        check_dimensions(width, height)?;
+        let (chroma_width, chroma_height) = ((width + 1) / 2 * 2, (height + 1) / 2);
+        check_plane("y_plane", y_plane, y_stride, width, height)?;
+        check_plane(
+            "chroma_plane",
+            chroma_plane,
+            chroma_stride,
+            chroma_width,
+            chroma_height,
+        )?;
+
+        let chroma = if let FrameFormat::Nv21 = format {
+            FramePlane::VuInterleaved
+        } else {
+            FramePlane::UvInterleaved
+        };
+        let mut planes = PlanarMemory::default();
+        planes.set(FramePlane::Y, y_plane.as_ptr(), y_stride);
+        planes.set(chroma, chroma_plane.as_ptr(), chroma_stride);
+        planes.wrap(format, width, height)
+    }
 
     pub fn get_buffer(&self) -> Result<&[u8], OtcError> {
         let ptr = self.ptr.load(Ordering::Relaxed);
@@ -190,6 +475,42 @@ impl VideoFrame {
         Ok(())
     }
 
+    /// Attaches an opaque blob of at most `METADATA_MAX_SIZE` bytes to the
+    /// frame. It is sent along with the frame and can be read back by
+    /// subscribers with `metadata`.
+    pub fn set_metadata(&mut self, metadata: &[u8]) -> OtcResult {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
+        }
+        if metadata.len() > METADATA_MAX_SIZE {
+            return Err(OtcError::InvalidParam("metadata"));
+        }
+        unsafe {
+            ffi::otc_video_frame_set_metadata(
+                ptr as *mut _,
+                metadata.as_ptr(),
+                metadata.len().try_into().expect("usize to size_t cast"),
+            )
+        }
+        .into_result()
+    }
+
+    /// Returns the metadata attached to the frame, which is empty if there
+    /// is none.
+    pub fn metadata(&self) -> Result<&[u8], OtcError> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
+        }
+        let mut size = 0;
+        let data = unsafe { ffi::otc_video_frame_get_metadata(ptr as *const _, &mut size) };
+        if data.is_null() {
+            return Ok(&[]);
+        }
+        Ok(unsafe { slice::from_raw_parts(data, size.try_into().expect("size_t to usize cast")) })
+    }
+
     pub fn get_width(&self) -> Result<i32, OtcError> {
         let ptr = self.ptr.load(Ordering::Relaxed);
         if ptr.is_null() {
@@ -264,10 +585,132 @@ impl VideoFrame {
         }
         Ok(unsafe { ffi::otc_video_frame_get_plane_stride(ptr as *mut _, plane.into()) })
     }
+
+    /// Returns a copy of this frame owning its pixel data, which can be
+    /// modified through `planes_mut`.
+    pub fn mutable_copy(&self) -> Result<VideoFrame, OtcError> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
+        }
+        let ptr = unsafe { ffi::otc_video_frame_mutable_copy(ptr as *const _) };
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
+        }
+        Ok(Self {
+            ptr: AtomicPtr::new(ptr as *mut _),
+            writable: true,
+        })
+    }
+
+    /// Returns views of the planes of the frame.
+    ///
+    /// Planar formats have one plane per component, semi-planar formats
+    /// (`Nv12`, `Nv21`) a luma and an interleaved chroma plane and packed
+    /// formats a single `Packed` plane. `Mjpeg` and `Compressed` frames
+    /// have a single `Packed` plane of one row holding the whole buffer.
+    /// In `Yuy2` and `Uyvy` planes, a sample is a pair of pixels.
+    pub fn planes(&self) -> Result<Vec<Plane<'_>>, OtcError> {
+        Ok(self
+            .plane_pointers()?
+            .into_iter()
+            .map(
+                |(data, len, (plane, width, height, sample_size), stride)| Plane {
+                    plane,
+                    data: unsafe { slice::from_raw_parts(data, len) },
+                    stride,
+                    width,
+                    height,
+                    sample_size,
+                },
+            )
+            .collect())
+    }
+
+    /// Returns mutable views of the planes of the frame. Only frames
+    /// returned by `mutable_copy` can be modified, other frames fail with
+    /// `OtcError::InvalidParam`.
+    pub fn planes_mut(&mut self) -> Result<Vec<PlaneMut<'_>>, OtcError> {
+        if !self.writable {
+            return Err(OtcError::InvalidParam("frame"));
+        }
+        Ok(self
+            .plane_pointers()?
+            .into_iter()
+            .map(
+                |(data, len, (plane, width, height, sample_size), stride)| PlaneMut {
+                    plane,
+                    data: unsafe { slice::from_raw_parts_mut(data as *mut u8, len) },
+                    stride,
+                    width,
+                    height,
+                    sample_size,
+                },
+            )
+            .collect())
+    }
+
+    /// Returns the data pointer, data length, layout and stride of each
+    /// plane.
+    #[allow(clippy::type_complexity)]
+    fn plane_pointers(&self) -> Result<Vec<(*const u8, usize, PlaneLayout, usize)>, OtcError> {
+        let ptr = self.ptr.load(Ordering::Relaxed) as *const ffi::otc_video_frame;
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
+        }
+        let format = self.get_format()?;
+        let (width, height) = (self.get_width()?, self.get_height()?);
+        if width <= 0 || height <= 0 {
+            return Err(OtcError::NullError);
+        }
+
+        let layouts = match format.plane_layouts(width as usize, height as usize) {
+            Some(layouts) => layouts,
+            None => {
+                if let FrameFormat::Max | FrameFormat::__Unknown = format {
+                    return Err(OtcError::InvalidParam("format"));
+                }
+                let data = unsafe { ffi::otc_video_frame_get_buffer(ptr) };
+                let size = unsafe { ffi::otc_video_frame_get_buffer_size(ptr) } as usize;
+                if data.is_null() {
+                    return Err(OtcError::NullError);
+                }
+                return Ok(vec![(data, size, (FramePlane::Packed, size, 1, 1), size)]);
+            }
+        };
+
+        let mut planes = Vec::with_capacity(layouts.len());
+        for layout in layouts {
+            let (plane, width, height, sample_size) = layout;
+            let ffi_plane: ffi::otc_video_frame_plane = plane.into();
+            let data = unsafe { ffi::otc_video_frame_get_plane_binary_data(ptr, ffi_plane) };
+            if data.is_null() {
+                return Err(OtcError::NullError);
+            }
+            let row_size = width * sample_size;
+            let stride = unsafe { ffi::otc_video_frame_get_plane_stride(ptr, ffi_plane) };
+            let stride = if stride > 0 {
+                stride as usize
+            } else {
+                row_size
+            };
+            if stride < row_size {
+                return Err(OtcError::Fatal);
+            }
+            planes.push((data, stride * (height - 1) + row_size, layout, stride));
+        }
+        Ok(planes)
+    }
 }
 
 impl Clone for VideoFrame {
     fn clone(&self) -> Self {
+        // Copies of frames created as shallow copyable, like the ones of
+        // `from_buffer`, share their pixel data, which must not happen for
+        // writable frames.
+        if self.writable {
+            return self.mutable_copy().unwrap_or_else(|_| Self::null());
+        }
         (self.ptr.load(Ordering::Relaxed) as *const ffi::otc_video_frame).into()
     }
 }
@@ -294,6 +737,177 @@ impl From<*const ffi::otc_video_frame> for VideoFrame {
         let ptr = unsafe { ffi::otc_video_frame_copy(ptr) };
         Self {
             ptr: AtomicPtr::new(ptr as *mut _),
+            writable: false,
+        }
+    }
+}
+
+/// A video frame borrowing its pixel data.
+///
+/// The frame is created as not shallow copyable, so every copy of it, made
+/// by the SDK to keep it or by cloning the underlying `VideoFrame`, gets its
+/// own pixel data and may outlive the borrow.
+pub struct VideoFrameRef<'a> {
+    frame: VideoFrame,
+    _data: PhantomData<&'a [u8]>,
+}
+
+impl<'a> VideoFrameRef<'a> {
+    fn new(ptr: *mut ffi::otc_video_frame) -> Result<Self, OtcError> {
+        if ptr.is_null() {
+            return Err(OtcError::NullError);
         }
+        Ok(Self {
+            frame: VideoFrame {
+                ptr: AtomicPtr::new(ptr as *mut _),
+                writable: false,
+            },
+            _data: PhantomData,
+        })
     }
 }
+
+impl Deref for VideoFrameRef<'_> {
+    type Target = VideoFrame;
+
+    fn deref(&self) -> &VideoFrame {
+        &self.frame
+    }
+}
+
+/// Owned pixel storage, released by the SDK through `release_buffer`.
+type Storage = Box<dyn AsRef<[u8]> + Send>;
+
+unsafe extern "C" fn release_buffer(_: *const u8, arg: *mut c_void) {
+    drop(Box::from_raw(arg as *mut Storage));
+}
+
+fn check_dimensions(width: i32, height: i32) -> OtcResult {
+    if width <= 0 {
+        return Err(OtcError::InvalidParam("width"));
+    }
+    if height <= 0 {
+        return Err(OtcError::InvalidParam("height"));
+    }
+    Ok(())
+}
+
+/// Checks that `buffer` holds a whole frame and returns the size to hand
+/// to the SDK.
+fn check_buffer(
+    format: FrameFormat,
+    width: i32,
+    height: i32,
+    buffer: &[u8],
+) -> Result<usize, OtcError> {
+    check_dimensions(width, height)?;
+    match (format, format.buffer_size(width, height)) {
+        (FrameFormat::Max, _) | (FrameFormat::__Unknown, _) => {
+            Err(OtcError::InvalidParam("format"))
+        }
+        (_, Some(size)) if buffer.len() >= size => Ok(size),
+        (_, None) if !buffer.is_empty() => Ok(buffer.len()),
+        _ => Err(OtcError::InvalidParam("buffer")),
+    }
+}
+
+/// Checks that a plane holds `rows` rows of at least `row_size` bytes,
+/// `stride` bytes apart.
+fn check_plane(
+    name: &'static str,
+    plane: &[u8],
+    stride: i32,
+    row_size: i32,
+    rows: i32,
+) -> OtcResult {
+    if stride < row_size {
+        return Err(OtcError::InvalidParam(name));
+    }
+    let size = stride as usize * (rows as usize - 1) + row_size as usize;
+    if plane.len() < size {
+        return Err(OtcError::InvalidParam(name));
+    }
+    Ok(())
+}
+
+/// Planes handed to the SDK through a planar memory wrapper. It is boxed
+/// and handed to the SDK as `user_data`, which releases it along with the
+/// frame.
+struct PlanarMemory {
+    callbacks: ffi::otc_video_frame_planar_memory_callbacks,
+    /// Pointer and stride of each plane, indexed by `otc_video_frame_plane`.
+    planes: [(*const u8, i32); 6],
+}
+
+impl Default for PlanarMemory {
+    fn default() -> Self {
+        Self {
+            callbacks: ffi::otc_video_frame_planar_memory_callbacks {
+                get_plane: Some(get_plane),
+                get_plane_stride: Some(get_plane_stride),
+                release: Some(release_planes),
+                user_data: std::ptr::null_mut(),
+                reserved: std::ptr::null_mut(),
+            },
+            planes: [(std::ptr::null(), 0); 6],
+        }
+    }
+}
+
+impl PlanarMemory {
+    fn set(&mut self, plane: FramePlane, data: *const u8, stride: i32) {
+        let index: ffi::otc_video_frame_plane = plane.into();
+        self.planes[index as usize] = (data, stride);
+    }
+
+    fn wrap<'a>(
+        self,
+        format: FrameFormat,
+        width: i32,
+        height: i32,
+    ) -> Result<VideoFrameRef<'a>, OtcError> {
+        let memory = Box::into_raw(Box::new(self));
+        unsafe {
+            (*memory).callbacks.user_data = memory as *mut c_void;
+        }
+        let ptr = unsafe {
+            ffi::otc_video_frame_new_planar_memory_wrapper(
+                format.into(),
+                width,
+                height,
+                OtcBool::from(false).0,
+                &mut (*memory).callbacks,
+            )
+        };
+        if ptr.is_null() {
+            drop(unsafe { Box::from_raw(memory) });
+        }
+        VideoFrameRef::new(ptr)
+    }
+}
+
+unsafe extern "C" fn get_plane(
+    user_data: *mut c_void,
+    plane: ffi::otc_video_frame_plane,
+) -> *const u8 {
+    let memory = &*(user_data as *const PlanarMemory);
+    memory
+        .planes
+        .get(plane as usize)
+        .map_or(std::ptr::null(), |(data, _)| *data)
+}
+
+unsafe extern "C" fn get_plane_stride(
+    user_data: *mut c_void,
+    plane: ffi::otc_video_frame_plane,
+) -> i32 {
+    let memory = &*(user_data as *const PlanarMemory);
+    memory
+        .planes
+        .get(plane as usize)
+        .map_or(0, |(_, stride)| *stride)
+}
+
+unsafe extern "C" fn release_planes(user_data: *mut c_void) {
+    drop(Box::from_raw(user_data as *mut PlanarMemory));
+}
@@ -0,0 +1,672 @@
+//! Pure-Rust image operations: pixel format conversion, scaling, cropping,
+//! rotation and mirroring.
+//!
+//! Operations work on an `Image`, a copy of the pixels of a frame held in
+//! memory. Images can be created from and turned back into `VideoFrame`s.
+//!
+//! Internally, images are unpacked into full resolution planes, one per
+//! component, which all operations then process row by row, with plain
+//! loops over slices of the same length and without per-sample closures or
+//! branches, so that the compiler can vectorize them. YUV formats use BT.601
+//! limited range. RGB formats are named after their byte order in memory,
+//! e.g. `Argb32` stores the alpha byte first.
+
+use super::{half, FrameFormat, VideoFrame};
+use crate::OtcError;
+
+use std::convert::TryInto;
+
+/// Scaling filter.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Filter {
+    /// Interpolates between the four nearest samples.
+    Bilinear,
+    /// Averages all the samples covered by the destination sample. Best
+    /// suited for downscaling.
+    Area,
+}
+
+/// Clockwise rotation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rotation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Rotation {
+    /// Returns the rotation for a number of degrees, as passed to
+    /// `VideoCapturer::provide_frame`.
+    pub fn from_degrees(degrees: i32) -> Option<Self> {
+        match degrees.rem_euclid(360) {
+            90 => Some(Rotation::Rotate90),
+            180 => Some(Rotation::Rotate180),
+            270 => Some(Rotation::Rotate270),
+            _ => None,
+        }
+    }
+}
+
+/// An image in one of the uncompressed `FrameFormat`s, with its planes
+/// tightly packed one after the other in a single buffer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Image {
+    format: FrameFormat,
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl Image {
+    /// Creates an image from its pixel data, laid out as described by
+    /// `FrameFormat::buffer_size`. Extra bytes at the end are dropped.
+    pub fn new(
+        format: FrameFormat,
+        width: i32,
+        height: i32,
+        mut data: Vec<u8>,
+    ) -> Result<Self, OtcError> {
+        check_dimensions(width, height)?;
+        let size = format
+            .buffer_size(width, height)
+            .ok_or(OtcError::InvalidParam("format"))?;
+        if data.len() < size {
+            return Err(OtcError::InvalidParam("data"));
+        }
+        data.truncate(size);
+        Ok(Self {
+            format,
+            width: width as usize,
+            height: height as usize,
+            data,
+        })
+    }
+
+    /// Copies the pixels of a frame.
+    pub fn from_frame(frame: &VideoFrame) -> Result<Self, OtcError> {
+        let format = frame.get_format()?;
+        let (width, height) = (frame.get_width()?, frame.get_height()?);
+        check_dimensions(width, height)?;
+        let size = format
+            .buffer_size(width, height)
+            .ok_or(OtcError::InvalidParam("format"))?;
+        let mut data = Vec::with_capacity(size);
+        for plane in frame.planes()? {
+            for y in 0..plane.height {
+                data.extend_from_slice(plane.row(y));
+            }
+        }
+        Self::new(format, width, height, data)
+    }
+
+    /// Turns the image into a frame owning its pixels.
+    pub fn into_frame(self) -> Result<VideoFrame, OtcError> {
+        let (width, height) = (self.width(), self.height());
+        VideoFrame::from_buffer(self.format, width, height, self.data)
+    }
+
+    pub fn format(&self) -> FrameFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width.try_into().expect("usize to i32 cast")
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height.try_into().expect("usize to i32 cast")
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Converts the image to another uncompressed format.
+    pub fn convert(&self, format: FrameFormat) -> Result<Image, OtcError> {
+        if format.buffer_size(1, 1).is_none() {
+            return Err(OtcError::InvalidParam("format"));
+        }
+        Ok(self.unpack().pack(format))
+    }
+
+    /// Scales the image to the given dimensions.
+    pub fn scale(&self, width: i32, height: i32, filter: Filter) -> Result<Image, OtcError> {
+        check_dimensions(width, height)?;
+        let (width, height) = (width as usize, height as usize);
+        Ok(self
+            .unpack()
+            .map(width, height, |plane, src_width, src_height| match filter {
+                Filter::Bilinear => scale_bilinear(plane, src_width, src_height, width, height),
+                Filter::Area => scale_area(plane, src_width, src_height, width, height),
+            })
+            .pack(self.format))
+    }
+
+    /// Returns the `width` x `height` region starting at `x`, `y`.
+    pub fn crop(&self, x: i32, y: i32, width: i32, height: i32) -> Result<Image, OtcError> {
+        check_dimensions(width, height)?;
+        if x < 0 || x as usize + width as usize > self.width {
+            return Err(OtcError::InvalidParam("x"));
+        }
+        if y < 0 || y as usize + height as usize > self.height {
+            return Err(OtcError::InvalidParam("y"));
+        }
+        let (x, y, width, height) = (x as usize, y as usize, width as usize, height as usize);
+        Ok(self
+            .unpack()
+            .map(width, height, |plane, src_width, _| {
+                let mut cropped = Vec::with_capacity(width * height);
+                for row in plane.chunks_exact(src_width).skip(y).take(height) {
+                    cropped.extend_from_slice(&row[x..x + width]);
+                }
+                cropped
+            })
+            .pack(self.format))
+    }
+
+    /// Rotates the image clockwise.
+    pub fn rotate(&self, rotation: Rotation) -> Image {
+        let (width, height) = match rotation {
+            Rotation::Rotate180 => (self.width, self.height),
+            Rotation::Rotate90 | Rotation::Rotate270 => (self.height, self.width),
+        };
+        self.unpack()
+            .map(width, height, |plane, src_width, src_height| {
+                rotate_plane(plane, src_width, src_height, rotation)
+            })
+            .pack(self.format)
+    }
+
+    /// Mirrors the image horizontally.
+    pub fn mirror(&self) -> Image {
+        self.unpack()
+            .map(self.width, self.height, |plane, src_width, _| {
+                let mut mirrored = plane.to_vec();
+                for row in mirrored.chunks_exact_mut(src_width) {
+                    row.reverse();
+                }
+                mirrored
+            })
+            .pack(self.format)
+    }
+
+    /// Unpacks the image into one full resolution plane per component.
+    fn unpack(&self) -> Planes {
+        let (width, height) = (self.width, self.height);
+        let (chroma_width, chroma_height) = (half(width), half(height));
+        let luma_size = width * height;
+        let chroma_size = chroma_width * chroma_height;
+        let data = &self.data;
+
+        let yuv = |planes| Planes {
+            family: Family::Yuv,
+            width,
+            height,
+            planes,
+        };
+        match self.format {
+            FrameFormat::Yuv420P => {
+                let u = &data[luma_size..luma_size + chroma_size];
+                let v = &data[luma_size + chroma_size..luma_size + 2 * chroma_size];
+                yuv(vec![
+                    data[..luma_size].to_vec(),
+                    upsample(u, chroma_width, width, height),
+                    upsample(v, chroma_width, width, height),
+                ])
+            }
+            FrameFormat::Nv12 | FrameFormat::Nv21 => {
+                let chroma = &data[luma_size..luma_size + 2 * chroma_size];
+                let (mut u, mut v) = (vec![0; chroma_size], vec![0; chroma_size]);
+                let (first, second) = match self.format {
+                    FrameFormat::Nv12 => (&mut u, &mut v),
+                    _ => (&mut v, &mut u),
+                };
+                for ((pair, first), second) in chroma
+                    .chunks_exact(2)
+                    .zip(first.iter_mut())
+                    .zip(second.iter_mut())
+                {
+                    *first = pair[0];
+                    *second = pair[1];
+                }
+                yuv(vec![
+                    data[..luma_size].to_vec(),
+                    upsample(&u, chroma_width, width, height),
+                    upsample(&v, chroma_width, width, height),
+                ])
+            }
+            FrameFormat::Yuy2 | FrameFormat::Uyvy => {
+                let [y0, u, y1, v] = packed_422_layout(self.format);
+                let mut planes = vec![vec![0; luma_size]; 3];
+                let rows = data.chunks_exact(chroma_width * 4);
+                for (y, row) in rows.enumerate() {
+                    let range = y * width..(y + 1) * width;
+                    let (luma_row, u_row, v_row) = split_rows(&mut planes, range);
+                    for (((group, luma), u_pair), v_pair) in row
+                        .chunks_exact(4)
+                        .zip(luma_row.chunks_exact_mut(2))
+                        .zip(u_row.chunks_exact_mut(2))
+                        .zip(v_row.chunks_exact_mut(2))
+                    {
+                        luma[0] = group[y0];
+                        luma[1] = group[y1];
+                        u_pair[0] = group[u];
+                        u_pair[1] = group[u];
+                        v_pair[0] = group[v];
+                        v_pair[1] = group[v];
+                    }
+                    if width % 2 == 1 {
+                        let group = &row[row.len() - 4..];
+                        luma_row[width - 1] = group[y0];
+                        u_row[width - 1] = group[u];
+                        v_row[width - 1] = group[v];
+                    }
+                }
+                yuv(planes)
+            }
+            format => {
+                let (offsets, pixel_size) = rgb_layout(format);
+                let mut planes = vec![vec![255; luma_size]; 4];
+                for (plane, offset) in planes.iter_mut().zip(offsets.iter()) {
+                    if let Some(offset) = *offset {
+                        for (sample, pixel) in plane.iter_mut().zip(data.chunks_exact(pixel_size)) {
+                            *sample = pixel[offset];
+                        }
+                    }
+                }
+                Planes {
+                    family: Family::Rgb,
+                    width,
+                    height,
+                    planes,
+                }
+            }
+        }
+    }
+}
+
+fn check_dimensions(width: i32, height: i32) -> Result<(), OtcError> {
+    if width <= 0 {
+        return Err(OtcError::InvalidParam("width"));
+    }
+    if height <= 0 {
+        return Err(OtcError::InvalidParam("height"));
+    }
+    Ok(())
+}
+
+/// Returns the offsets of the R, G, B and A bytes in a pixel of an RGB
+/// format, and the size of a pixel.
+fn rgb_layout(format: FrameFormat) -> ([Option<usize>; 4], usize) {
+    match format {
+        FrameFormat::Rgba32 => ([Some(0), Some(1), Some(2), Some(3)], 4),
+        FrameFormat::Argb32 => ([Some(1), Some(2), Some(3), Some(0)], 4),
+        FrameFormat::Bgra32 => ([Some(2), Some(1), Some(0), Some(3)], 4),
+        FrameFormat::Abgr32 => ([Some(3), Some(2), Some(1), Some(0)], 4),
+        FrameFormat::Rgb24 => ([Some(0), Some(1), Some(2), None], 3),
+        _ => unreachable!("not an RGB format"),
+    }
+}
+
+/// Returns the offsets of Y0, U, Y1 and V in each group of four bytes of a
+/// packed 4:2:2 format.
+fn packed_422_layout(format: FrameFormat) -> [usize; 4] {
+    match format {
+        FrameFormat::Yuy2 => [0, 1, 2, 3],
+        FrameFormat::Uyvy => [1, 0, 3, 2],
+        _ => unreachable!("not a packed 4:2:2 format"),
+    }
+}
+
+/// Returns the given range of the first three planes.
+fn split_rows(
+    planes: &mut [Vec<u8>],
+    range: std::ops::Range<usize>,
+) -> (&mut [u8], &mut [u8], &mut [u8]) {
+    let (first, rest) = planes.split_at_mut(1);
+    let (second, third) = rest.split_at_mut(1);
+    (
+        &mut first[0][range.clone()],
+        &mut second[0][range.clone()],
+        &mut third[0][range],
+    )
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Family {
+    /// Y, U and V planes.
+    Yuv,
+    /// R, G, B and A planes.
+    Rgb,
+}
+
+/// An unpacked image: one full resolution plane per component.
+struct Planes {
+    family: Family,
+    width: usize,
+    height: usize,
+    planes: Vec<Vec<u8>>,
+}
+
+impl Planes {
+    /// Applies `f` to every plane, producing planes of the given size.
+    fn map<F>(self, width: usize, height: usize, f: F) -> Planes
+    where
+        F: Fn(&[u8], usize, usize) -> Vec<u8>,
+    {
+        let planes = self
+            .planes
+            .iter()
+            .map(|plane| f(plane, self.width, self.height))
+            .collect();
+        Planes {
+            family: self.family,
+            width,
+            height,
+            planes,
+        }
+    }
+
+    fn into_family(self, family: Family) -> Planes {
+        if self.family == family {
+            return self;
+        }
+        let size = self.width * self.height;
+        // All the slices are cut to `size` so that the loops need no bounds
+        // checks.
+        let (first, second, third) = (
+            &self.planes[0][..size],
+            &self.planes[1][..size],
+            &self.planes[2][..size],
+        );
+        let planes = match family {
+            Family::Yuv => {
+                let mut planes = vec![vec![0; size]; 3];
+                let (y, u, v) = split_rows(&mut planes, 0..size);
+                for i in 0..size {
+                    let (r, g, b) = (first[i] as i32, second[i] as i32, third[i] as i32);
+                    y[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+                    u[i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+                    v[i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+                }
+                planes
+            }
+            Family::Rgb => {
+                let mut planes = vec![vec![0; size]; 3];
+                let (r, g, b) = split_rows(&mut planes, 0..size);
+                for i in 0..size {
+                    let c = 298 * (first[i] as i32 - 16);
+                    let d = second[i] as i32 - 128;
+                    let e = third[i] as i32 - 128;
+                    r[i] = clamp((c + 409 * e + 128) >> 8);
+                    g[i] = clamp((c - 100 * d - 208 * e + 128) >> 8);
+                    b[i] = clamp((c + 516 * d + 128) >> 8);
+                }
+                planes.push(vec![255; size]);
+                planes
+            }
+        };
+        Planes {
+            family,
+            width: self.width,
+            height: self.height,
+            planes,
+        }
+    }
+
+    /// Packs the planes into an image of the given format.
+    fn pack(self, format: FrameFormat) -> Image {
+        let (width, height) = (self.width, self.height);
+        let size = format
+            .buffer_size(width as i32, height as i32)
+            .expect("uncompressed format");
+        let mut data = vec![0; size];
+        match format {
+            FrameFormat::Yuv420P | FrameFormat::Nv12 | FrameFormat::Nv21 => {
+                let planes = self.into_family(Family::Yuv).planes;
+                let (luma, chroma) = data.split_at_mut(width * height);
+                luma.copy_from_slice(&planes[0]);
+                let u = downsample(&planes[1], width, height);
+                let v = downsample(&planes[2], width, height);
+                if let FrameFormat::Yuv420P = format {
+                    let (u_plane, v_plane) = chroma.split_at_mut(u.len());
+                    u_plane.copy_from_slice(&u);
+                    v_plane.copy_from_slice(&v);
+                } else {
+                    let (first, second) = match format {
+                        FrameFormat::Nv12 => (&u, &v),
+                        _ => (&v, &u),
+                    };
+                    for ((pair, first), second) in chroma.chunks_exact_mut(2).zip(first).zip(second)
+                    {
+                        pair[0] = *first;
+                        pair[1] = *second;
+                    }
+                }
+            }
+            FrameFormat::Yuy2 | FrameFormat::Uyvy => {
+                let [y0, u, y1, v] = packed_422_layout(format);
+                let planes = self.into_family(Family::Yuv).planes;
+                let rows = data.chunks_exact_mut(half(width) * 4);
+                for (y, row) in rows.enumerate() {
+                    let range = y * width..(y + 1) * width;
+                    let luma_row = &planes[0][range.clone()];
+                    let u_row = &planes[1][range.clone()];
+                    let v_row = &planes[2][range];
+                    for (((group, luma), u_pair), v_pair) in row
+                        .chunks_exact_mut(4)
+                        .zip(luma_row.chunks_exact(2))
+                        .zip(u_row.chunks_exact(2))
+                        .zip(v_row.chunks_exact(2))
+                    {
+                        group[y0] = luma[0];
+                        group[y1] = luma[1];
+                        group[u] = average2(u_pair[0], u_pair[1]);
+                        group[v] = average2(v_pair[0], v_pair[1]);
+                    }
+                    // An odd last pixel is paired with itself.
+                    if width % 2 == 1 {
+                        let last = row.len() - 4;
+                        let group = &mut row[last..];
+                        group[y0] = luma_row[width - 1];
+                        group[y1] = luma_row[width - 1];
+                        group[u] = u_row[width - 1];
+                        group[v] = v_row[width - 1];
+                    }
+                }
+            }
+            format => {
+                let planes = self.into_family(Family::Rgb).planes;
+                let (offsets, pixel_size) = rgb_layout(format);
+                for (plane, offset) in planes.iter().zip(offsets.iter()) {
+                    if let Some(offset) = *offset {
+                        for (pixel, sample) in data.chunks_exact_mut(pixel_size).zip(plane) {
+                            pixel[offset] = *sample;
+                        }
+                    }
+                }
+            }
+        }
+        Image {
+            format,
+            width,
+            height,
+            data,
+        }
+    }
+}
+
+fn clamp(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+fn average2(a: u8, b: u8) -> u8 {
+    ((a as u16 + b as u16 + 1) >> 1) as u8
+}
+
+fn average4(a: u8, b: u8, c: u8, d: u8) -> u8 {
+    ((a as u16 + b as u16 + c as u16 + d as u16 + 2) >> 2) as u8
+}
+
+/// Upsamples a 4:2:0 chroma plane to full resolution.
+fn upsample(plane: &[u8], chroma_width: usize, width: usize, height: usize) -> Vec<u8> {
+    let mut upsampled = vec![0; width * height];
+    for (y, upsampled_row) in upsampled.chunks_exact_mut(width).enumerate() {
+        let row = &plane[y / 2 * chroma_width..(y / 2 + 1) * chroma_width];
+        let mut pairs = upsampled_row.chunks_exact_mut(2);
+        for (pair, sample) in (&mut pairs).zip(row) {
+            pair[0] = *sample;
+            pair[1] = *sample;
+        }
+        if let [last] = pairs.into_remainder() {
+            *last = row[chroma_width - 1];
+        }
+    }
+    upsampled
+}
+
+/// Downsamples a full resolution plane to 4:2:0 by averaging each 2x2 block.
+fn downsample(plane: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let (chroma_width, chroma_height) = (half(width), half(height));
+    let mut downsampled = vec![0; chroma_width * chroma_height];
+    for (y, downsampled_row) in downsampled.chunks_exact_mut(chroma_width).enumerate() {
+        let top = &plane[2 * y * width..(2 * y + 1) * width];
+        // An odd last row is averaged with itself.
+        let bottom_y = (2 * y + 1).min(height - 1);
+        let bottom = &plane[bottom_y * width..(bottom_y + 1) * width];
+        for ((sample, top), bottom) in downsampled_row
+            .iter_mut()
+            .zip(top.chunks_exact(2))
+            .zip(bottom.chunks_exact(2))
+        {
+            *sample = average4(top[0], top[1], bottom[0], bottom[1]);
+        }
+        // So is an odd last column.
+        if width % 2 == 1 {
+            let (top, bottom) = (top[width - 1], bottom[width - 1]);
+            downsampled_row[chroma_width - 1] = average4(top, top, bottom, bottom);
+        }
+    }
+    downsampled
+}
+
+fn rotate_plane(plane: &[u8], width: usize, height: usize, rotation: Rotation) -> Vec<u8> {
+    let mut rotated = plane.to_vec();
+    match rotation {
+        Rotation::Rotate180 => rotated.reverse(),
+        // The rotated image is `height` samples wide. Each row of the source
+        // becomes one of its columns, the last one for the first row at 90
+        // degrees and the first one at 270, written downwards at 90 degrees
+        // and upwards at 270.
+        Rotation::Rotate90 => {
+            for (y, row) in plane.chunks_exact(width).enumerate() {
+                let column = height - 1 - y;
+                for (x, sample) in row.iter().enumerate() {
+                    rotated[x * height + column] = *sample;
+                }
+            }
+        }
+        Rotation::Rotate270 => {
+            for (y, row) in plane.chunks_exact(width).enumerate() {
+                for (x, sample) in row.iter().enumerate() {
+                    rotated[(width - 1 - x) * height + y] = *sample;
+                }
+            }
+        }
+    }
+    rotated
+}
+
+/// Returns, for each destination sample, the two source samples to
+/// interpolate between and the weight of the second one, out of 256.
+/// Sample centres are aligned, as in most scalers.
+fn bilinear_taps(src: usize, dst: usize) -> Vec<(usize, usize, u32)> {
+    (0..dst)
+        .map(|d| {
+            let position = (((2 * d + 1) * src * 256) / (2 * dst)) as i64 - 128;
+            let position = position.max(0) as usize;
+            let first = (position >> 8).min(src - 1);
+            let second = (first + 1).min(src - 1);
+            let weight = if first == second {
+                0
+            } else {
+                position as u32 & 255
+            };
+            (first, second, weight)
+        })
+        .collect()
+}
+
+fn scale_bilinear(
+    plane: &[u8],
+    src_width: usize,
+    src_height: usize,
+    width: usize,
+    height: usize,
+) -> Vec<u8> {
+    let columns = bilinear_taps(src_width, width);
+    let rows = bilinear_taps(src_height, height);
+    let mut blended = vec![0u32; src_width];
+    let mut scaled = Vec::with_capacity(width * height);
+    for (top, bottom, weight_y) in rows {
+        let top = &plane[top * src_width..(top + 1) * src_width];
+        let bottom = &plane[bottom * src_width..(bottom + 1) * src_width];
+        // Interpolate between the two rows first, then between the two
+        // columns of each destination sample.
+        for ((blended, top), bottom) in blended.iter_mut().zip(top).zip(bottom) {
+            *blended = *top as u32 * (256 - weight_y) + *bottom as u32 * weight_y;
+        }
+        for (left, right, weight_x) in &columns {
+            let value = blended[*left] * (256 - weight_x) + blended[*right] * weight_x;
+            scaled.push(((value + (1 << 15)) >> 16) as u8);
+        }
+    }
+    scaled
+}
+
+/// Returns, for each destination sample, the range of source samples it
+/// covers. Each range holds at least one sample.
+fn area_spans(src: usize, dst: usize) -> Vec<(usize, usize)> {
+    (0..dst)
+        .map(|d| {
+            let start = d * src / dst;
+            // `(d + 1) * src / dst`, rounded up.
+            let end = ((d + 1) * src - 1) / dst + 1;
+            let end = end.max(start + 1);
+            (start, end)
+        })
+        .collect()
+}
+
+fn scale_area(
+    plane: &[u8],
+    src_width: usize,
+    src_height: usize,
+    width: usize,
+    height: usize,
+) -> Vec<u8> {
+    let columns = area_spans(src_width, width);
+    let rows = area_spans(src_height, height);
+    // Wide enough for any box: a u32 overflows past 2^24 samples.
+    let mut sums = vec![0u64; src_width];
+    let mut scaled = Vec::with_capacity(width * height);
+    for (top, bottom) in rows {
+        // Sum the covered rows first, then the covered columns.
+        sums.iter_mut().for_each(|sum| *sum = 0);
+        for row in plane[top * src_width..bottom * src_width].chunks_exact(src_width) {
+            for (sum, sample) in sums.iter_mut().zip(row) {
+                *sum += *sample as u64;
+            }
+        }
+        for (left, right) in &columns {
+            let count = ((bottom - top) * (right - left)) as u64;
+            let sum: u64 = sums[*left..*right].iter().sum();
+            scaled.push(((sum + count / 2) / count) as u8);
+        }
+    }
+    scaled
+}
@@ -0,0 +1,146 @@
+//! Trait-based custom video capturers.
+//!
+//! A `VideoSource` owns its state and only has to produce frames. Turning it
+//! into a `VideoCapturer` with `VideoCapturer::from_source` takes care of the
+//! SDK callbacks and of a thread that pulls frames from the source at the
+//! configured frame rate while the SDK is capturing.
+
+use crate::enums::{OtcError, OtcResult};
+use crate::video_capturer::{VideoCapturer, VideoCapturerCallbacks, VideoCapturerSettings};
+use crate::video_frame::VideoFrame;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A source of video frames for a publisher.
+///
+/// All methods but `settings` and `frame` have empty default
+/// implementations. They are called from SDK or crate threads, never
+/// concurrently.
+pub trait VideoSource: Send + 'static {
+    /// Returns the settings the capturer is created with.
+    fn settings(&self) -> VideoCapturerSettings;
+
+    /// Called when the SDK initializes the capturer.
+    fn init(&mut self) -> OtcResult {
+        Ok(())
+    }
+
+    /// Called when the SDK starts capturing, before the first frame is
+    /// requested.
+    fn start(&mut self) -> OtcResult {
+        Ok(())
+    }
+
+    /// Called when the SDK stops capturing. No frames are requested
+    /// afterwards, until the next `start`.
+    fn stop(&mut self) -> OtcResult {
+        Ok(())
+    }
+
+    /// Called when the SDK is done with the capturer.
+    fn destroy(&mut self) -> OtcResult {
+        Ok(())
+    }
+
+    /// Returns the next frame to publish, or `None` to skip this tick.
+    ///
+    /// This is called about `fps` times per second, as set in the current
+    /// capturer settings.
+    fn frame(&mut self) -> Option<VideoFrame>;
+}
+
+/// Handle on the frame pacing thread of a running capturer.
+#[derive(Default)]
+struct Pacer {
+    running: Option<Arc<AtomicBool>>,
+}
+
+impl Pacer {
+    fn start<S: VideoSource>(&mut self, capturer: &VideoCapturer, source: Arc<Mutex<S>>) {
+        self.stop();
+        let running = Arc::new(AtomicBool::new(true));
+        self.running = Some(running.clone());
+        let capturer = capturer.clone();
+        thread::spawn(move || {
+            let mut deadline = Instant::now();
+            loop {
+                // Checked under the source lock, which `stop` is called with,
+                // so that no frame is requested once the source is stopped.
+                let frame = {
+                    let mut source = source.lock().unwrap();
+                    if !running.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    source.frame()
+                };
+                if let Some(frame) = frame {
+                    let _ = capturer.provide_frame(0, &frame);
+                }
+
+                let fps = capturer.settings().fps.max(1) as u32;
+                deadline += Duration::from_secs(1) / fps;
+                let now = Instant::now();
+                if deadline > now {
+                    thread::sleep(deadline - now);
+                } else {
+                    // Running late, do not try to catch up.
+                    deadline = now;
+                }
+            }
+        });
+    }
+
+    /// Signals the thread to exit. This must be called with the source
+    /// locked: the thread then requests no more frames, and exits on its
+    /// next tick, without blocking the SDK thread stopping the capturer.
+    fn stop(&mut self) {
+        if let Some(running) = self.running.take() {
+            running.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+impl VideoCapturer {
+    /// Creates a capturer publishing the frames of `source`, with the
+    /// settings returned by `VideoSource::settings`.
+    pub fn from_source<S: VideoSource>(source: S) -> Result<VideoCapturer, OtcError> {
+        let settings = source.settings();
+        let source = Arc::new(Mutex::new(source));
+        let pacer = Arc::new(Mutex::new(Pacer::default()));
+
+        let callbacks = VideoCapturerCallbacks::builder()
+            .init({
+                let source = source.clone();
+                move |_| source.lock().unwrap().init()
+            })
+            .start({
+                let source = source.clone();
+                let pacer = pacer.clone();
+                move |capturer| {
+                    source.lock().unwrap().start()?;
+                    pacer.lock().unwrap().start(capturer, source.clone());
+                    Ok(())
+                }
+            })
+            .stop({
+                let source = source.clone();
+                let pacer = pacer.clone();
+                move |_| {
+                    let mut source = source.lock().unwrap();
+                    pacer.lock().unwrap().stop();
+                    source.stop()
+                }
+            })
+            .destroy(move |_| {
+                let mut source = source.lock().unwrap();
+                pacer.lock().unwrap().stop();
+                source.destroy()
+            })
+            .build();
+
+        VideoCapturer::try_new(settings, callbacks)
+    }
+}
@@ -0,0 +1,144 @@
+#!/usr/bin/env python3
+"""Generates the reference images of the `video_frame::ops` tests.
+
+They are computed independently of the crate, with floating point BT.601
+limited range formulas and straightforward filter definitions, so the tests
+compare the fixed point implementation against them with a small tolerance.
+
+The picture is 8x6. Its luma varies from pixel to pixel and its chroma from
+2x2 block to 2x2 block, so that every format, subsampled or not, holds it
+exactly. Its RGB values are within range, so no format clips it.
+
+Run it from this directory to regenerate the `.raw` files.
+"""
+
+import math
+
+WIDTH, HEIGHT = 8, 6
+
+
+def luma(x, y):
+    return 60 + 13 * x + 9 * y
+
+
+def chroma(x, y):
+    bx, by = x // 2, y // 2
+    return 116 + 6 * bx - 3 * by, 120 + 4 * by + 3 * bx
+
+
+def rgb(x, y):
+    c = 1.164 * (luma(x, y) - 16)
+    u, v = (value - 128 for value in chroma(x, y))
+    values = (c + 1.596 * v, c - 0.391 * u - 0.813 * v, c + 2.018 * u)
+    assert all(0 <= value <= 255 for value in values)
+    return [round(value) for value in values]
+
+
+def pixels():
+    return [(x, y) for y in range(HEIGHT) for x in range(WIDTH)]
+
+
+def yuv_formats():
+    y_plane = [luma(x, y) for x, y in pixels()]
+    blocks = [(x, y) for y in range(0, HEIGHT, 2) for x in range(0, WIDTH, 2)]
+    u_plane = [chroma(x, y)[0] for x, y in blocks]
+    v_plane = [chroma(x, y)[1] for x, y in blocks]
+    yuy2, uyvy = [], []
+    for x, y in pixels():
+        if x % 2 == 0:
+            u, v = chroma(x, y)
+            y0, y1 = luma(x, y), luma(x + 1, y)
+            yuy2 += [y0, u, y1, v]
+            uyvy += [u, y0, v, y1]
+    return {
+        "yuv420p": y_plane + u_plane + v_plane,
+        "nv12": y_plane + [s for uv in zip(u_plane, v_plane) for s in uv],
+        "nv21": y_plane + [s for vu in zip(v_plane, u_plane) for s in vu],
+        "yuy2": yuy2,
+        "uyvy": uyvy,
+    }
+
+
+def rgb_formats():
+    layouts = {
+        "rgba32": "rgba",
+        "argb32": "argb",
+        "bgra32": "bgra",
+        "abgr32": "abgr",
+        "rgb24": "rgb",
+    }
+    images = {}
+    for name, layout in layouts.items():
+        data = []
+        for x, y in pixels():
+            r, g, b = rgb(x, y)
+            components = {"r": r, "g": g, "b": b, "a": 255}
+            data += [components[c] for c in layout]
+        images[name] = data
+    return images
+
+
+def bilinear(plane, src_width, src_height, width, height):
+    """Interpolates between the four nearest samples, with sample centres
+    aligned and edges clamped."""
+
+    def taps(src, dst, d):
+        position = max((d + 0.5) * src / dst - 0.5, 0)
+        first = min(math.floor(position), src - 1)
+        second = min(first + 1, src - 1)
+        return first, second, position - first if first != second else 0
+
+    scaled = []
+    for dy in range(height):
+        top, bottom, wy = taps(src_height, height, dy)
+        for dx in range(width):
+            left, right, wx = taps(src_width, width, dx)
+            sample = lambda x, y: plane[y * src_width + x]
+            value = (sample(left, top) * (1 - wx) + sample(right, top) * wx) * (
+                1 - wy
+            ) + (sample(left, bottom) * (1 - wx) + sample(right, bottom) * wx) * wy
+            scaled.append(round(value))
+    return scaled
+
+
+def area(plane, src_width, src_height, width, height):
+    """Averages all the source samples the destination sample overlaps."""
+
+    def span(src, dst, d):
+        return math.floor(d * src / dst), max(
+            math.ceil((d + 1) * src / dst), math.floor(d * src / dst) + 1
+        )
+
+    scaled = []
+    for dy in range(height):
+        top, bottom = span(src_height, height, dy)
+        for dx in range(width):
+            left, right = span(src_width, width, dx)
+            samples = [
+                plane[y * src_width + x]
+                for y in range(top, bottom)
+                for x in range(left, right)
+            ]
+            scaled.append(round(sum(samples) / len(samples)))
+    return scaled
+
+
+def scaled_rgb24(rgb24, filter, width, height):
+    planes = [rgb24[c::3] for c in range(3)]
+    planes = [filter(plane, WIDTH, HEIGHT, width, height) for plane in planes]
+    return [s for pixel in zip(*planes) for s in pixel]
+
+
+def main():
+    images = {**yuv_formats(), **rgb_formats()}
+    for filter in (bilinear, area):
+        for width, height in ((4, 3), (5, 4), (12, 9)):
+            name = "rgb24_%s_%dx%d" % (filter.__name__, width, height)
+            images[name] = scaled_rgb24(images["rgb24"], filter, width, height)
+    for name, data in images.items():
+        with open(name + ".raw", "wb") as file:
+            file.write(bytes(data))
+
+
+if __name__ == "__main__":
+    main()
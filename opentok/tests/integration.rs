@@ -1,22 +1,40 @@
 #[cfg(test)]
 mod tests {
+    use opentok::connection::parse_connection_data;
+    use opentok::publisher::{Publisher, PublisherCallbacks, PublisherSettings, VideoType};
+    use opentok::session::{Session, SessionCallbacks, SessionError, SessionSettings};
+    use opentok::subscriber::{Subscriber, SubscriberCallbacks};
+    use opentok::video_capturer::{VideoCapturer, VideoCapturerCallbacks, VideoCapturerSettings};
+    use opentok::video_frame::ops::{Filter, Image, Rotation};
+    use opentok::video_frame::{FrameFormat, FramePlane, VideoFrame, METADATA_MAX_SIZE};
+    use opentok::video_source::VideoSource;
+    use opentok::OtcError;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+
+    // Used by the tests against a live OpenTok project, which the mock
+    // cannot stand in for.
+    #[cfg(not(feature = "mock"))]
     use futures::executor::LocalPool;
+    #[cfg(not(feature = "mock"))]
     use opentok::audio_device::{AudioDevice, AudioDeviceSettings};
+    #[cfg(not(feature = "mock"))]
     use opentok::log::{self, LogLevel};
-    use opentok::publisher::{Publisher, PublisherCallbacks};
-    use opentok::session::{Session, SessionCallbacks};
-    use opentok::subscriber::{Subscriber, SubscriberCallbacks};
-    use opentok::video_capturer::{VideoCapturer, VideoCapturerCallbacks, VideoCapturerSettings};
-    use opentok::video_frame::VideoFrame;
+    #[cfg(not(feature = "mock"))]
+    use opentok::session::{MAX_SIGNAL_DATA_LENGTH, MAX_SIGNAL_TYPE_LENGTH};
+    #[cfg(not(feature = "mock"))]
     use opentok_server::{OpenTok, SessionOptions, TokenRole};
+    #[cfg(not(feature = "mock"))]
     use opentok_utils::capturer;
+    #[cfg(not(feature = "mock"))]
     use opentok_utils::common::Credentials;
+    #[cfg(not(feature = "mock"))]
     use opentok_utils::publisher::Publisher as UtilsPublisher;
+    #[cfg(not(feature = "mock"))]
     use std::env;
-    use std::sync::atomic::{AtomicBool, Ordering};
-    use std::sync::mpsc;
-    use std::sync::{Arc, Mutex};
 
+    #[cfg(not(feature = "mock"))]
     fn setup_test() -> (String, String, String) {
         opentok::init().unwrap();
         let api_key = env::var("OPENTOK_KEY").unwrap();
@@ -31,10 +49,12 @@ mod tests {
         (api_key, session_id, token)
     }
 
+    #[cfg(not(feature = "mock"))]
     fn test_teardown() {
         opentok::deinit().unwrap();
     }
 
+    #[cfg(not(feature = "mock"))]
     #[test]
     fn test_logger_callback() {
         opentok::init().unwrap();
@@ -55,6 +75,7 @@ mod tests {
         opentok::deinit().unwrap();
     }
 
+    #[cfg(not(feature = "mock"))]
     #[test]
     fn test_session_connection() {
         let (api_key, session_id, token) = setup_test();
@@ -67,6 +88,9 @@ mod tests {
         let session_callbacks = SessionCallbacks::builder()
             .on_connected(move |session| {
                 assert_eq!(session.id(), session_id_);
+                let connection = session.connection().unwrap();
+                assert!(!connection.id().is_empty());
+                assert_eq!(connection.session_id(), session_id_);
                 on_connected_received.store(true, Ordering::Relaxed);
                 session.disconnect().unwrap();
             })
@@ -88,6 +112,53 @@ mod tests {
         test_teardown();
     }
 
+    #[test]
+    fn test_connection_data_parsing() {
+        let data = parse_connection_data("name=Jane%20Doe&role=host&flag").unwrap();
+        assert_eq!(data.len(), 3);
+        assert_eq!(data["name"], "Jane Doe");
+        assert_eq!(data["role"], "host");
+        assert_eq!(data["flag"], "");
+
+        assert!(parse_connection_data("").unwrap().is_empty());
+
+        if cfg!(feature = "serde_json") {
+            let data = parse_connection_data(r#"{"name": "Jane", "id": 42}"#).unwrap();
+            assert_eq!(data["name"], "Jane");
+            assert_eq!(data["id"], "42");
+        }
+        assert!(parse_connection_data("{not json").is_err());
+    }
+
+    #[cfg(all(feature = "futures", not(feature = "mock")))]
+    #[test]
+    fn test_session_connect_async() {
+        use futures::StreamExt;
+        use opentok::session::SessionEvent;
+
+        let (api_key, session_id, token) = setup_test();
+
+        let session_callbacks = SessionCallbacks::builder().build();
+        let session = Session::new(&api_key, &session_id, session_callbacks).unwrap();
+        let mut events = session.events();
+
+        let mut pool = LocalPool::new();
+        pool.run_until(session.connect_async(&token)).unwrap();
+        assert!(matches!(
+            pool.run_until(events.next()),
+            Some(SessionEvent::Connected)
+        ));
+
+        session.disconnect().unwrap();
+        assert!(matches!(
+            pool.run_until(events.next()),
+            Some(SessionEvent::Disconnected)
+        ));
+
+        test_teardown();
+    }
+
+    #[cfg(not(feature = "mock"))]
     #[test]
     fn test_session_connection_invalid_api_key() {
         let (_, session_id, token) = setup_test();
@@ -112,6 +183,7 @@ mod tests {
         test_teardown();
     }
 
+    #[cfg(not(feature = "mock"))]
     #[test]
     fn test_session_connection_invalid_token() {
         let (api_key, session_id, _) = setup_test();
@@ -136,6 +208,7 @@ mod tests {
         test_teardown();
     }
 
+    #[cfg(not(feature = "mock"))]
     #[test]
     fn test_session_connection_invalid_session_id() {
         let (api_key, _, token) = setup_test();
@@ -160,6 +233,748 @@ mod tests {
         test_teardown();
     }
 
+    #[cfg(not(feature = "mock"))]
+    #[test]
+    fn test_session_connection_with_settings() {
+        let (api_key, session_id, token) = setup_test();
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let session_callbacks = SessionCallbacks::builder()
+            .on_connected(move |_| {
+                sender.lock().unwrap().send(()).unwrap();
+            })
+            .on_error(|_, error, _| {
+                panic!("{:?}", error);
+            })
+            .build();
+
+        let settings = SessionSettings::builder()
+            .connection_events_suppressed(true)
+            .build();
+        let session =
+            Session::with_settings(&api_key, &session_id, session_callbacks, &settings).unwrap();
+
+        session.connect(&token).unwrap();
+
+        receiver.recv().unwrap();
+
+        test_teardown();
+    }
+
+    #[cfg(not(feature = "mock"))]
+    #[test]
+    fn test_session_signal() {
+        let (api_key, session_id, token) = setup_test();
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let session_callbacks = SessionCallbacks::builder()
+            .on_connected(|session| {
+                assert_eq!(
+                    session.send_signal(&"a".repeat(MAX_SIGNAL_TYPE_LENGTH + 1), "data"),
+                    Err(SessionError::SignalTypeTooLong)
+                );
+                assert_eq!(
+                    session.send_signal("type", &"a".repeat(MAX_SIGNAL_DATA_LENGTH + 1)),
+                    Err(SessionError::SignalDataTooLong)
+                );
+                session.send_signal("type", "data").unwrap();
+            })
+            .on_signal_received(move |_, type_, data, _| {
+                assert_eq!(type_, "type");
+                assert_eq!(data, "data");
+                sender.lock().unwrap().send(()).unwrap();
+            })
+            .on_error(|_, error, _| {
+                panic!("{:?}", error);
+            })
+            .build();
+
+        let session = Session::new(&api_key, &session_id, session_callbacks).unwrap();
+
+        session.connect(&token).unwrap();
+
+        receiver.recv().unwrap();
+
+        test_teardown();
+    }
+
+    #[test]
+    fn test_video_capturer_settings() {
+        let settings = VideoCapturerSettings {
+            format: FrameFormat::Nv12,
+            width: 1920,
+            height: 1080,
+            ..Default::default()
+        };
+        let capturer =
+            VideoCapturer::try_new(settings, VideoCapturerCallbacks::builder().build()).unwrap();
+        assert_eq!(capturer.settings().width, 1920);
+
+        let odd = VideoCapturerSettings {
+            width: 1919,
+            ..settings
+        };
+        assert!(odd.validate().is_err());
+        assert!(capturer.set_settings(odd).is_err());
+        assert_eq!(capturer.settings().width, 1920);
+        assert!(VideoCapturerSettings {
+            format: FrameFormat::Rgb24,
+            ..odd
+        }
+        .validate()
+        .is_ok());
+        assert!(VideoCapturerSettings { fps: 0, ..settings }
+            .validate()
+            .is_err());
+        assert!(VideoCapturerSettings {
+            width: 7680,
+            height: 4320,
+            fps: 120,
+            ..settings
+        }
+        .validate()
+        .is_ok());
+        // Only `try_new` validates the settings.
+        assert_eq!(
+            VideoCapturer::new(odd, VideoCapturerCallbacks::builder().build())
+                .settings()
+                .width,
+            1919
+        );
+        assert!(VideoCapturer::try_new(
+            VideoCapturerSettings {
+                format: FrameFormat::Compressed,
+                ..settings
+            },
+            VideoCapturerCallbacks::builder().build()
+        )
+        .is_err());
+
+        capturer
+            .set_settings(VideoCapturerSettings {
+                width: 1280,
+                height: 720,
+                ..settings
+            })
+            .unwrap();
+        assert_eq!(capturer.settings().height, 720);
+
+        // The settings are fixed from when the SDK initializes the capturer
+        // until it destroys it.
+        let mut capturer = capturer;
+        let callbacks = capturer.callbacks();
+        let (init, destroy, user_data) = {
+            let callbacks = callbacks.lock().unwrap();
+            (
+                callbacks.init.unwrap(),
+                callbacks.destroy.unwrap(),
+                callbacks.user_data,
+            )
+        };
+        assert_ne!(unsafe { init(std::ptr::null(), user_data) }, 0);
+        assert_eq!(
+            capturer.set_settings(settings),
+            Err(OtcError::AlreadyInitialized("video capturer"))
+        );
+        assert_eq!(capturer.settings().height, 720);
+        assert_ne!(unsafe { destroy(std::ptr::null(), user_data) }, 0);
+        capturer.set_settings(settings).unwrap();
+        assert_eq!(capturer.settings().height, 1080);
+    }
+
+    #[test]
+    fn test_video_source() {
+        struct Source;
+        impl VideoSource for Source {
+            fn settings(&self) -> VideoCapturerSettings {
+                VideoCapturerSettings {
+                    fps: 15,
+                    ..Default::default()
+                }
+            }
+
+            fn frame(&mut self) -> Option<VideoFrame> {
+                None
+            }
+        }
+
+        let capturer = VideoCapturer::from_source(Source).unwrap();
+        assert_eq!(capturer.settings().fps, 15);
+    }
+
+    #[test]
+    fn test_video_source_stop() {
+        use std::sync::atomic::AtomicUsize;
+        use std::time::Duration;
+
+        struct Source {
+            started: bool,
+            frames: Arc<AtomicUsize>,
+            late_frames: Arc<AtomicUsize>,
+        }
+        impl VideoSource for Source {
+            fn settings(&self) -> VideoCapturerSettings {
+                VideoCapturerSettings {
+                    fps: 1000,
+                    ..Default::default()
+                }
+            }
+
+            fn start(&mut self) -> Result<(), OtcError> {
+                self.started = true;
+                Ok(())
+            }
+
+            fn stop(&mut self) -> Result<(), OtcError> {
+                self.started = false;
+                Ok(())
+            }
+
+            fn frame(&mut self) -> Option<VideoFrame> {
+                self.frames.fetch_add(1, Ordering::Relaxed);
+                if !self.started {
+                    self.late_frames.fetch_add(1, Ordering::Relaxed);
+                }
+                None
+            }
+        }
+
+        let frames = Arc::new(AtomicUsize::new(0));
+        let late_frames = Arc::new(AtomicUsize::new(0));
+        let mut capturer = VideoCapturer::from_source(Source {
+            started: false,
+            frames: frames.clone(),
+            late_frames: late_frames.clone(),
+        })
+        .unwrap();
+        // Drive the capturer as the SDK would, stopping and restarting it
+        // quickly.
+        let callbacks = capturer.callbacks();
+        let callbacks = callbacks.lock().unwrap();
+        let user_data = callbacks.user_data;
+        let (start, stop, destroy) = (
+            callbacks.start.unwrap(),
+            callbacks.stop.unwrap(),
+            callbacks.destroy.unwrap(),
+        );
+        drop(callbacks);
+        for _ in 0..20 {
+            unsafe { start(std::ptr::null(), user_data) };
+            std::thread::sleep(Duration::from_millis(2));
+            unsafe { stop(std::ptr::null(), user_data) };
+        }
+        std::thread::sleep(Duration::from_millis(20));
+        unsafe { destroy(std::ptr::null(), user_data) };
+
+        assert!(frames.load(Ordering::Relaxed) > 0);
+        assert_eq!(late_frames.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_video_capturer_lifecycle() {
+        struct Flag(Arc<AtomicBool>);
+        impl Drop for Flag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::Relaxed);
+            }
+        }
+
+        let capturer = || {
+            VideoCapturer::new(
+                Default::default(),
+                VideoCapturerCallbacks::builder().build(),
+            )
+        };
+        // Ids keep growing, even as earlier capturers are dropped.
+        let ids: Vec<usize> = (0..3).map(|_| capturer().id()).collect();
+        assert!(ids.windows(2).all(|ids| ids[0] < ids[1]));
+        let first = capturer();
+        assert_eq!(first.clone().id(), first.id());
+        assert!(capturer().id() > first.id());
+
+        // A capturer never handed to the SDK is released with its last clone.
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = Flag(dropped.clone());
+        let capturer = VideoCapturer::new(
+            Default::default(),
+            VideoCapturerCallbacks::builder()
+                .init(move |_| {
+                    let _ = &flag;
+                    Ok(())
+                })
+                .build(),
+        );
+        let clone = capturer.clone();
+        drop(capturer);
+        assert!(!dropped.load(Ordering::Relaxed));
+        drop(clone);
+        assert!(dropped.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_late_event_count() {
+        let mut capturer = VideoCapturer::new(
+            Default::default(),
+            VideoCapturerCallbacks::builder().build(),
+        );
+        let callbacks = capturer.callbacks();
+        let start = callbacks.lock().unwrap().start.unwrap();
+
+        // The SDK calling back with no target, as it would for a capturer
+        // that no longer exists, is counted.
+        let before = opentok::late_event_count();
+        assert_eq!(unsafe { start(std::ptr::null(), std::ptr::null_mut()) }, 0);
+        assert!(opentok::late_event_count() > before);
+    }
+
+    #[test]
+    fn test_video_frame_buffer_checks() {
+        assert_eq!(FrameFormat::Yuv420P.buffer_size(4, 2), Some(12));
+        assert_eq!(FrameFormat::Yuy2.buffer_size(3, 1), Some(8));
+        assert_eq!(FrameFormat::Rgb24.buffer_size(2, 2), Some(12));
+        assert_eq!(FrameFormat::Mjpeg.buffer_size(2, 2), None);
+
+        assert_eq!(
+            VideoFrame::from_buffer(FrameFormat::Yuv420P, 4, 2, vec![0; 11]).err(),
+            Some(OtcError::InvalidParam("buffer"))
+        );
+        assert_eq!(
+            VideoFrame::from_buffer(FrameFormat::Rgb24, 0, 2, vec![0; 12]).err(),
+            Some(OtcError::InvalidParam("width"))
+        );
+        #[allow(deprecated)]
+        let frame = VideoFrame::new(FrameFormat::Yuv420P, 4, 2, vec![0; 11]);
+        assert!(frame.get_width().is_err());
+
+        let (y, u, v) = ([0u8; 8], [0u8; 2], [0u8; 2]);
+        assert_eq!(
+            VideoFrame::wrap_i420(4, 2, &y, 3, &u, 2, &v, 2).err(),
+            Some(OtcError::InvalidParam("y_plane"))
+        );
+        assert_eq!(
+            VideoFrame::wrap_i420(4, 2, &y, 4, &u, 2, &v[..1], 2).err(),
+            Some(OtcError::InvalidParam("v_plane"))
+        );
+        assert_eq!(
+            VideoFrame::wrap_nv12(4, 2, &y, 4, &u, 4).err(),
+            Some(OtcError::InvalidParam("chroma_plane"))
+        );
+    }
+
+    #[test]
+    fn test_video_frame_planes() {
+        let mut buffer = vec![0u8; 12];
+        buffer[8] = 1;
+        buffer[10] = 2;
+        let frame = VideoFrame::from_buffer(FrameFormat::Yuv420P, 4, 2, buffer).unwrap();
+        let planes = frame.planes().unwrap();
+        assert_eq!(planes.len(), 3);
+        assert_eq!(
+            (planes[0].width, planes[0].height, planes[0].stride),
+            (4, 2, 4)
+        );
+        assert_eq!(planes[1].row(0), &[1, 0]);
+        assert_eq!(planes[2].row(0), &[2, 0]);
+        assert!(frame.clone().planes_mut().is_err());
+
+        let mut copy = frame.mutable_copy().unwrap();
+        copy.planes_mut().unwrap()[0].row_mut(1)[3] = 9;
+        assert_eq!(copy.planes().unwrap()[0].row(1)[3], 9);
+        assert_eq!(frame.planes().unwrap()[0].row(1)[3], 0);
+
+        let (y, uv) = ([0u8; 8], [5u8, 6, 7, 8]);
+        let frame = VideoFrame::wrap_nv12(4, 2, &y, 4, &uv, 4).unwrap();
+        let planes = frame.planes().unwrap();
+        assert_eq!(planes.len(), 2);
+        assert_eq!(planes[1].sample_size, 2);
+        assert_eq!(planes[1].row(0), &uv);
+
+        let frame = VideoFrame::wrap_nv21(4, 2, &y, 4, &uv, 4).unwrap();
+        let planes = frame.planes().unwrap();
+        assert_eq!(planes.len(), 2);
+        assert!(matches!(planes[1].plane, FramePlane::VuInterleaved));
+        assert_eq!(planes[1].row(0), &uv);
+    }
+
+    #[test]
+    fn test_video_frame_ops_convert() {
+        let red = Image::new(FrameFormat::Rgba32, 2, 2, [255, 0, 0, 255].repeat(4)).unwrap();
+        let i420 = red.convert(FrameFormat::Yuv420P).unwrap();
+        assert_eq!(i420.data(), &[82, 82, 82, 82, 90, 240]);
+        let nv21 = red.convert(FrameFormat::Nv21).unwrap();
+        assert_eq!(nv21.data(), &[82, 82, 82, 82, 240, 90]);
+        assert_eq!(
+            i420.convert(FrameFormat::Rgba32).unwrap().data(),
+            &[255, 1, 0, 255].repeat(4)[..]
+        );
+
+        let argb = Image::new(FrameFormat::Argb32, 1, 1, vec![1, 2, 3, 4]).unwrap();
+        assert_eq!(
+            argb.convert(FrameFormat::Bgra32).unwrap().data(),
+            &[4, 3, 2, 1]
+        );
+        assert_eq!(argb.convert(FrameFormat::Rgb24).unwrap().data(), &[2, 3, 4]);
+
+        let yuy2 = Image::new(
+            FrameFormat::Yuy2,
+            2,
+            2,
+            vec![10, 100, 20, 200, 30, 110, 40, 210],
+        )
+        .unwrap();
+        let i420 = yuy2.convert(FrameFormat::Yuv420P).unwrap();
+        assert_eq!(i420.data(), &[10, 20, 30, 40, 105, 205]);
+        assert_eq!(
+            i420.convert(FrameFormat::Uyvy).unwrap().data(),
+            &[105, 10, 205, 20, 105, 30, 205, 40]
+        );
+
+        assert!(red.convert(FrameFormat::Mjpeg).is_err());
+        assert!(Image::new(FrameFormat::Rgb24, 2, 2, vec![0; 11]).is_err());
+    }
+
+    #[test]
+    fn test_video_frame_ops_geometry() {
+        // 3x2 RGB24 image whose pixels are numbered 0 to 5, row by row.
+        let pixels = |indices: &[u8]| -> Vec<u8> { indices.iter().flat_map(|i| [*i; 3]).collect() };
+        let image = Image::new(FrameFormat::Rgb24, 3, 2, pixels(&[0, 1, 2, 3, 4, 5])).unwrap();
+
+        let rotated = image.rotate(Rotation::Rotate90);
+        assert_eq!((rotated.width(), rotated.height()), (2, 3));
+        assert_eq!(rotated.data(), &pixels(&[3, 0, 4, 1, 5, 2])[..]);
+        assert_eq!(
+            image.rotate(Rotation::Rotate180).data(),
+            &pixels(&[5, 4, 3, 2, 1, 0])[..]
+        );
+        assert_eq!(
+            image.rotate(Rotation::Rotate270).data(),
+            &pixels(&[2, 5, 1, 4, 0, 3])[..]
+        );
+        assert_eq!(image.mirror().data(), &pixels(&[2, 1, 0, 5, 4, 3])[..]);
+        assert_eq!(
+            image.crop(1, 0, 2, 2).unwrap().data(),
+            &pixels(&[1, 2, 4, 5])[..]
+        );
+        assert!(image.crop(2, 0, 2, 2).is_err());
+        assert_eq!(Rotation::from_degrees(-90), Some(Rotation::Rotate270));
+
+        // Geometry operations do not alter 4:2:0 chroma on even boundaries.
+        let data: Vec<u8> = (0..24).collect();
+        let i420 = Image::new(FrameFormat::Yuv420P, 4, 4, data).unwrap();
+        assert_eq!(
+            i420.rotate(Rotation::Rotate90).rotate(Rotation::Rotate270),
+            i420
+        );
+        assert_eq!(i420.mirror().mirror(), i420);
+        assert_eq!(
+            i420.rotate(Rotation::Rotate180).data(),
+            &[
+                15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 19, 18, 17, 16, 23, 22, 21,
+                20
+            ]
+        );
+    }
+
+    #[test]
+    fn test_video_frame_ops_scale() {
+        let gray = |values: &[u8]| -> Vec<u8> { values.iter().flat_map(|v| [*v; 3]).collect() };
+        let image = Image::new(FrameFormat::Rgb24, 2, 1, gray(&[0, 255])).unwrap();
+        let scaled = image.scale(4, 1, Filter::Bilinear).unwrap();
+        assert_eq!(scaled.data(), &gray(&[0, 64, 191, 255])[..]);
+
+        let image = Image::new(
+            FrameFormat::Rgb24,
+            4,
+            2,
+            gray(&[0, 10, 20, 30, 40, 50, 60, 70]),
+        )
+        .unwrap();
+        let scaled = image.scale(2, 1, Filter::Area).unwrap();
+        assert_eq!(scaled.data(), &gray(&[25, 45])[..]);
+        assert_eq!(image.scale(4, 2, Filter::Bilinear).unwrap(), image);
+        assert!(image.scale(0, 1, Filter::Area).is_err());
+
+        // More samples per box than a u32 sum of them can hold.
+        let (width, height) = (8192, 4096);
+        let image = Image::new(
+            FrameFormat::Yuv420P,
+            width,
+            height,
+            vec![255; width as usize * height as usize * 3 / 2],
+        )
+        .unwrap();
+        let scaled = image.scale(1, 1, Filter::Area).unwrap();
+        assert_eq!(scaled.data(), &[255, 255, 255][..]);
+    }
+
+    #[test]
+    fn test_video_frame_ops_reference_images() {
+        // Generated by `tests/fixtures/ops/generate.py` with floating point
+        // formulas, which the fixed point ones must match to within one.
+        let fixture = |name: &str| {
+            let path = format!(
+                "{}/tests/fixtures/ops/{}.raw",
+                env!("CARGO_MANIFEST_DIR"),
+                name
+            );
+            std::fs::read(&path).unwrap()
+        };
+        let assert_close = |image: &Image, expected: &[u8], what: &str| {
+            assert_eq!(image.data().len(), expected.len(), "{}", what);
+            for (i, (actual, expected)) in image.data().iter().zip(expected).enumerate() {
+                assert!(
+                    (*actual as i32 - *expected as i32).abs() <= 1,
+                    "{}: byte {} is {}, expected {}",
+                    what,
+                    i,
+                    actual,
+                    expected
+                );
+            }
+        };
+
+        let formats = [
+            (FrameFormat::Yuv420P, "yuv420p"),
+            (FrameFormat::Nv12, "nv12"),
+            (FrameFormat::Nv21, "nv21"),
+            (FrameFormat::Yuy2, "yuy2"),
+            (FrameFormat::Uyvy, "uyvy"),
+            (FrameFormat::Rgba32, "rgba32"),
+            (FrameFormat::Argb32, "argb32"),
+            (FrameFormat::Bgra32, "bgra32"),
+            (FrameFormat::Abgr32, "abgr32"),
+            (FrameFormat::Rgb24, "rgb24"),
+        ];
+        for (src_format, src_name) in &formats {
+            let image = Image::new(*src_format, 8, 6, fixture(src_name)).unwrap();
+            for (format, name) in &formats {
+                assert_close(
+                    &image.convert(*format).unwrap(),
+                    &fixture(name),
+                    &format!("{} to {}", src_name, name),
+                );
+            }
+        }
+
+        let image = Image::new(FrameFormat::Rgb24, 8, 6, fixture("rgb24")).unwrap();
+        for (filter, filter_name) in &[(Filter::Bilinear, "bilinear"), (Filter::Area, "area")] {
+            for (width, height) in &[(4, 3), (5, 4), (12, 9)] {
+                let name = format!("rgb24_{}_{}x{}", filter_name, width, height);
+                assert_close(
+                    &image.scale(*width, *height, *filter).unwrap(),
+                    &fixture(&name),
+                    &name,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_video_frame_ops_frames() {
+        let data: Vec<u8> = (0..12).collect();
+        let frame = VideoFrame::from_buffer(FrameFormat::Yuv420P, 4, 2, data.clone()).unwrap();
+        let image = Image::from_frame(&frame).unwrap();
+        assert_eq!(image.data(), &data[..]);
+        let frame = image.mirror().into_frame().unwrap();
+        assert_eq!(
+            frame.get_buffer().unwrap(),
+            &[3, 2, 1, 0, 7, 6, 5, 4, 9, 8, 11, 10]
+        );
+    }
+
+    #[test]
+    fn test_video_frame_metadata() {
+        let mut frame = VideoFrame::from_buffer(FrameFormat::Rgb24, 1, 1, vec![0; 3]).unwrap();
+        assert!(frame.metadata().unwrap().is_empty());
+        frame.set_metadata(&42u64.to_le_bytes()).unwrap();
+        assert_eq!(frame.metadata().unwrap(), &42u64.to_le_bytes());
+        assert_eq!(
+            frame.set_metadata(&[0; METADATA_MAX_SIZE + 1]),
+            Err(OtcError::InvalidParam("metadata"))
+        );
+
+        let capturer = VideoCapturer::new(
+            Default::default(),
+            VideoCapturerCallbacks::builder().build(),
+        );
+        assert_eq!(
+            capturer.provide_frame_with_metadata(0, &frame, &[1, 2, 3]),
+            Err(OtcError::NullError)
+        );
+        assert_eq!(frame.metadata().unwrap(), &42u64.to_le_bytes());
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    #[test]
+    fn test_stats_serialization() {
+        use opentok::publisher::PublisherAudioStats;
+        use opentok::subscriber::SubscriberVideoStats;
+
+        let stats = PublisherAudioStats {
+            connection_id: "connection".into(),
+            packets_sent: 10,
+            bytes_sent: 1200,
+            audio_level: 0.5,
+            timestamp: 1000.0,
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PublisherAudioStats>(&json).unwrap(),
+            stats
+        );
+
+        let stats = SubscriberVideoStats {
+            packets_lost: 1,
+            packets_received: 99,
+            bytes_received: 120_000,
+            timestamp: 1000.0,
+        };
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["packets_received"], 99);
+        assert_eq!(
+            serde_json::from_value::<SubscriberVideoStats>(json).unwrap(),
+            stats
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_rtc_stats_report_parsing() {
+        use opentok::rtc_stats::{
+            selected_candidate_pair, RtcStats, RtcStatsError, RtcStatsReport,
+        };
+
+        let report = RtcStatsReport {
+            connection_id: String::new(),
+            json: r#"[
+                {"id": "OT1", "type": "outbound-rtp", "timestamp": 1000.5, "kind": "video",
+                 "ssrc": 1234, "bytesSent": 52000, "framesEncoded": 90,
+                 "qualityLimitationReason": "bandwidth"},
+                {"id": "IT1", "type": "inbound-rtp", "timestamp": 1000.5, "kind": "audio",
+                 "packetsLost": -1, "jitter": 0.012, "framesDropped": 3},
+                {"id": "RI1", "type": "remote-inbound-rtp", "localId": "OT1",
+                 "roundTripTime": 0.045},
+                {"id": "CP1", "type": "candidate-pair", "state": "succeeded",
+                 "nominated": true, "currentRoundTripTime": 0.04},
+                {"id": "CP2", "type": "candidate-pair", "state": "succeeded",
+                 "nominated": true, "currentRoundTripTime": 0.03},
+                {"id": "T1", "type": "transport", "selectedCandidatePairId": "CP2"},
+                {"id": "C1", "type": "codec", "mimeType": "video/VP8", "clockRate": 90000},
+                {"id": "M1", "type": "media-source", "kind": "audio"}
+            ]"#
+            .into(),
+        };
+        let stats = report.parse().unwrap();
+        assert_eq!(stats.len(), 8);
+
+        match &stats[0] {
+            RtcStats::OutboundRtp(outbound) => {
+                assert_eq!(outbound.id, "OT1");
+                assert_eq!(outbound.timestamp, 1000.5);
+                assert_eq!(outbound.kind.as_deref(), Some("video"));
+                assert_eq!(outbound.ssrc, Some(1234));
+                assert_eq!(outbound.bytes_sent, Some(52000));
+                assert_eq!(outbound.packets_sent, None);
+                assert_eq!(
+                    outbound.quality_limitation_reason.as_deref(),
+                    Some("bandwidth")
+                );
+            }
+            other => panic!("Unexpected entry {:?}", other),
+        }
+        match &stats[1] {
+            RtcStats::InboundRtp(inbound) => {
+                assert_eq!(inbound.packets_lost, Some(-1));
+                assert_eq!(inbound.jitter, Some(0.012));
+                assert_eq!(inbound.frames_dropped, Some(3));
+            }
+            other => panic!("Unexpected entry {:?}", other),
+        }
+        match &stats[2] {
+            RtcStats::RemoteInboundRtp(remote) => {
+                assert_eq!(remote.local_id.as_deref(), Some("OT1"));
+                assert_eq!(remote.round_trip_time, Some(0.045));
+            }
+            other => panic!("Unexpected entry {:?}", other),
+        }
+        match &stats[6] {
+            RtcStats::Codec(codec) => {
+                assert_eq!(codec.mime_type.as_deref(), Some("video/VP8"));
+                assert_eq!(codec.clock_rate, Some(90000));
+            }
+            other => panic!("Unexpected entry {:?}", other),
+        }
+        match &stats[7] {
+            RtcStats::Other(json) => assert!(json.contains("media-source")),
+            other => panic!("Unexpected entry {:?}", other),
+        }
+
+        let pair = selected_candidate_pair(&stats).unwrap();
+        assert_eq!(pair.id, "CP2");
+        assert_eq!(pair.current_round_trip_time, Some(0.03));
+        assert!(selected_candidate_pair(&stats[..5]).is_none());
+
+        let report = RtcStatsReport {
+            connection_id: String::new(),
+            json: "{}".into(),
+        };
+        assert!(matches!(report.parse(), Err(RtcStatsError::InvalidJson(_))));
+    }
+
+    #[test]
+    fn test_publisher_settings() {
+        use opentok::stream::StreamVideoType;
+        use std::convert::TryFrom;
+
+        let settings = PublisherSettings::default();
+        assert!(settings.audio_track());
+        assert!(settings.video_track());
+        assert!(!settings.stereo());
+        assert_eq!(settings.max_audio_bitrate(), None);
+        assert_eq!(settings.audio_fallback(), None);
+        assert_eq!(settings.video_type(), None);
+
+        let settings = PublisherSettings::builder()
+            .video_track(false)
+            .stereo(true)
+            .max_audio_bitrate(128_000)
+            .audio_fallback(false)
+            .video_type(VideoType::Screen)
+            .build();
+        assert!(settings.audio_track());
+        assert!(!settings.video_track());
+        assert!(settings.stereo());
+        assert_eq!(settings.max_audio_bitrate(), Some(128_000));
+        assert_eq!(settings.audio_fallback(), Some(false));
+        assert_eq!(settings.video_type(), Some(VideoType::Screen));
+        assert_eq!(
+            VideoType::try_from(StreamVideoType::Screen),
+            Ok(VideoType::Screen)
+        );
+        assert_eq!(
+            VideoType::try_from(StreamVideoType::Custom),
+            Err(OtcError::InvalidParam("video_type"))
+        );
+        assert_eq!(
+            StreamVideoType::from(VideoType::Camera),
+            StreamVideoType::Camera
+        );
+
+        assert!(matches!(
+            Publisher::with_settings(
+                "bad\0name",
+                None,
+                PublisherCallbacks::builder().build(),
+                &settings
+            ),
+            Err(OtcError::InvalidParam("name"))
+        ));
+    }
+
+    #[cfg(not(feature = "mock"))]
     #[test]
     fn test_publisher() {
         let (api_key, session_id, token) = setup_test();
@@ -211,12 +1026,13 @@ mod tests {
                         }
                         if let Ok(buffer) = capturer.pull_buffer() {
                             buf.extend_from_slice((*buffer).as_ref());
-                            let frame = VideoFrame::new(
+                            let frame = VideoFrame::from_buffer(
                                 settings.format,
                                 settings.width,
                                 settings.height,
                                 buf.clone(),
-                            );
+                            )
+                            .unwrap();
                             video_capturer.provide_frame(0, &frame).unwrap();
                             buf.clear();
                         }
@@ -254,12 +1070,49 @@ mod tests {
 
         receiver.recv().unwrap();
 
+        {
+            let publisher = publisher.lock().unwrap();
+            assert_eq!(publisher.name(), "publisher");
+            assert_eq!(publisher.session().unwrap().id(), session.id());
+            assert!(publisher.is_publishing_audio().unwrap());
+            assert!(publisher.is_publishing_video().unwrap());
+            assert_eq!(publisher.video_type().unwrap(), VideoType::Camera);
+            publisher.set_video_type(VideoType::Screen).unwrap();
+            assert_eq!(publisher.video_type().unwrap(), VideoType::Screen);
+            publisher.set_max_audio_bitrate(64_000).unwrap();
+        }
+
         audio_capture_thread_running_.store(false, Ordering::Relaxed);
         render_thread_running__.store(false, Ordering::Relaxed);
 
         test_teardown();
     }
 
+    #[test]
+    fn test_subscriber_audio_muted() {
+        let subscriber = Subscriber::new(SubscriberCallbacks::builder().build());
+        assert!(!subscriber.is_audio_muted());
+        assert_eq!(subscriber.set_audio_muted(true), Ok(()));
+        assert!(subscriber.is_audio_muted());
+        assert_eq!(
+            subscriber.set_subscribe_to_audio(true),
+            Err(OtcError::NullError)
+        );
+        assert_eq!(subscriber.set_audio_muted(false), Ok(()));
+        assert!(!subscriber.is_audio_muted());
+    }
+
+    #[test]
+    fn test_subscriber_auto_resubscribe() {
+        let subscriber = Subscriber::new(SubscriberCallbacks::builder().build());
+        assert!(subscriber.get_stream().is_none());
+        assert!(!subscriber.auto_resubscribe());
+        subscriber.set_auto_resubscribe(true);
+        assert!(subscriber.auto_resubscribe());
+        assert!(subscriber.clone().auto_resubscribe());
+    }
+
+    #[cfg(not(feature = "mock"))]
     #[test]
     fn test_subscriber() {
         let (api_key, session_id, token) = setup_test();
@@ -339,4 +1192,1013 @@ mod tests {
 
         test_teardown();
     }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_publish_subscribe() {
+        struct Source;
+        impl VideoSource for Source {
+            fn settings(&self) -> VideoCapturerSettings {
+                VideoCapturerSettings {
+                    width: 320,
+                    height: 240,
+                    ..Default::default()
+                }
+            }
+
+            fn frame(&mut self) -> Option<VideoFrame> {
+                VideoFrame::from_buffer(FrameFormat::Yuv420P, 320, 240, vec![0; 320 * 240 * 3 / 2])
+                    .ok()
+            }
+        }
+
+        opentok::init().unwrap();
+        let session_id = "mock-publish-subscribe";
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let subscriber = Arc::new(Subscriber::new(
+            SubscriberCallbacks::builder()
+                .on_render_frame(move |_, frame| {
+                    let size = (frame.get_width().unwrap(), frame.get_height().unwrap());
+                    let _ = sender.lock().unwrap().send(size);
+                })
+                .build(),
+        ));
+
+        let connection_created = Arc::new(AtomicBool::new(false));
+        let connection_created_ = connection_created.clone();
+        let stream_dropped = Arc::new(AtomicBool::new(false));
+        let stream_dropped_ = stream_dropped.clone();
+        let subscriber_ = subscriber.clone();
+        let session = Session::new(
+            "",
+            session_id,
+            SessionCallbacks::builder()
+                .on_connection_created(move |_, _| {
+                    connection_created_.store(true, Ordering::Relaxed);
+                })
+                .on_stream_received(move |session, stream| {
+                    subscriber_.set_stream(stream).unwrap();
+                    session.subscribe(&subscriber_).unwrap();
+                })
+                .on_stream_dropped(move |_, _| {
+                    stream_dropped_.store(true, Ordering::Relaxed);
+                })
+                .build(),
+        )
+        .unwrap();
+        session.connect("").unwrap();
+        opentok::mock::flush();
+
+        let publisher_session =
+            Session::new("", session_id, SessionCallbacks::builder().build()).unwrap();
+        publisher_session.connect("").unwrap();
+        opentok::mock::flush();
+        assert!(connection_created.load(Ordering::Relaxed));
+
+        let publisher = Publisher::new(
+            "publisher",
+            Some(VideoCapturer::from_source(Source).unwrap()),
+            PublisherCallbacks::builder().build(),
+        );
+        publisher_session.publish(&publisher).unwrap();
+
+        assert_eq!(receiver.recv().unwrap(), (320, 240));
+
+        publisher_session.unpublish(&publisher).unwrap();
+        opentok::mock::flush();
+        assert!(stream_dropped.load(Ordering::Relaxed));
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_auto_resubscribe() {
+        use opentok::stream::Stream;
+
+        opentok::init().unwrap();
+        let session_id = "mock-auto-resubscribe";
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        // Resubscribing happens out of the session's callback, so it is
+        // waited for with the subscriber's.
+        let (connected_sender, connected) = mpsc::channel();
+        let connected_sender = Mutex::new(connected_sender);
+        let subscriber = Subscriber::new(
+            SubscriberCallbacks::builder()
+                .on_connected(move |_, stream| {
+                    let _ = connected_sender.lock().unwrap().send(stream.id());
+                })
+                .build(),
+        );
+        subscriber.set_auto_resubscribe(true);
+        let subscriber_ = subscriber.clone();
+        let subscribed = AtomicBool::new(false);
+        let session = Session::new(
+            "",
+            session_id,
+            SessionCallbacks::builder()
+                .on_stream_received(move |session, stream| {
+                    if !subscribed.swap(true, Ordering::Relaxed) {
+                        subscriber_.set_stream(stream.clone()).unwrap();
+                        session.subscribe(&subscriber_).unwrap();
+                    }
+                    let _ = sender.lock().unwrap().send(stream);
+                })
+                .build(),
+        )
+        .unwrap();
+        session.connect("").unwrap();
+        let peer = Session::new("", session_id, SessionCallbacks::builder().build()).unwrap();
+        peer.connect("").unwrap();
+        opentok::mock::flush();
+
+        let publish = |name: &str, video_type: VideoType| {
+            let settings = PublisherSettings::builder().video_type(video_type).build();
+            let publisher = Publisher::with_settings(
+                name,
+                None,
+                PublisherCallbacks::builder().build(),
+                &settings,
+            )
+            .unwrap();
+            peer.publish(&publisher).unwrap();
+            opentok::mock::flush();
+            (publisher, receiver.recv().unwrap())
+        };
+
+        // Streams without a name are not told apart.
+        let (_unnamed, unnamed) = publish("", VideoType::Camera);
+        assert_eq!(connected.recv().unwrap(), unnamed.id());
+        publish("", VideoType::Camera);
+
+        // Neither are streams of different video types.
+        let (camera_publisher, camera) = publish("camera", VideoType::Camera);
+        subscriber.switch_stream(camera.clone()).unwrap();
+        assert_eq!(connected.recv().unwrap(), camera.id());
+        publish("camera", VideoType::Screen);
+
+        // Only the republished stream is subscribed to, so the streams
+        // received before were left alone.
+        peer.unpublish(&camera_publisher).unwrap();
+        opentok::mock::flush();
+        let (_camera_publisher, republished) = publish("camera", VideoType::Camera);
+        assert_eq!(connected.recv().unwrap(), republished.id());
+        assert_eq!(subscriber.get_stream().unwrap().id(), republished.id());
+        assert!(connected.try_recv().is_err());
+
+        // A stream no subscriber can be created for leaves it as it was.
+        assert_eq!(
+            subscriber.switch_stream(Stream::from(std::ptr::null())),
+            Err(OtcError::NullError)
+        );
+        assert_eq!(subscriber.get_stream().unwrap().id(), republished.id());
+        assert!(subscriber.set_subscribe_to_video(false).is_ok());
+    }
+
+    #[cfg(all(feature = "mock", feature = "futures"))]
+    #[test]
+    fn test_mock_render_frame_events_are_bounded() {
+        use futures::FutureExt;
+        use futures::StreamExt;
+        use opentok::events::LOSSY_EVENT_QUEUE_LENGTH;
+        use opentok::subscriber::SubscriberEvent;
+
+        struct Source;
+        impl VideoSource for Source {
+            fn settings(&self) -> VideoCapturerSettings {
+                VideoCapturerSettings {
+                    width: 16,
+                    height: 16,
+                    fps: 60,
+                    ..Default::default()
+                }
+            }
+
+            fn frame(&mut self) -> Option<VideoFrame> {
+                VideoFrame::from_buffer(FrameFormat::Yuv420P, 16, 16, vec![0; 16 * 16 * 3 / 2]).ok()
+            }
+        }
+
+        opentok::init().unwrap();
+        let session_id = "mock-render-frame-events";
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let subscriber = Arc::new(Subscriber::new(
+            SubscriberCallbacks::builder()
+                .on_render_frame(move |_, _| {
+                    let _ = sender.lock().unwrap().send(());
+                })
+                .build(),
+        ));
+        let mut events = subscriber.events();
+        let subscriber_ = subscriber.clone();
+        let session = Session::new(
+            "",
+            session_id,
+            SessionCallbacks::builder()
+                .on_stream_received(move |session, stream| {
+                    subscriber_.set_stream(stream).unwrap();
+                    session.subscribe(&subscriber_).unwrap();
+                })
+                .build(),
+        )
+        .unwrap();
+        session.connect("").unwrap();
+        let publisher_session =
+            Session::new("", session_id, SessionCallbacks::builder().build()).unwrap();
+        publisher_session.connect("").unwrap();
+        opentok::mock::flush();
+
+        let publisher = Publisher::new(
+            "publisher",
+            Some(VideoCapturer::from_source(Source).unwrap()),
+            PublisherCallbacks::builder().build(),
+        );
+        publisher_session.publish(&publisher).unwrap();
+        // Every frame reaches the callback while the stream is not read.
+        for _ in 0..LOSSY_EVENT_QUEUE_LENGTH * 3 {
+            receiver.recv().unwrap();
+        }
+        publisher_session.unpublish(&publisher).unwrap();
+        opentok::mock::flush();
+
+        let mut frames = 0;
+        let mut connected = false;
+        while let Some(Some(event)) = events.next().now_or_never() {
+            match event {
+                SubscriberEvent::RenderFrame(_) => frames += 1,
+                SubscriberEvent::Connected(_) => connected = true,
+                _ => {}
+            }
+        }
+        assert!(connected);
+        assert_eq!(frames, LOSSY_EVENT_QUEUE_LENGTH);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_video_capturer_destroy() {
+        struct Flag(Arc<AtomicBool>);
+        impl Drop for Flag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::Relaxed);
+            }
+        }
+
+        opentok::init().unwrap();
+        let session_id = "mock-video-capturer-destroy";
+        let session = Session::new("", session_id, SessionCallbacks::builder().build()).unwrap();
+        session.connect("").unwrap();
+        opentok::mock::flush();
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = Flag(dropped.clone());
+        let capturer = VideoCapturer::new(
+            Default::default(),
+            VideoCapturerCallbacks::builder()
+                .destroy(move |_| {
+                    let _ = &flag;
+                    let _ = sender.lock().unwrap().send("destroyed");
+                    Ok(())
+                })
+                .build(),
+        );
+        let publisher = Publisher::new(
+            "publisher",
+            Some(capturer),
+            PublisherCallbacks::builder().build(),
+        );
+        session.publish(&publisher).unwrap();
+        opentok::mock::flush();
+        session.unpublish(&publisher).unwrap();
+        opentok::mock::flush();
+        // Still in use by the publisher.
+        assert!(receiver.try_recv().is_err());
+        assert!(!dropped.load(Ordering::Relaxed));
+
+        // Deleting the publisher destroys the capturer, after which the SDK
+        // no longer holds it.
+        drop(publisher);
+        assert_eq!(receiver.try_recv().unwrap(), "destroyed");
+        assert!(dropped.load(Ordering::Relaxed));
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_signals_and_archives() {
+        opentok::init().unwrap();
+        let session_id = "mock-signals-and-archives";
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let sender_ = sender.clone();
+        let sender__ = sender.clone();
+        let receiving_session = Session::new(
+            "",
+            session_id,
+            SessionCallbacks::builder()
+                .on_signal_received(move |_, type_, data, connection| {
+                    let signal = format!("{}:{}:{}", type_, data, connection.id());
+                    sender.lock().unwrap().send(signal).unwrap();
+                })
+                .on_archive_started(move |_, archive_id, name| {
+                    let event = format!("started:{}:{}", archive_id, name);
+                    sender_.lock().unwrap().send(event).unwrap();
+                })
+                .on_archive_stopped(move |_, archive_id| {
+                    let event = format!("stopped:{}", archive_id);
+                    sender__.lock().unwrap().send(event).unwrap();
+                })
+                .build(),
+        )
+        .unwrap();
+        receiving_session.connect("").unwrap();
+
+        let sending_session =
+            Session::new("", session_id, SessionCallbacks::builder().build()).unwrap();
+        sending_session.connect("").unwrap();
+        opentok::mock::flush();
+
+        sending_session.send_signal("type", "data").unwrap();
+        let connection_id = sending_session.connection().unwrap().id();
+        assert_eq!(
+            receiver.recv().unwrap(),
+            format!("type:data:{}", connection_id)
+        );
+
+        let archive_id = opentok::mock::start_archive(session_id, "archive");
+        assert_eq!(
+            receiver.recv().unwrap(),
+            format!("started:{}:archive", archive_id)
+        );
+        opentok::mock::stop_archive(session_id, &archive_id);
+        assert_eq!(receiver.recv().unwrap(), format!("stopped:{}", archive_id));
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_errors() {
+        use opentok::publisher::PublisherError;
+
+        opentok::init().unwrap();
+        let session_id = "mock-errors";
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let session = Session::new(
+            "",
+            session_id,
+            SessionCallbacks::builder()
+                .on_error(move |_, _, error| {
+                    sender.lock().unwrap().send(error).unwrap();
+                })
+                .build(),
+        )
+        .unwrap();
+        opentok::mock::set_connect_error(session_id, Some(SessionError::ConnectionRefused));
+        session.connect("").unwrap();
+        assert_eq!(receiver.recv().unwrap(), SessionError::ConnectionRefused);
+
+        opentok::mock::set_connect_error(session_id, None);
+        let session = Session::new("", session_id, SessionCallbacks::builder().build()).unwrap();
+        session.connect("").unwrap();
+        opentok::mock::flush();
+        assert!(session.connection().is_some());
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let publisher = Publisher::new(
+            "publisher",
+            None,
+            PublisherCallbacks::builder()
+                .on_error(move |_, _, error| {
+                    sender.lock().unwrap().send(error).unwrap();
+                })
+                .build(),
+        );
+        opentok::mock::set_publish_error(session_id, Some(PublisherError::TimedOut));
+        session.publish(&publisher).unwrap();
+        assert_eq!(receiver.recv().unwrap(), PublisherError::TimedOut);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_session_faults() {
+        use opentok::mock::{Fault, Script};
+        use opentok::publisher::PublisherError;
+        use std::time::Duration;
+
+        // The faults played against a connected session publishing a stream,
+        // and the callbacks it gets, followed by those of a new `connect`.
+        let cases: Vec<(Vec<Fault>, Vec<&str>)> = vec![
+            (
+                vec![Fault::ConnectionInterrupted, Fault::Reconnected],
+                vec!["reconnection_started", "reconnected"],
+            ),
+            (
+                vec![Fault::ConnectionInterrupted],
+                vec!["reconnection_started"],
+            ),
+            (vec![Fault::Reconnected], vec![]),
+            (
+                vec![
+                    Fault::Error(SessionError::ConnectionDropped),
+                    Fault::Disconnected,
+                ],
+                vec![
+                    "error ConnectionDropped",
+                    "stream_destroyed",
+                    "disconnected",
+                    "connected",
+                ],
+            ),
+            (
+                vec![Fault::ConnectionInterrupted, Fault::Disconnected],
+                vec![
+                    "reconnection_started",
+                    "stream_destroyed",
+                    "disconnected",
+                    "connected",
+                ],
+            ),
+            (vec![Fault::StreamsDropped], vec!["stream_destroyed"]),
+            (
+                vec![Fault::PublisherError(PublisherError::Internal)],
+                vec!["publisher_error Internal", "stream_destroyed"],
+            ),
+        ];
+
+        opentok::init().unwrap();
+        for (index, (faults, expected)) in cases.into_iter().enumerate() {
+            let session_id = format!("mock-session-faults-{}", index);
+            let events = Arc::new(Mutex::new(Vec::new()));
+            let record = |event: &'static str| {
+                let events = events.clone();
+                move |_: &Session| events.lock().unwrap().push(event.to_owned())
+            };
+            let events_ = events.clone();
+            let session = Session::new(
+                "",
+                &session_id,
+                SessionCallbacks::builder()
+                    .on_connected(record("connected"))
+                    .on_reconnection_started(record("reconnection_started"))
+                    .on_reconnected(record("reconnected"))
+                    .on_disconnected(record("disconnected"))
+                    .on_error(move |_, _, error| {
+                        let event = format!("error {:?}", error);
+                        events_.lock().unwrap().push(event);
+                    })
+                    .build(),
+            )
+            .unwrap();
+            let events_ = events.clone();
+            let events__ = events.clone();
+            let publisher = Publisher::new(
+                "publisher",
+                None,
+                PublisherCallbacks::builder()
+                    .on_stream_destroyed(move |_, _| {
+                        events_.lock().unwrap().push("stream_destroyed".to_owned());
+                    })
+                    .on_error(move |_, _, error| {
+                        let event = format!("publisher_error {:?}", error);
+                        events__.lock().unwrap().push(event);
+                    })
+                    .build(),
+            );
+            session.connect("").unwrap();
+            opentok::mock::flush();
+            session.publish(&publisher).unwrap();
+            opentok::mock::flush();
+            events.lock().unwrap().clear();
+
+            faults
+                .into_iter()
+                .fold(Script::new(), |script, fault| {
+                    script.at(Duration::from_millis(0), &session, fault)
+                })
+                .play();
+            session.connect("").unwrap();
+            opentok::mock::flush();
+
+            assert_eq!(*events.lock().unwrap(), expected, "case {}", index);
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_script_stall() {
+        use opentok::mock::{Fault, Script};
+        use std::time::{Duration, Instant};
+
+        opentok::init().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let session = Session::new(
+            "",
+            "mock-script-stall",
+            SessionCallbacks::builder()
+                .on_reconnection_started(move |_| {
+                    sender.lock().unwrap().send(Instant::now()).unwrap();
+                })
+                .build(),
+        )
+        .unwrap();
+        session.connect("").unwrap();
+        opentok::mock::flush();
+
+        let start = Instant::now();
+        Script::new()
+            .stall(Duration::from_millis(0), Duration::from_millis(100))
+            .at(
+                Duration::from_millis(10),
+                &session,
+                Fault::ConnectionInterrupted,
+            )
+            .play();
+        let delivered = receiver.recv().unwrap();
+        assert!(delivered.duration_since(start) >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_reconnect_policy() {
+        use opentok::reconnect::ReconnectPolicy;
+        use std::time::Duration;
+
+        let policy = ReconnectPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+            multiplier: 2.,
+            jitter: 0.,
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(300));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(300));
+        assert!(policy.allows(3));
+        assert!(!policy.allows(4));
+        assert!(ReconnectPolicy {
+            max_attempts: 0,
+            ..policy
+        }
+        .allows(u32::MAX));
+
+        let jittered = ReconnectPolicy {
+            jitter: 0.5,
+            ..policy
+        };
+        for _ in 0..100 {
+            let delay = jittered.delay(1);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+        }
+
+        // Jitter lengthening the delay past what a `Duration` holds gives
+        // the maximum delay instead of panicking.
+        let unbounded = ReconnectPolicy {
+            max_delay: Duration::MAX,
+            jitter: 1.,
+            ..policy
+        };
+        for _ in 0..100 {
+            let delay = unbounded.delay(u32::MAX);
+            assert!(delay == Duration::MAX || delay.as_secs_f64() < u64::MAX as f64);
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_reconnect() {
+        use opentok::mock::{Fault, Script};
+        use opentok::reconnect::{ReconnectEvent, ReconnectPolicy};
+        use std::time::Duration;
+
+        opentok::init().unwrap();
+        let session_id = "mock-reconnect";
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            jitter: 0.,
+            ..Default::default()
+        };
+
+        let peer = Session::new("", session_id, SessionCallbacks::builder().build()).unwrap();
+        peer.connect("").unwrap();
+        let peer_publisher = Publisher::new("peer", None, PublisherCallbacks::builder().build());
+        peer.publish(&peer_publisher).unwrap();
+        opentok::mock::flush();
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let sender_ = sender.clone();
+        let sender__ = sender.clone();
+        let subscriber = Subscriber::new(
+            SubscriberCallbacks::builder()
+                .on_connected(move |_, _| {
+                    let _ = sender_
+                        .lock()
+                        .unwrap()
+                        .send("subscriber connected".to_owned());
+                })
+                .build(),
+        );
+        let subscriber_ = subscriber.clone();
+        let subscribed = AtomicBool::new(false);
+        let session = Session::new(
+            "",
+            session_id,
+            SessionCallbacks::builder()
+                .on_stream_received(move |session, stream| {
+                    if !subscribed.swap(true, Ordering::Relaxed) {
+                        subscriber_.set_stream(stream).unwrap();
+                        session.subscribe(&subscriber_).unwrap();
+                    }
+                })
+                .on_reconnect(move |_, event| {
+                    let _ = sender__.lock().unwrap().send(format!("{:?}", event));
+                })
+                .build(),
+        )
+        .unwrap();
+        session.set_reconnect_policy(Some(policy));
+        assert_eq!(session.reconnect_policy(), Some(policy));
+        let publisher = Publisher::new(
+            "publisher",
+            None,
+            PublisherCallbacks::builder()
+                .on_stream_created(move |_, _| {
+                    let _ = sender.lock().unwrap().send("stream created".to_owned());
+                })
+                .build(),
+        );
+        session.connect("").unwrap();
+        opentok::mock::flush();
+        session.publish(&publisher).unwrap();
+        assert_eq!(receiver.recv().unwrap(), "subscriber connected");
+        assert_eq!(receiver.recv().unwrap(), "stream created");
+
+        Script::new()
+            .at(
+                Duration::from_millis(0),
+                &session,
+                Fault::Error(SessionError::ConnectionDropped),
+            )
+            .play();
+        let mut events: Vec<String> = (0..5).map(|_| receiver.recv().unwrap()).collect();
+        // The publisher and the subscriber are restored in any order.
+        events[3..].sort();
+        assert_eq!(
+            events,
+            vec![
+                format!(
+                    "{:?}",
+                    ReconnectEvent::AttemptScheduled(1, Duration::from_millis(10))
+                ),
+                format!("{:?}", ReconnectEvent::AttemptStarted(1)),
+                format!("{:?}", ReconnectEvent::Reconnected(1)),
+                "stream created".to_owned(),
+                "subscriber connected".to_owned(),
+            ]
+        );
+        assert!(session.connection().is_some());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_reconnect_gives_up() {
+        use opentok::mock::{Fault, Script};
+        use opentok::reconnect::{ReconnectEvent, ReconnectPolicy};
+        use opentok::session::StateCause;
+        use std::time::Duration;
+
+        opentok::init().unwrap();
+        let session_id = "mock-reconnect-gives-up";
+        let (sender, receiver) = mpsc::channel();
+        let sender = Arc::new(Mutex::new(sender));
+        let session = Session::new(
+            "",
+            session_id,
+            SessionCallbacks::builder()
+                .on_reconnect(move |_, event| {
+                    sender.lock().unwrap().send(event).unwrap();
+                })
+                .build(),
+        )
+        .unwrap();
+        session.set_reconnect_policy(Some(ReconnectPolicy {
+            max_attempts: 2,
+            initial_delay: Duration::from_millis(1),
+            jitter: 0.,
+            ..Default::default()
+        }));
+        session.connect("").unwrap();
+        opentok::mock::flush();
+
+        // Disconnecting on request does not reconnect.
+        session.disconnect().unwrap();
+        opentok::mock::flush();
+        session.connect("").unwrap();
+        opentok::mock::flush();
+        assert!(receiver.try_recv().is_err());
+
+        opentok::mock::set_connect_error(session_id, Some(SessionError::ConnectionRefused));
+        let watcher = session.watch_state();
+        Script::new()
+            .at(Duration::from_millis(0), &session, Fault::Disconnected)
+            .play();
+        let events: Vec<ReconnectEvent> = (0..7).map(|_| receiver.recv().unwrap()).collect();
+        assert_eq!(
+            events,
+            vec![
+                ReconnectEvent::AttemptScheduled(1, Duration::from_millis(1)),
+                ReconnectEvent::AttemptStarted(1),
+                ReconnectEvent::AttemptFailed(1),
+                ReconnectEvent::AttemptScheduled(2, Duration::from_millis(2)),
+                ReconnectEvent::AttemptStarted(2),
+                ReconnectEvent::AttemptFailed(2),
+                ReconnectEvent::GaveUp(2),
+            ]
+        );
+        let causes: Vec<StateCause> = std::iter::from_fn(|| watcher.try_recv())
+            .map(|transition| transition.cause)
+            .collect();
+        assert_eq!(
+            causes,
+            vec![
+                StateCause::ConnectionLost,
+                StateCause::ReconnectAttempt(1),
+                StateCause::Error(SessionError::ConnectionRefused),
+                StateCause::ReconnectAttempt(2),
+                StateCause::Error(SessionError::ConnectionRefused),
+            ]
+        );
+
+        // The session can still be connected by hand.
+        opentok::mock::set_connect_error(session_id, None);
+        session.connect("").unwrap();
+        opentok::mock::flush();
+        assert!(session.connection().is_some());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_reconnect_does_not_keep_alive() {
+        use opentok::reconnect::ReconnectPolicy;
+        use std::sync::mpsc::TryRecvError;
+
+        opentok::init().unwrap();
+        let session_id = "mock-reconnect-does-not-keep-alive";
+
+        let peer = Session::new("", session_id, SessionCallbacks::builder().build()).unwrap();
+        peer.connect("").unwrap();
+        let peer_publisher = Publisher::new("peer", None, PublisherCallbacks::builder().build());
+        peer.publish(&peer_publisher).unwrap();
+        opentok::mock::flush();
+
+        let (stream_sender, stream_receiver) = mpsc::channel();
+        let stream_sender = Mutex::new(stream_sender);
+        let session = Session::new(
+            "",
+            session_id,
+            SessionCallbacks::builder()
+                .on_stream_received(move |_, stream| {
+                    let _ = stream_sender.lock().unwrap().send(stream);
+                })
+                .build(),
+        )
+        .unwrap();
+        session.set_reconnect_policy(Some(ReconnectPolicy::default()));
+        session.connect("").unwrap();
+        opentok::mock::flush();
+
+        // The callbacks hold the senders, so the receivers disconnect once
+        // the publisher and the subscriber are deleted.
+        let (publisher_sender, publisher_receiver) = mpsc::channel::<()>();
+        let publisher_sender = Mutex::new(publisher_sender);
+        let publisher = Publisher::new(
+            "publisher",
+            None,
+            PublisherCallbacks::builder()
+                .on_stream_created(move |_, _| {
+                    let _ = publisher_sender.lock().unwrap().send(());
+                })
+                .build(),
+        );
+        session.publish(&publisher).unwrap();
+        opentok::mock::flush();
+
+        let (subscriber_sender, subscriber_receiver) = mpsc::channel::<()>();
+        let subscriber_sender = Mutex::new(subscriber_sender);
+        let subscriber = Subscriber::new(
+            SubscriberCallbacks::builder()
+                .on_connected(move |_, _| {
+                    let _ = subscriber_sender.lock().unwrap().send(());
+                })
+                .build(),
+        );
+        subscriber
+            .set_stream(stream_receiver.recv().unwrap())
+            .unwrap();
+        session.subscribe(&subscriber).unwrap();
+        opentok::mock::flush();
+
+        assert_eq!(publisher_receiver.recv(), Ok(()));
+        assert_eq!(subscriber_receiver.recv(), Ok(()));
+        drop(publisher);
+        drop(subscriber);
+        assert_eq!(
+            publisher_receiver.try_recv(),
+            Err(TryRecvError::Disconnected)
+        );
+        assert_eq!(
+            subscriber_receiver.try_recv(),
+            Err(TryRecvError::Disconnected)
+        );
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_session_state() {
+        use opentok::mock::{Fault, Script};
+        use opentok::session::{ConnectionState, StateCause};
+        use std::time::Duration;
+
+        opentok::init().unwrap();
+        let session_id = "mock-session-state";
+        let session = Session::new("", session_id, SessionCallbacks::builder().build()).unwrap();
+        let watcher = session.watch_state();
+        assert_eq!(session.state(), ConnectionState::Disconnected);
+
+        session.connect("").unwrap();
+        opentok::mock::flush();
+        assert_eq!(watcher.state(), ConnectionState::Connected);
+        Script::new()
+            .at(
+                Duration::from_millis(0),
+                &session,
+                Fault::ConnectionInterrupted,
+            )
+            .at(Duration::from_millis(0), &session, Fault::Reconnected)
+            .at(Duration::from_millis(0), &session, Fault::Disconnected)
+            .play();
+        opentok::mock::set_connect_error(session_id, Some(SessionError::ConnectionRefused));
+        session.connect("").unwrap();
+        opentok::mock::flush();
+        opentok::mock::set_connect_error(session_id, None);
+        session.connect("").unwrap();
+        opentok::mock::flush();
+        session.disconnect().unwrap();
+        opentok::mock::flush();
+
+        use ConnectionState::*;
+        let expected = vec![
+            (Disconnected, Connecting, StateCause::ConnectRequested),
+            (Connecting, Connected, StateCause::Connected),
+            (Connected, Reconnecting, StateCause::ConnectionInterrupted),
+            (Reconnecting, Connected, StateCause::Reconnected),
+            (Connected, Disconnected, StateCause::ConnectionLost),
+            (Disconnected, Connecting, StateCause::ConnectRequested),
+            (
+                Connecting,
+                Disconnected,
+                StateCause::Error(SessionError::ConnectionRefused),
+            ),
+            (Disconnected, Connecting, StateCause::ConnectRequested),
+            (Connecting, Connected, StateCause::Connected),
+            (Connected, Disconnecting, StateCause::DisconnectRequested),
+            (Disconnecting, Disconnected, StateCause::Disconnected),
+        ];
+        let mut time = None;
+        for (from, to, cause) in expected {
+            let transition = watcher.try_recv().unwrap();
+            assert_eq!(
+                (transition.from, transition.to, transition.cause),
+                (from, to, cause)
+            );
+            assert!(time <= Some(transition.time));
+            time = Some(transition.time);
+        }
+        assert!(watcher.try_recv().is_none());
+        assert_eq!(session.state(), Disconnected);
+
+        drop(session);
+        assert!(watcher.recv().is_none());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_mock_roster() {
+        use opentok::roster::{RosterChange, RosterWatcher};
+
+        fn changes(watcher: &RosterWatcher) -> Vec<String> {
+            std::iter::from_fn(|| watcher.try_recv())
+                .map(|change| match change {
+                    RosterChange::ParticipantJoined(connection) => {
+                        format!("joined {}", connection.id())
+                    }
+                    RosterChange::ParticipantLeft(connection) => {
+                        format!("left {}", connection.id())
+                    }
+                    RosterChange::StreamAdded(info) => format!("added {}", info.id()),
+                    RosterChange::StreamRemoved(info) => format!("removed {}", info.id()),
+                    RosterChange::StreamChanged(info) => format!(
+                        "changed {} {} {} {}x{} {:?}",
+                        info.id(),
+                        info.has_audio,
+                        info.has_video,
+                        info.width,
+                        info.height,
+                        info.video_type
+                    ),
+                })
+                .collect()
+        }
+
+        opentok::init().unwrap();
+        let session_id = "mock-roster";
+        let session = Session::new("", session_id, SessionCallbacks::builder().build()).unwrap();
+        session.connect("").unwrap();
+        let suppressed = Session::with_settings(
+            "",
+            session_id,
+            SessionCallbacks::builder().build(),
+            &SessionSettings::builder()
+                .connection_events_suppressed(true)
+                .build(),
+        )
+        .unwrap();
+        suppressed.connect("").unwrap();
+        opentok::mock::flush();
+        let roster = session.roster();
+        let watcher = roster.watch();
+        let suppressed_watcher = suppressed.roster().watch();
+        assert_eq!(roster.participants().len(), 1);
+        assert!(suppressed.roster().participants().is_empty());
+
+        let peer = Session::new("", session_id, SessionCallbacks::builder().build()).unwrap();
+        peer.connect("").unwrap();
+        opentok::mock::flush();
+        let connection = peer.connection().unwrap().id();
+        assert_eq!(changes(&watcher), vec![format!("joined {}", connection)]);
+        assert!(changes(&suppressed_watcher).is_empty());
+
+        let publisher = Publisher::new("peer", None, PublisherCallbacks::builder().build());
+        peer.publish(&publisher).unwrap();
+        opentok::mock::flush();
+        let stream = publisher.stream().unwrap().id();
+        assert_eq!(changes(&watcher), vec![format!("added {}", stream)]);
+        assert_eq!(
+            changes(&suppressed_watcher),
+            vec![
+                format!("joined {}", connection),
+                format!("added {}", stream)
+            ]
+        );
+        let participant = roster.participant(&connection).unwrap();
+        assert_eq!(participant.streams.len(), 1);
+        assert_eq!(participant.streams[0].id(), stream);
+
+        publisher.toggle_video(false).unwrap();
+        opentok::mock::flush();
+        publisher.set_video_type(VideoType::Screen).unwrap();
+        opentok::mock::flush();
+        assert_eq!(
+            changes(&watcher),
+            vec![
+                format!("changed {} true false 640x480 Camera", stream),
+                format!("changed {} true false 640x480 Screen", stream),
+            ]
+        );
+        let info = suppressed.roster().stream(&stream).unwrap();
+        assert!(info.has_audio && !info.has_video);
+        assert_eq!(roster.streams().len(), 1);
+
+        peer.unpublish(&publisher).unwrap();
+        opentok::mock::flush();
+        assert_eq!(changes(&watcher), vec![format!("removed {}", stream)]);
+        assert_eq!(
+            changes(&suppressed_watcher)[2..],
+            [
+                format!("removed {}", stream),
+                format!("left {}", connection)
+            ]
+        );
+        assert!(roster.streams().is_empty());
+        assert!(suppressed.roster().participants().is_empty());
+
+        peer.disconnect().unwrap();
+        opentok::mock::flush();
+        assert_eq!(changes(&watcher), vec![format!("left {}", connection)]);
+        assert_eq!(roster.participants().len(), 1);
+
+        session.disconnect().unwrap();
+        opentok::mock::flush();
+        assert_eq!(changes(&watcher).len(), 1);
+        assert!(roster.participants().is_empty());
+    }
 }
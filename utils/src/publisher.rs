@@ -4,12 +4,50 @@ use crate::common::Credentials;
 use opentok::audio_device::{AudioDevice, AudioDeviceSettings};
 use opentok::publisher::{Publisher as OpenTokPublisher, PublisherCallbacks};
 use opentok::session::{Session, SessionCallbacks};
-use opentok::video_capturer::{VideoCapturer, VideoCapturerCallbacks, VideoCapturerSettings};
+use opentok::video_capturer::{VideoCapturer, VideoCapturerSettings};
 use opentok::video_frame::VideoFrame;
+use opentok::video_source::VideoSource;
+use opentok::{OtcError, OtcResult};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
+/// Publishes the frames of a GStreamer test source.
+#[derive(Default)]
+struct TestVideoSource {
+    settings: VideoCapturerSettings,
+    capturer: Option<capturer::Capturer>,
+}
+
+impl VideoSource for TestVideoSource {
+    fn settings(&self) -> VideoCapturerSettings {
+        self.settings
+    }
+
+    fn start(&mut self) -> OtcResult {
+        let capturer =
+            capturer::Capturer::new(&self.settings).map_err(|_| OtcError::VideoCaptureFailed)?;
+        self.capturer = Some(capturer);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> OtcResult {
+        self.capturer = None;
+        Ok(())
+    }
+
+    fn frame(&mut self) -> Option<VideoFrame> {
+        let buffer = self.capturer.as_ref()?.pull_buffer().ok()?;
+        VideoFrame::from_buffer(
+            self.settings.format,
+            self.settings.width,
+            self.settings.height,
+            (*buffer).as_ref().to_vec(),
+        )
+        .ok()
+    }
+}
+
 type Callback = Box<dyn Fn(&Publisher, String) + Send + Sync + 'static>;
 
 #[derive(Clone)]
@@ -54,44 +92,7 @@ impl Publisher {
             }
         });
 
-        let render_thread_running = Arc::new(AtomicBool::new(false));
-        let render_thread_running_ = render_thread_running.clone();
-        let render_thread_running__ = render_thread_running.clone();
-        let video_capturer_callbacks = VideoCapturerCallbacks::builder()
-            .start(move |video_capturer| {
-                let video_capturer = video_capturer.clone();
-                render_thread_running.store(true, Ordering::Relaxed);
-                let render_thread_running_ = render_thread_running.clone();
-                std::thread::spawn(move || {
-                    let settings = VideoCapturerSettings::default();
-                    let capturer = capturer::Capturer::new(&settings).unwrap();
-                    let mut buf: Vec<u8> = vec![];
-                    loop {
-                        if !render_thread_running_.load(Ordering::Relaxed) {
-                            break;
-                        }
-                        if let Ok(buffer) = capturer.pull_buffer() {
-                            buf.extend_from_slice((*buffer).as_ref());
-                            let frame = VideoFrame::new(
-                                settings.format,
-                                settings.width,
-                                settings.height,
-                                buf.clone(),
-                            );
-                            video_capturer.provide_frame(0, &frame).unwrap();
-                            buf.clear();
-                        }
-                        std::thread::sleep(std::time::Duration::from_micros(30 * 1_000));
-                    }
-                });
-                Ok(())
-            })
-            .stop(move |_| {
-                render_thread_running_.store(false, Ordering::Relaxed);
-                Ok(())
-            })
-            .build();
-        let video_capturer = VideoCapturer::new(Default::default(), video_capturer_callbacks);
+        let video_capturer = VideoCapturer::from_source(TestVideoSource::default())?;
 
         let on_stream_created = self.on_stream_created.clone();
         let this = self.clone();
@@ -150,7 +151,6 @@ impl Publisher {
         self.main_loop.run();
 
         audio_capture_thread_running_.store(false, Ordering::Relaxed);
-        render_thread_running__.store(false, Ordering::Relaxed);
 
         publisher.lock().unwrap().unpublish().unwrap();
 
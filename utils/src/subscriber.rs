@@ -4,7 +4,6 @@ use crate::renderer;
 use opentok::audio_device::AudioDevice;
 use opentok::session::{Session, SessionCallbacks};
 use opentok::subscriber::{Subscriber as OpenTokSubscriber, SubscriberCallbacks};
-use opentok::video_frame::FramePlane;
 use std::sync::{Arc, Mutex};
 
 pub struct Subscriber {
@@ -51,35 +50,21 @@ impl Subscriber {
                 let width = frame.get_width().unwrap() as u32;
                 let height = frame.get_height().unwrap() as u32;
 
-                let get_plane_size = |format, width: u32, height: u32| match format {
-                    FramePlane::Y => width * height,
-                    FramePlane::U | FramePlane::V => {
-                        let pw = (width + 1) >> 1;
-                        let ph = (height + 1) >> 1;
-                        pw * ph
-                    }
-                    _ => unimplemented!(),
-                };
-
-                let offset = [
-                    0,
-                    get_plane_size(FramePlane::Y, width, height) as usize,
-                    get_plane_size(FramePlane::Y, width, height) as usize
-                        + get_plane_size(FramePlane::U, width, height) as usize,
-                ];
-
-                let stride = [
-                    frame.get_plane_stride(FramePlane::Y).unwrap(),
-                    frame.get_plane_stride(FramePlane::U).unwrap(),
-                    frame.get_plane_stride(FramePlane::V).unwrap(),
-                ];
+                let buffer = frame.get_buffer().unwrap();
+                let planes = frame.planes().unwrap();
+                let offset: Vec<usize> = planes
+                    .iter()
+                    .map(|plane| plane.data.as_ptr() as usize - buffer.as_ptr() as usize)
+                    .collect();
+                let stride: Vec<i32> = planes.iter().map(|plane| plane.stride as i32).collect();
+
                 renderer_
                     .lock()
                     .unwrap()
                     .as_ref()
                     .unwrap()
                     .push_video_buffer(
-                        frame.get_buffer().unwrap(),
+                        buffer,
                         frame.get_format().unwrap(),
                         width,
                         height,